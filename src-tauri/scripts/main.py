@@ -1,55 +1,75 @@
 import logging
-from fastapi import FastAPI, HTTPException
+from fastapi import FastAPI, HTTPException, Request
 from fastapi.middleware.cors import CORSMiddleware
-from fastapi.responses import StreamingResponse
-from pydantic import BaseModel, ConfigDict
-from huggingface_hub import hf_hub_download, list_repo_files
+from fastapi.responses import JSONResponse, StreamingResponse
+from pydantic import BaseModel
+from typing import Optional
 from ctransformers import AutoModelForCausalLM
 import uvicorn
 from sys import argv
+import hmac
 import json
+import os
+import secrets
+import sys
+import threading
 
 global reload_state
-if "dev" not in argv:
-    import ensure_exit  # type: ignore
-
-    reload_state = False
-else:
-    reload_state = True
+reload_state = "dev" in argv
 
 # Set up logging
 logging.basicConfig(level=logging.DEBUG, 
                     format='%(asctime)s - %(levelname)s - %(message)s')
 
+# The app picks a free port and a random token for each start and passes
+# them in the environment. Running by hand falls back to port 8000 and a
+# token printed at startup.
+port = int(os.environ.get("SQLMAN_AI_PORT", "8000"))
+api_token = os.environ.get("SQLMAN_AI_TOKEN")
+if not api_token:
+    api_token = secrets.token_hex(32)
+    print(f"SQLMAN_AI_TOKEN not set, using generated token: {api_token}")
+
 app = FastAPI()
 
-# Add CORS middleware
+# Every request must carry the token, so other local processes and web
+# pages can't drive the model. CORS preflights carry no headers and are
+# answered by the CORS middleware.
+@app.middleware("http")
+async def require_token(request: Request, call_next):
+    if request.method != "OPTIONS":
+        header = request.headers.get("authorization", "")
+        scheme, _, token = header.partition(" ")
+        if scheme.lower() != "bearer" or not hmac.compare_digest(token, api_token):
+            return JSONResponse(status_code=401, content={"detail": "Unauthorized"})
+    return await call_next(request)
+
+# Add CORS middleware. Added after the token check, so it runs first and
+# preflights never reach it.
 app.add_middleware(
     CORSMiddleware,
-    allow_origins=["http://localhost","http://localhost:*","http://127.0.0.1:*", "tauri://localhost", "*"],
+    allow_origins=["tauri://localhost", "http://tauri.localhost", "https://tauri.localhost", "http://localhost:3000"],
     allow_credentials=True,
     allow_methods=["*"],
-    allow_headers=["*"],
+    allow_headers=["Authorization", "Content-Type"],
 )
 
+# A schema sent with the prompt is used for that request only
 class SQLRequest(BaseModel):
     prompt: str
+    schema: Optional[dict] = None
 
+# Models are downloaded or registered by the app, which passes the path of
+# the local file and the architecture from its GGUF header
 class ModelRequest(BaseModel):
-    model_name: str
-    model_config = ConfigDict(protected_namespaces=())
-
-class TokenRepoRequest(BaseModel):
-    token: str
-    repo: str
+    path: str
+    architecture: Optional[str] = None
 
 class SchemaRequest(BaseModel):
     schema: dict
 
 # Global variables
 current_model = None
-hf_token = None
-repo_id = None
 table_schemas = {}
 
 # Improved GPU detection
@@ -64,17 +84,21 @@ try:
 except ImportError:
     logging.debug("PyTorch not installed, defaulting to CPU")
 
-def get_token_and_repo():
-    if hf_token is None or repo_id is None:
-        raise HTTPException(status_code=400, detail="Hugging Face token or repo not set")
-    return hf_token, repo_id
+# GGUF architectures ctransformers has a model type for. Others are tried
+# with the llama backend, which most GGUF models are built on.
+MODEL_TYPES = {"llama", "falcon", "mpt", "gpt2", "gptj", "gpt_neox", "starcoder", "replit"}
 
-def generate_response(prompt):
+def model_type_for(architecture):
+    if architecture in MODEL_TYPES:
+        return architecture
+    return "llama"
+
+def generate_response(prompt, schema=None):
     if current_model is None:
         raise HTTPException(status_code=400, detail="No model loaded")
     
     logging.debug(f"Generating response for prompt: {prompt}")
-    schema_info = json.dumps(table_schemas, indent=2)
+    schema_info = json.dumps(table_schemas if schema is None else schema, indent=2)
     full_prompt = f"""
     Table Schemas:
     {schema_info}
@@ -87,13 +111,6 @@ def generate_response(prompt):
     logging.debug(f"Generated SQL:\n{response}")
     return response.strip()
 
-@app.post("/set_token_and_repo")
-async def set_token_and_repo(request: TokenRepoRequest):
-    global hf_token, repo_id
-    hf_token = request.token
-    repo_id = request.repo
-    return {"message": f"Token and repo set. Repo: {repo_id}"}
-
 @app.post("/set_schema")
 async def set_schema(request: SchemaRequest):
     global table_schemas
@@ -103,42 +120,26 @@ async def set_schema(request: SchemaRequest):
 @app.post("/generate_sql")
 async def generate_sql(request: SQLRequest):
     try:
-        sql = generate_response(request.prompt)
+        sql = generate_response(request.prompt, request.schema)
         return sql
     except Exception as e:
         logging.error(f"Error: {str(e)}")
         raise HTTPException(status_code=500, detail=str(e))
 
-@app.get("/list_models")
-async def list_models():
-    token, repo = get_token_and_repo()
-    try:
-        files = list_repo_files(repo, token=token)
-        models = [file for file in files if file.endswith('.gguf')]
-        return {"models": models}
-    except Exception as e:
-        logging.error(f"Error listing models: {str(e)}")
-        raise HTTPException(status_code=500, detail=str(e))
-
 @app.post("/load_model")
 async def load_model_endpoint(request: ModelRequest):
-    token, repo = get_token_and_repo()
     global current_model
+    local_path = request.path
+    if not os.path.isfile(local_path):
+        raise HTTPException(status_code=404, detail=f"Model file not found: {local_path}")
+    model_type = model_type_for(request.architecture)
+    logging.debug(f"Loading {local_path} as {model_type}")
     try:
-        local_path = hf_hub_download(
-                repo_id=repo,
-                token=token,
-                filename=request.model_name,
-                local_files_only=False,
-                resume_download=True,
-                force_download=False,
-                legacy_cache_layout=False,
-        )
         if gpu_available:
-            current_model = AutoModelForCausalLM.from_pretrained(local_path, model_type="llama", gpu_layers=50)
+            current_model = AutoModelForCausalLM.from_pretrained(local_path, model_type=model_type, gpu_layers=50)
             return {"message": "Model loaded with GPU support"}
         else:
-            current_model = AutoModelForCausalLM.from_pretrained(local_path, model_type="llama")
+            current_model = AutoModelForCausalLM.from_pretrained(local_path, model_type=model_type)
             return {"message": "Model loaded on CPU"}
     except Exception as e:
         raise HTTPException(status_code=500, detail=str(e))
@@ -156,7 +157,33 @@ async def unload_model():
 async def ping():
     return {"message": "Pong"}
 
+server = None
+
+# Asks uvicorn to finish the requests in flight and exit. The app sends this
+# before it falls back to terminating the process.
+@app.post("/shutdown")
+async def shutdown():
+    if server is not None:
+        server.should_exit = True
+    return {"message": "Shutting down"}
+
+def exit_when_parent_exits():
+    # The app holds the write end of stdin for as long as it runs, so EOF
+    # means it has exited, even if it crashed without stopping us
+    sys.stdin.read()
+    logging.debug("App exited, shutting down")
+    if server is not None:
+        server.should_exit = True
+    # Give uvicorn a moment to stop, then make sure nothing outlives the app
+    threading.Timer(5, lambda: os._exit(0)).start()
+
 if __name__ == "__main__":
-    uvicorn.run(app, host="127.0.0.1", port=8000, reload=reload_state)
+    if reload_state:
+        uvicorn.run("main:app", host="127.0.0.1", port=port, reload=True)
+    else:
+        if os.environ.get("SQLMAN_AI_WATCH_STDIN") == "1":
+            threading.Thread(target=exit_when_parent_exits, daemon=True).start()
+        server = uvicorn.Server(uvicorn.Config(app, host="127.0.0.1", port=port))
+        server.run()
 
 logging.debug("FastAPI SQLCoder service is ready.")
\ No newline at end of file
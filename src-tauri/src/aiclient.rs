@@ -0,0 +1,393 @@
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+
+use crate::engine::{Engine, Session};
+use crate::gguf;
+use crate::localmodels::LocalModelStore;
+use crate::modelmanager::ModelManager;
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::runtime::run_blocking;
+use crate::schema::{self, ConstraintKind};
+use crate::sidecar::{Endpoint, Sidecar, SidecarState};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Generation runs on the CPU on most machines, and loading reads a model of
+// several gigabytes into memory
+const GENERATE_TIMEOUT: Duration = Duration::from_secs(300);
+const LOAD_MODEL_TIMEOUT: Duration = Duration::from_secs(600);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// Attempts for a request the service never received (connection refused,
+// or 503 while it starts up), with a growing delay in between
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+// How long a request waits for a service that is starting, or restarting
+// after a crash: the sidecar's two minute readiness timeout plus the longest
+// restart backoff
+const STARTUP_WAIT: Duration = Duration::from_secs(160);
+const STARTUP_POLL: Duration = Duration::from_millis(250);
+
+// Tables described to the model when the caller doesn't name any
+const SCHEMA_TABLE_LIMIT: usize = 50;
+
+#[derive(Serialize)]
+struct GenerateSqlRequest<'a> {
+    prompt: &'a str,
+    // Used for this request only; the schema set with `ai_set_schema` when
+    // absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<&'a Map<String, Value>>,
+}
+
+#[derive(Serialize)]
+struct SchemaRequest<'a> {
+    schema: &'a Map<String, Value>,
+}
+
+#[derive(Serialize)]
+struct LoadModelRequest<'a> {
+    path: &'a str,
+    // From the GGUF header, so the service can pick the model type
+    architecture: Option<&'a str>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MessageResponse {
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ModelList {
+    pub models: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct GeneratedSql {
+    pub sql: String,
+    // Tables whose schema was sent along with the prompt
+    pub tables: Vec<String>,
+}
+
+// FastAPI's error body
+#[derive(Deserialize)]
+struct ErrorResponse {
+    detail: Value,
+}
+
+// HTTP client for the SQLCoder service, so the webview never talks to it
+// directly.
+pub struct AiClient {
+    http: reqwest::Client,
+}
+
+impl AiClient {
+    pub fn new() -> Self {
+        AiClient {
+            http: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    async fn call<B: Serialize, R: DeserializeOwned>(
+        &self,
+        sidecar: &Sidecar,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+        timeout: Duration,
+    ) -> Result<R, String> {
+        let mut attempt = 1;
+        loop {
+            // Looked up per attempt: a restarted service has a new port
+            let endpoint = wait_endpoint(sidecar).await?;
+            let mut request = self
+                .http
+                .request(method.clone(), format!("{}{}", endpoint.url, path))
+                .bearer_auth(&endpoint.token)
+                .timeout(timeout);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+
+            let retry = match request.send().await {
+                Ok(response) if response.status().is_success() => {
+                    return response
+                        .json::<R>()
+                        .await
+                        .map_err(|e| format!("Unexpected response from the AI service: {}", e));
+                }
+                Ok(response) if response.status() == StatusCode::SERVICE_UNAVAILABLE => {
+                    error_message(response).await
+                }
+                Ok(response) => return Err(error_message(response).await),
+                Err(e) if e.is_timeout() => {
+                    return Err(format!(
+                        "The AI service did not answer {} within {}s",
+                        path,
+                        timeout.as_secs()
+                    ));
+                }
+                Err(e) if e.is_connect() => format!("Failed to reach the AI service: {}", e),
+                Err(e) => return Err(format!("AI service request failed: {}", e)),
+            };
+            if attempt == ATTEMPTS {
+                return Err(retry);
+            }
+            tokio::time::sleep(RETRY_DELAY * attempt).await;
+            attempt += 1;
+        }
+    }
+}
+
+// The service's endpoint, once a start or supervised restart in progress
+// has finished. A stopped service, or one the supervisor gave up on, fails
+// straight away.
+async fn wait_endpoint(sidecar: &Sidecar) -> Result<Endpoint, String> {
+    let deadline = Instant::now() + STARTUP_WAIT;
+    loop {
+        let status = sidecar.status()?;
+        let coming_up = status.state == SidecarState::Starting
+            || (status.state == SidecarState::Crashed && status.supervised);
+        if !coming_up || Instant::now() >= deadline {
+            return sidecar.endpoint();
+        }
+        tokio::time::sleep(STARTUP_POLL).await;
+    }
+}
+
+// The `detail` FastAPI puts in error responses, or the status.
+async fn error_message(response: reqwest::Response) -> String {
+    let status = response.status();
+    match response.json::<ErrorResponse>().await {
+        Ok(ErrorResponse {
+            detail: Value::String(detail),
+        }) => detail,
+        Ok(ErrorResponse { detail }) => detail.to_string(),
+        Err(_) => format!("The AI service answered {}", status),
+    }
+}
+
+// Describes `tables` (or the first tables of the current database) the way
+// main.py lays them out in the prompt: columns with types, the primary key
+// and foreign keys.
+fn schema_context(
+    session: Session,
+    tables: Option<Vec<String>>,
+) -> Result<Map<String, Value>, String> {
+    let mut conn = session.connect()?;
+    let tables = match tables {
+        Some(tables) => tables,
+        None => {
+            let current = schema::current_schema(&mut conn)?;
+            let mut tables = schema::list_tables(&mut conn, &current)?;
+            tables.truncate(SCHEMA_TABLE_LIMIT);
+            tables
+        }
+    };
+
+    let mut context = Map::new();
+    for table in tables {
+        let table = schema::table_schema(&mut conn, &table)?;
+        let columns: Vec<Value> = table
+            .columns
+            .iter()
+            .map(|c| {
+                json!({
+                    "name": c.name,
+                    "type": c.column_type,
+                    "nullable": c.nullable,
+                })
+            })
+            .collect();
+        let foreign_keys: Vec<&str> = table
+            .constraints
+            .iter()
+            .filter(|c| c.kind == ConstraintKind::ForeignKey)
+            .map(|c| c.definition.as_str())
+            .collect();
+        context.insert(
+            table.name.clone(),
+            json!({
+                "columns": columns,
+                "primary_key": table.primary_key,
+                "foreign_keys": foreign_keys,
+            }),
+        );
+    }
+    Ok(context)
+}
+
+// Checks `path` is a readable GGUF file and has the service load it.
+async fn load_model_file(
+    client: &AiClient,
+    sidecar: &Sidecar,
+    path: PathBuf,
+) -> Result<MessageResponse, String> {
+    let info = {
+        let path = path.clone();
+        run_blocking(move || gguf::inspect(&path)).await?
+    };
+    let path = path.to_string_lossy();
+    client
+        .call(
+            sidecar,
+            Method::POST,
+            "/load_model",
+            Some(&LoadModelRequest {
+                path: &path,
+                architecture: info.architecture.as_deref(),
+            }),
+            LOAD_MODEL_TIMEOUT,
+        )
+        .await
+}
+
+// Models tend to continue past the statement with an explanation in a
+// fenced block; only what comes before it is kept.
+fn clean_sql(response: &str) -> String {
+    response
+        .split("```")
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+// Models are listed and downloaded from `repo` by the model manager; the
+// service only ever sees local paths.
+#[tauri::command]
+pub fn ai_set_repository(
+    token: String,
+    repo: String,
+    models: State<'_, ModelManager>,
+) -> Result<MessageResponse, String> {
+    models.set_repository(&repo, &token)?;
+    Ok(MessageResponse {
+        message: format!("Token and repo set. Repo: {}", repo.trim()),
+    })
+}
+
+// Generates SQL for `prompt`. With an engine, the schema of `tables` on its
+// current connection (or of the first tables in the database) is sent
+// along, so the model sees the real column names; the schema set with
+// `ai_set_schema` is left as it is for later requests.
+#[tauri::command]
+pub async fn ai_generate_sql(
+    prompt: String,
+    engine: Option<Engine>,
+    tables: Option<Vec<String>>,
+    client: State<'_, AiClient>,
+    sidecar: State<'_, Sidecar>,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+) -> Result<GeneratedSql, String> {
+    let context = match engine {
+        Some(engine) => {
+            let session = Session::open(engine, &mysql_state, &pg_state)?;
+            Some(run_blocking(move || schema_context(session, tables)).await?)
+        }
+        None => None,
+    };
+    let described = context
+        .as_ref()
+        .map(|context| context.keys().cloned().collect())
+        .unwrap_or_default();
+
+    let response: String = client
+        .call(
+            &sidecar,
+            Method::POST,
+            "/generate_sql",
+            Some(&GenerateSqlRequest {
+                prompt: &prompt,
+                schema: context.as_ref(),
+            }),
+            GENERATE_TIMEOUT,
+        )
+        .await?;
+    Ok(GeneratedSql {
+        sql: clean_sql(&response),
+        tables: described,
+    })
+}
+
+#[tauri::command]
+pub async fn ai_set_schema(
+    schema: Map<String, Value>,
+    client: State<'_, AiClient>,
+    sidecar: State<'_, Sidecar>,
+) -> Result<MessageResponse, String> {
+    client
+        .call(
+            &sidecar,
+            Method::POST,
+            "/set_schema",
+            Some(&SchemaRequest { schema: &schema }),
+            REQUEST_TIMEOUT,
+        )
+        .await
+}
+
+#[tauri::command]
+pub async fn ai_list_models(models: State<'_, ModelManager>) -> Result<ModelList, String> {
+    let remote = models.list_remote().await?;
+    Ok(ModelList {
+        models: remote.into_iter().map(|model| model.file).collect(),
+    })
+}
+
+// Downloads `model_name` from the repository if it isn't cached yet, with
+// progress on `model-download-progress`, then loads it into the service.
+#[tauri::command]
+pub async fn ai_load_model(
+    model_name: String,
+    app: AppHandle,
+    client: State<'_, AiClient>,
+    sidecar: State<'_, Sidecar>,
+    models: State<'_, ModelManager>,
+) -> Result<MessageResponse, String> {
+    let path = models.download(&app, &model_name).await?;
+    load_model_file(&client, &sidecar, path).await
+}
+
+// Loads a model registered with `register_local_model`, without going
+// through Hugging Face.
+#[tauri::command]
+pub async fn ai_load_local_model(
+    path: String,
+    client: State<'_, AiClient>,
+    sidecar: State<'_, Sidecar>,
+    local_models: State<'_, LocalModelStore>,
+) -> Result<MessageResponse, String> {
+    let path = Path::new(&path);
+    if !local_models.contains(path)? {
+        return Err(format!("{} is not a registered model", path.display()));
+    }
+    load_model_file(&client, &sidecar, path.to_path_buf()).await
+}
+
+#[tauri::command]
+pub async fn ai_unload_model(
+    client: State<'_, AiClient>,
+    sidecar: State<'_, Sidecar>,
+) -> Result<MessageResponse, String> {
+    client
+        .call::<(), _>(
+            &sidecar,
+            Method::POST,
+            "/unload_model",
+            None,
+            REQUEST_TIMEOUT,
+        )
+        .await
+}
@@ -0,0 +1,814 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::iter::Peekable;
+use std::sync::mpsc;
+use std::thread;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::dialect;
+use crate::engine::{Connection, Engine, Session};
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::resultset::{Cell, ColumnInfo, RowSink};
+use crate::runtime::run_blocking;
+use crate::schema::{self, ColumnDef};
+
+// Target rows read ahead of the merge
+const MERGE_BUFFER: usize = 1000;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DataEndpoint {
+    // Another server; the engine's current connection when absent
+    connection_string: Option<String>,
+    // Optionally schema-qualified
+    table: String,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct DataDiffOptions {
+    // Rows per checksummed key range
+    chunk_size: u64,
+    // Columns to compare; every column both tables have when absent
+    columns: Option<Vec<String>>,
+    // Differences listed in the result; all of them are counted and scripted
+    max_listed: usize,
+    // Where to write the SQL that makes the target match the source
+    script_path: Option<String>,
+}
+
+impl Default for DataDiffOptions {
+    fn default() -> Self {
+        DataDiffOptions {
+            chunk_size: 10_000,
+            columns: None,
+            max_listed: 1000,
+            script_path: None,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum RowStatus {
+    // In the source only
+    Missing,
+    // In the target only
+    Extra,
+    Changed,
+}
+
+#[derive(Serialize)]
+pub struct RowDifference {
+    pub status: RowStatus,
+    pub key: Map<String, Value>,
+    // The compared columns whose values differ, for changed rows
+    pub columns: Vec<String>,
+}
+
+#[derive(Serialize, Default)]
+pub struct DataDiffSummary {
+    pub key: Vec<String>,
+    pub columns: Vec<String>,
+    pub chunks: u64,
+    // Chunks whose checksums differed and whose rows were compared
+    pub differing_chunks: u64,
+    pub source_rows: u64,
+    pub target_rows: u64,
+    pub missing: u64,
+    pub extra: u64,
+    pub changed: u64,
+    pub differences: Vec<RowDifference>,
+    // More differences were found than are listed
+    pub truncated: bool,
+    pub script_path: Option<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DataDiffProgress {
+    table: String,
+    chunks: u64,
+    rows: u64,
+    done: bool,
+}
+
+// Compares the rows of two tables of one engine, matched by primary key.
+// The key space is split into ranges of `chunk_size` source rows; each range
+// is checksummed on both servers and only the rows of ranges that differ
+// are read and compared.
+#[tauri::command]
+pub async fn diff_table_data(
+    app: AppHandle,
+    engine: Engine,
+    source: DataEndpoint,
+    target: DataEndpoint,
+    options: Option<DataDiffOptions>,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+) -> Result<DataDiffSummary, String> {
+    let options = options.unwrap_or_default();
+    if source.table.trim().is_empty() || target.table.trim().is_empty() {
+        return Err("Both table names are required".to_string());
+    }
+    let current = |endpoint: &DataEndpoint| match endpoint.connection_string {
+        Some(_) => Ok(None),
+        None => Session::open(engine, &mysql_state, &pg_state).map(Some),
+    };
+    let source_session = current(&source)?;
+    let target_session = current(&target)?;
+
+    run_blocking(move || {
+        let mut source_conn = connect(engine, &source, source_session)?;
+        let mut target_conn = connect(engine, &target, target_session)?;
+        let mut diff = DataDiff::new(
+            &app,
+            &mut source_conn,
+            &mut target_conn,
+            &source.table,
+            &target.table,
+            &options,
+        )?;
+        diff.run(&mut source_conn, &mut target_conn)?;
+        Ok(diff.summary)
+    })
+    .await
+}
+
+fn connect(
+    engine: Engine,
+    endpoint: &DataEndpoint,
+    session: Option<Session>,
+) -> Result<Connection, String> {
+    match (session, endpoint.connection_string.as_deref()) {
+        (Some(session), _) => session.connect(),
+        (None, Some(connection_string)) => {
+            Session::from_connection_string(engine, connection_string, None)?.connect()
+        }
+        (None, None) => Err("Database not connected".to_string()),
+    }
+}
+
+struct DataDiff<'a> {
+    app: &'a AppHandle,
+    engine: Engine,
+    source_table: String,
+    target_table: String,
+    // Key columns first, then the compared columns
+    columns: Vec<ColumnDef>,
+    key_len: usize,
+    // Columns both tables have that aren't compared, read from the source
+    // so missing rows are inserted whole
+    other_columns: Vec<ColumnDef>,
+    options: &'a DataDiffOptions,
+    script: Option<BufWriter<File>>,
+    summary: DataDiffSummary,
+}
+
+impl<'a> DataDiff<'a> {
+    fn new(
+        app: &'a AppHandle,
+        source: &mut Connection,
+        target: &mut Connection,
+        source_table: &str,
+        target_table: &str,
+        options: &'a DataDiffOptions,
+    ) -> Result<Self, String> {
+        let engine = source.engine();
+        let source_schema = schema::table_schema(source, source_table)?;
+        let target_schema = schema::table_schema(target, target_table)?;
+        let key = source_schema.primary_key.clone();
+        if key.is_empty() {
+            return Err(format!("Table {} has no primary key", source_table));
+        }
+        if target_schema.primary_key != key {
+            return Err(format!(
+                "Table {} has a different primary key than {}",
+                target_table, source_table
+            ));
+        }
+
+        let mut summary = DataDiffSummary {
+            key: key.clone(),
+            script_path: options.script_path.clone(),
+            ..Default::default()
+        };
+        let in_target = |name: &str| target_schema.columns.iter().any(|c| c.name == name);
+        for column in &source_schema.columns {
+            if !in_target(&column.name) {
+                summary
+                    .warnings
+                    .push(format!("Column {} is only in the source", column.name));
+            }
+        }
+        for column in &target_schema.columns {
+            if !source_schema.columns.iter().any(|c| c.name == column.name) {
+                summary
+                    .warnings
+                    .push(format!("Column {} is only in the target", column.name));
+            }
+        }
+
+        let find = |name: &str| source_schema.columns.iter().find(|c| c.name == name);
+        let mut columns: Vec<ColumnDef> = key.iter().filter_map(|k| find(k)).cloned().collect();
+        let compared: Vec<ColumnDef> = match &options.columns {
+            Some(names) => names
+                .iter()
+                .map(|name| {
+                    find(name)
+                        .filter(|c| in_target(&c.name))
+                        .cloned()
+                        .ok_or_else(|| format!("Column {} is not in both tables", name))
+                })
+                .collect::<Result<_, _>>()?,
+            None => source_schema
+                .columns
+                .iter()
+                .filter(|c| in_target(&c.name))
+                .cloned()
+                .collect(),
+        };
+        summary.columns = compared
+            .iter()
+            .filter(|c| !key.contains(&c.name))
+            .map(|c| c.name.clone())
+            .collect();
+        columns.extend(compared.into_iter().filter(|c| !key.contains(&c.name)));
+        let other_columns = source_schema
+            .columns
+            .iter()
+            .filter(|c| in_target(&c.name) && !columns.iter().any(|d| d.name == c.name))
+            .cloned()
+            .collect();
+
+        // Rows are matched by their keys' bytes, but chunk ranges follow each
+        // server's collation; the two sides only agree when they share one
+        let source_collations = schema::column_collations(source, source_table)?;
+        let target_collations = schema::column_collations(target, target_table)?;
+        let collation = |collations: &[(String, String)], name: &str| {
+            collations
+                .iter()
+                .find(|(column, _)| column == name)
+                .map(|(_, collation)| collation.clone())
+        };
+        for name in &key {
+            match (
+                collation(&source_collations, name),
+                collation(&target_collations, name),
+            ) {
+                (Some(s), Some(t)) if s != t => summary.warnings.push(format!(
+                    "Key column {} uses collation {} on the source and {} on the target; \
+                     rows near chunk boundaries may be listed as both missing and extra",
+                    name, s, t
+                )),
+                _ => {}
+            }
+        }
+
+        let script = match &options.script_path {
+            Some(path) => {
+                let mut script = BufWriter::new(
+                    File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?,
+                );
+                if let Some(prelude) = dialect::script_prelude(engine) {
+                    writeln!(script, "{}", prelude).map_err(|e| e.to_string())?;
+                }
+                Some(script)
+            }
+            None => None,
+        };
+
+        Ok(DataDiff {
+            app,
+            engine,
+            source_table: source_table.to_string(),
+            target_table: target_table.to_string(),
+            key_len: key.len(),
+            columns,
+            other_columns,
+            options,
+            script,
+            summary,
+        })
+    }
+
+    fn run(&mut self, source: &mut Connection, target: &mut Connection) -> Result<(), String> {
+        let mut lower: Option<Vec<Cell>> = None;
+        loop {
+            let upper = self.chunk_end(source, lower.as_deref())?;
+            let range = self.range_condition(lower.as_deref(), upper.as_deref());
+            self.summary.chunks += 1;
+
+            let source_sql = self.checksum_sql(&self.source_table, &range);
+            let target_sql = self.checksum_sql(&self.target_table, &range);
+            let source_sum = query(source, &source_sql)?;
+            let target_sum = query(target, &target_sql)?;
+            self.summary.source_rows += count(&source_sum);
+            self.summary.target_rows += count(&target_sum);
+            if source_sum != target_sum {
+                self.summary.differing_chunks += 1;
+                self.compare_rows(source, target, &range)?;
+            }
+
+            self.emit_progress(false);
+            match upper {
+                Some(upper) => lower = Some(upper),
+                None => break,
+            }
+        }
+
+        if let Some(script) = self.script.as_mut() {
+            script.flush().map_err(|e| e.to_string())?;
+        }
+        self.emit_progress(true);
+        Ok(())
+    }
+
+    // Key of the last source row in the chunk after `lower`, or None when
+    // the rest of the table fits in one chunk.
+    fn chunk_end(
+        &self,
+        source: &mut Connection,
+        lower: Option<&[Cell]>,
+    ) -> Result<Option<Vec<Cell>>, String> {
+        let key_columns: Vec<String> = self.columns[..self.key_len]
+            .iter()
+            .map(|c| dialect::select_column(c, self.engine))
+            .collect();
+        let sql = format!(
+            "SELECT {} FROM {} WHERE {} ORDER BY {} LIMIT 1 OFFSET {}",
+            key_columns.join(", "),
+            dialect::quote_table(&self.source_table, self.engine),
+            self.range_condition(lower, None),
+            self.key_list(),
+            self.options.chunk_size.max(1) - 1
+        );
+        Ok(query(source, &sql)?.into_iter().next())
+    }
+
+    fn key_list(&self) -> String {
+        let names: Vec<&str> = self.columns[..self.key_len]
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        dialect::column_list(&names, self.engine)
+    }
+
+    fn range_condition(&self, lower: Option<&[Cell]>, upper: Option<&[Cell]>) -> String {
+        let tuple = |key: &[Cell]| {
+            let values: Vec<String> = key
+                .iter()
+                .map(|cell| dialect::literal(cell, self.engine))
+                .collect();
+            format!("({})", values.join(", "))
+        };
+        let mut conditions = Vec::new();
+        if let Some(lower) = lower {
+            conditions.push(format!("({}) > {}", self.key_list(), tuple(lower)));
+        }
+        if let Some(upper) = upper {
+            conditions.push(format!("({}) <= {}", self.key_list(), tuple(upper)));
+        }
+        if conditions.is_empty() {
+            "1 = 1".to_string()
+        } else {
+            conditions.join(" AND ")
+        }
+    }
+
+    // Row count and the sum of a 32-bit hash of every row in the range.
+    // NULLs are hashed apart from empty strings.
+    fn checksum_sql(&self, table: &str, range: &str) -> String {
+        let quoted: Vec<String> = self
+            .columns
+            .iter()
+            .map(|c| dialect::quote_ident(&c.name, self.engine))
+            .collect();
+        let hash = match self.engine {
+            Engine::Mysql => {
+                let nulls: Vec<String> = quoted.iter().map(|c| format!("ISNULL({})", c)).collect();
+                format!(
+                    "CAST(COALESCE(SUM(CAST(CONV(SUBSTRING(MD5(CONCAT_WS('#', {}, CONCAT({}))), 1, 8), 16, 10) AS UNSIGNED)), 0) AS CHAR)",
+                    quoted.join(", "),
+                    nulls.join(", ")
+                )
+            }
+            Engine::Postgres => format!(
+                "COALESCE(sum(('x' || substr(md5(ROW({})::text), 1, 8))::bit(32)::int), 0)::text",
+                quoted.join(", ")
+            ),
+        };
+        format!(
+            "SELECT COUNT(*), {} FROM {} WHERE {}",
+            hash,
+            dialect::quote_table(table, self.engine),
+            range
+        )
+    }
+
+    // Rows of a range in the key order `compare_keys` follows. Source rows
+    // carry the other shared columns after the compared ones.
+    fn rows_sql(&self, table: &str, range: &str, with_others: bool) -> String {
+        let others = if with_others {
+            &self.other_columns[..]
+        } else {
+            &[]
+        };
+        let columns: Vec<String> = self
+            .columns
+            .iter()
+            .chain(others)
+            .map(|c| dialect::select_column(c, self.engine))
+            .collect();
+        let order: Vec<String> = self.columns[..self.key_len]
+            .iter()
+            .map(|c| dialect::order_column(c, self.engine))
+            .collect();
+        format!(
+            "SELECT {} FROM {} WHERE {} ORDER BY {}",
+            columns.join(", "),
+            dialect::quote_table(table, self.engine),
+            range,
+            order.join(", ")
+        )
+    }
+
+    // Streams both sides of a range in key order and merges them, so only
+    // a few rows of each are held at a time. The target is read on its own
+    // thread since both result sets are open at once.
+    fn compare_rows(
+        &mut self,
+        source: &mut Connection,
+        target: &mut Connection,
+        range: &str,
+    ) -> Result<(), String> {
+        let source_sql = self.rows_sql(&self.source_table, range, true);
+        let target_sql = self.rows_sql(&self.target_table, range, false);
+
+        thread::scope(|scope| {
+            let (sender, receiver) = mpsc::sync_channel(MERGE_BUFFER);
+            let reader = scope.spawn(move || target.stream(&target_sql, &mut ChannelSink(sender)));
+
+            let mut merge = Merge {
+                diff: self,
+                target: receiver.into_iter().peekable(),
+            };
+            let merged = source.stream(&source_sql, &mut merge);
+            // Closing the channel stops the reader if the merge ended early
+            drop(merge);
+            let read = reader
+                .join()
+                .unwrap_or_else(|_| Err("The target reader panicked".to_string()));
+            // A failed merge is why the reader stopped. A failed read ends
+            // the channel early, which the merge takes for the end of the
+            // range, so the results are only good when both succeeded.
+            merged?;
+            read?;
+            Ok(())
+        })
+    }
+
+    fn missing(&mut self, row: &[Cell]) -> Result<(), String> {
+        self.summary.missing += 1;
+        self.list(RowStatus::Missing, row, Vec::new());
+
+        let values: Vec<String> = row
+            .iter()
+            .map(|cell| dialect::literal(cell, self.engine))
+            .collect();
+        let names: Vec<&str> = self
+            .columns
+            .iter()
+            .chain(&self.other_columns)
+            .map(|c| c.name.as_str())
+            .collect();
+        let statement = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            dialect::quote_table(&self.target_table, self.engine),
+            dialect::column_list(&names, self.engine),
+            values.join(", ")
+        );
+        self.write(&statement)
+    }
+
+    fn extra(&mut self, row: &[Cell]) -> Result<(), String> {
+        self.summary.extra += 1;
+        self.list(RowStatus::Extra, row, Vec::new());
+
+        let statement = format!(
+            "DELETE FROM {} WHERE {}",
+            dialect::quote_table(&self.target_table, self.engine),
+            self.key_condition(row)
+        );
+        self.write(&statement)
+    }
+
+    fn changed(&mut self, row: &[Cell], differing: &[usize]) -> Result<(), String> {
+        self.summary.changed += 1;
+        let names = differing
+            .iter()
+            .map(|&c| self.columns[c].name.clone())
+            .collect();
+        self.list(RowStatus::Changed, row, names);
+
+        let assignments: Vec<String> = differing
+            .iter()
+            .map(|&c| {
+                format!(
+                    "{} = {}",
+                    dialect::quote_ident(&self.columns[c].name, self.engine),
+                    dialect::literal(&row[c], self.engine)
+                )
+            })
+            .collect();
+        let statement = format!(
+            "UPDATE {} SET {} WHERE {}",
+            dialect::quote_table(&self.target_table, self.engine),
+            assignments.join(", "),
+            self.key_condition(row)
+        );
+        self.write(&statement)
+    }
+
+    fn key_condition(&self, row: &[Cell]) -> String {
+        let conditions: Vec<String> = self.columns[..self.key_len]
+            .iter()
+            .zip(row)
+            .map(|(column, cell)| {
+                format!(
+                    "{} = {}",
+                    dialect::quote_ident(&column.name, self.engine),
+                    dialect::literal(cell, self.engine)
+                )
+            })
+            .collect();
+        conditions.join(" AND ")
+    }
+
+    fn list(&mut self, status: RowStatus, row: &[Cell], columns: Vec<String>) {
+        if self.summary.differences.len() >= self.options.max_listed {
+            self.summary.truncated = true;
+            return;
+        }
+        let key = self.columns[..self.key_len]
+            .iter()
+            .zip(row)
+            .map(|(column, cell)| (column.name.clone(), cell.to_json()))
+            .collect();
+        self.summary.differences.push(RowDifference {
+            status,
+            key,
+            columns,
+        });
+    }
+
+    fn write(&mut self, statement: &str) -> Result<(), String> {
+        match self.script.as_mut() {
+            Some(script) => writeln!(script, "{};", statement).map_err(|e| e.to_string()),
+            None => Ok(()),
+        }
+    }
+
+    fn emit_progress(&self, done: bool) {
+        let _ = self.app.emit(
+            "data-diff-progress",
+            DataDiffProgress {
+                table: self.source_table.clone(),
+                chunks: self.summary.chunks,
+                rows: self.summary.source_rows,
+                done,
+            },
+        );
+    }
+}
+
+// Order of two keys as `rows_sql` sorts them: numbers and dates by value,
+// text and everything read as text by its bytes.
+fn compare_keys(a: &[Cell], b: &[Cell]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| compare_cells(a, b))
+        .find(|order| order.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+fn compare_cells(a: &Cell, b: &Cell) -> Ordering {
+    match (a, b) {
+        (Cell::Bool(a), Cell::Bool(b)) => a.cmp(b),
+        (Cell::Int(a), Cell::Int(b)) => a.cmp(b),
+        (Cell::UInt(a), Cell::UInt(b)) => a.cmp(b),
+        (Cell::Int(a), Cell::UInt(b)) => i128::from(*a).cmp(&i128::from(*b)),
+        (Cell::UInt(a), Cell::Int(b)) => i128::from(*a).cmp(&i128::from(*b)),
+        (Cell::Float(a), Cell::Float(b)) => a.total_cmp(b),
+        (Cell::Decimal(a), Cell::Decimal(b)) => compare_decimals(a, b),
+        (Cell::Date(a), Cell::Date(b)) => a.cmp(b),
+        (Cell::DateTime(a), Cell::DateTime(b)) => a.cmp(b),
+        (Cell::TimestampTz(a), Cell::TimestampTz(b)) => a.cmp(b),
+        (Cell::Bytes(a), Cell::Bytes(b)) => a.cmp(b),
+        _ => a.to_text().cmp(&b.to_text()),
+    }
+}
+
+// Numeric order of two decimal strings without going through f64, which
+// would merge keys that differ past its precision
+fn compare_decimals(a: &str, b: &str) -> Ordering {
+    let (a_negative, a) = a.strip_prefix('-').map_or((false, a), |a| (true, a));
+    let (b_negative, b) = b.strip_prefix('-').map_or((false, b), |b| (true, b));
+    let (a_int, a_frac) = a.split_once('.').unwrap_or((a, ""));
+    let (b_int, b_frac) = b.split_once('.').unwrap_or((b, ""));
+    let (a_int, b_int) = (a_int.trim_start_matches('0'), b_int.trim_start_matches('0'));
+    let (a_frac, b_frac) = (a_frac.trim_end_matches('0'), b_frac.trim_end_matches('0'));
+    let zero = |int: &str, frac: &str| int.is_empty() && frac.is_empty();
+
+    let magnitude = a_int
+        .len()
+        .cmp(&b_int.len())
+        .then_with(|| a_int.cmp(b_int))
+        .then_with(|| a_frac.cmp(b_frac));
+    match (
+        a_negative && !zero(a_int, a_frac),
+        b_negative && !zero(b_int, b_frac),
+    ) {
+        (false, false) => magnitude,
+        (true, true) => magnitude.reverse(),
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+    }
+}
+
+fn count(checksum: &[Vec<Cell>]) -> u64 {
+    checksum
+        .first()
+        .and_then(|row| row.first())
+        .and_then(Cell::to_text)
+        .and_then(|text| text.parse().ok())
+        .unwrap_or(0)
+}
+
+// Source rows of a range, merged against the target's as they arrive.
+// Both sides are in key order, so a target row that sorts before the
+// current source row is extra, an equal one is compared and the source row
+// is missing otherwise.
+struct Merge<'d, 'a> {
+    diff: &'d mut DataDiff<'a>,
+    target: Peekable<mpsc::IntoIter<Vec<Cell>>>,
+}
+
+impl RowSink for Merge<'_, '_> {
+    fn start(&mut self, _columns: &[ColumnInfo]) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        let key_len = self.diff.key_len;
+        let key = &row[..key_len];
+        while let Some(other) = self
+            .target
+            .next_if(|other| compare_keys(&other[..key_len], key).is_lt())
+        {
+            self.diff.extra(&other)?;
+        }
+
+        match self
+            .target
+            .next_if(|other| compare_keys(&other[..key_len], key).is_eq())
+        {
+            Some(other) => {
+                let differing: Vec<usize> = (key_len..self.diff.columns.len())
+                    .filter(|&c| row[c] != other[c])
+                    .collect();
+                if !differing.is_empty() {
+                    self.diff.changed(&row, &differing)?;
+                }
+            }
+            None => self.diff.missing(&row)?,
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        for other in self.target.by_ref() {
+            self.diff.extra(&other)?;
+        }
+        Ok(())
+    }
+}
+
+// Hands the target's rows to the merge on the other thread
+struct ChannelSink(mpsc::SyncSender<Vec<Cell>>);
+
+impl RowSink for ChannelSink {
+    fn start(&mut self, _columns: &[ColumnInfo]) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        self.0
+            .send(row)
+            .map_err(|_| "The comparison stopped".to_string())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+// Rows of a result small enough to hold in memory: a key or a checksum.
+#[derive(Default)]
+struct Rows(Vec<Vec<Cell>>);
+
+impl RowSink for Rows {
+    fn start(&mut self, _columns: &[ColumnInfo]) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        self.0.push(row);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn query(conn: &mut Connection, sql: &str) -> Result<Vec<Vec<Cell>>, String> {
+    let mut rows = Rows::default();
+    conn.stream(sql, &mut rows)?;
+    Ok(rows.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_decimals_by_value() {
+        assert_eq!(compare_decimals("2", "10"), Ordering::Less);
+        assert_eq!(compare_decimals("-2", "-10"), Ordering::Greater);
+        assert_eq!(compare_decimals("-1", "0.5"), Ordering::Less);
+        assert_eq!(compare_decimals("1.05", "1.5"), Ordering::Less);
+        assert_eq!(compare_decimals("-1.05", "-1.5"), Ordering::Greater);
+        assert_eq!(
+            compare_decimals("12345678901234567890.1", "12345678901234567890.2"),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn ignores_leading_and_trailing_zeros() {
+        assert_eq!(compare_decimals("007.50", "7.5"), Ordering::Equal);
+        assert_eq!(compare_decimals("10", "10.000"), Ordering::Equal);
+        assert_eq!(compare_decimals("0.10", ".1"), Ordering::Equal);
+        assert_eq!(compare_decimals("0100", "99.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn treats_negative_zero_as_zero() {
+        assert_eq!(compare_decimals("-0", "0"), Ordering::Equal);
+        assert_eq!(compare_decimals("-0.00", "0"), Ordering::Equal);
+        assert_eq!(compare_decimals("-0", "0.001"), Ordering::Less);
+        assert_eq!(compare_decimals("-0.001", "-0"), Ordering::Less);
+    }
+
+    #[test]
+    fn compares_keys_column_by_column() {
+        let key = |id: i64, name: &str| vec![Cell::Int(id), Cell::Text(name.to_string())];
+        assert_eq!(compare_keys(&key(1, "b"), &key(2, "a")), Ordering::Less);
+        assert_eq!(compare_keys(&key(2, "a"), &key(2, "b")), Ordering::Less);
+        assert_eq!(compare_keys(&key(2, "b"), &key(2, "b")), Ordering::Equal);
+        // Text keys sort by their bytes
+        assert_eq!(compare_keys(&key(1, "Z"), &key(1, "a")), Ordering::Less);
+        assert_eq!(
+            compare_keys(
+                &[Cell::Decimal("9.5".to_string())],
+                &[Cell::Decimal("10".to_string())]
+            ),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn compares_signed_and_unsigned_keys() {
+        assert_eq!(
+            compare_keys(&[Cell::Int(-1)], &[Cell::UInt(0)]),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_keys(&[Cell::UInt(u64::MAX)], &[Cell::Int(i64::MAX)]),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_keys(&[Cell::Int(5)], &[Cell::UInt(5)]),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare_keys(&[Cell::UInt(5)], &[Cell::Int(5)]),
+            Ordering::Equal
+        );
+    }
+}
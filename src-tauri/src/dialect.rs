@@ -0,0 +1,470 @@
+use crate::engine::Engine;
+use crate::resultset::{datetime_text, to_hex, Cell, CellKind, ColumnInfo};
+use crate::schema::ColumnDef;
+
+// SQL text generation for a target dialect: identifiers, literals and column
+// types. Shared by the SQL export, imports and cross-engine copies.
+
+// Types the PostgreSQL row reader decodes; other columns are read as text
+const PG_READABLE_TYPES: &[&str] = &[
+    "bool",
+    "int2",
+    "int4",
+    "int8",
+    "oid",
+    "float4",
+    "float8",
+    "numeric",
+    "bytea",
+    "date",
+    "time",
+    "timestamp",
+    "timestamptz",
+    "json",
+    "jsonb",
+    "uuid",
+    "text",
+    "varchar",
+    "bpchar",
+    "name",
+];
+
+pub fn quote_ident(name: &str, dialect: Engine) -> String {
+    match dialect {
+        Engine::Mysql => format!("`{}`", name.replace('`', "``")),
+        Engine::Postgres => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+// Quotes each part of a possibly schema-qualified name, e.g. `public.users`.
+pub fn quote_table(name: &str, dialect: Engine) -> String {
+    name.split('.')
+        .map(|part| quote_ident(part, dialect))
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// First statement of a generated MySQL script. String literals escape with
+// backslashes, which NO_BACKSLASH_ESCAPES would turn into literal text, so
+// the mode is switched off for the session running the script.
+pub const MYSQL_SCRIPT_PRELUDE: &str = "SET SESSION sql_mode = \
+    REPLACE(REPLACE(REPLACE(@@SESSION.sql_mode, 'NO_BACKSLASH_ESCAPES,', ''), \
+    ',NO_BACKSLASH_ESCAPES', ''), 'NO_BACKSLASH_ESCAPES', '');";
+
+pub fn script_prelude(dialect: Engine) -> Option<&'static str> {
+    match dialect {
+        Engine::Mysql => Some(MYSQL_SCRIPT_PRELUDE),
+        Engine::Postgres => None,
+    }
+}
+
+pub fn quote_string(value: &str, dialect: Engine) -> String {
+    match dialect {
+        // Assumes NO_BACKSLASH_ESCAPES is off, as it is by default and in
+        // scripts that start with the prelude above
+        Engine::Mysql => {
+            let mut out = String::with_capacity(value.len() + 2);
+            out.push('\'');
+            for c in value.chars() {
+                match c {
+                    '\'' => out.push_str("\\'"),
+                    '\\' => out.push_str("\\\\"),
+                    '\0' => out.push_str("\\0"),
+                    '\n' => out.push_str("\\n"),
+                    '\r' => out.push_str("\\r"),
+                    '\u{1a}' => out.push_str("\\Z"),
+                    c => out.push(c),
+                }
+            }
+            out.push('\'');
+            out
+        }
+        // standard_conforming_strings is on by default, so only quotes are
+        // doubled
+        Engine::Postgres => format!("'{}'", value.replace('\'', "''")),
+    }
+}
+
+pub fn literal(cell: &Cell, dialect: Engine) -> String {
+    match (cell, dialect) {
+        (Cell::Null, _) => "NULL".to_string(),
+        (Cell::Bool(b), Engine::Mysql) => if *b { "1" } else { "0" }.to_string(),
+        (Cell::Bool(b), Engine::Postgres) => if *b { "TRUE" } else { "FALSE" }.to_string(),
+        (Cell::Int(i), _) => i.to_string(),
+        (Cell::UInt(u), _) => u.to_string(),
+        (Cell::Float(f), _) if f.is_finite() => format!("{:?}", f),
+        // MySQL has no NaN or infinity
+        (Cell::Float(_), Engine::Mysql) => "NULL".to_string(),
+        (Cell::Float(f), Engine::Postgres) => {
+            let special = if f.is_nan() {
+                "NaN"
+            } else if *f > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            format!("'{}'::float8", special)
+        }
+        // NUMERIC 'NaN' and 'Infinity' only parse as quoted strings
+        (Cell::Decimal(d), Engine::Postgres) if !d.parse::<f64>().is_ok_and(|v| v.is_finite()) => {
+            format!("'{}'::numeric", d)
+        }
+        // MySQL DECIMAL has no NaN or infinity either
+        (Cell::Decimal(d), Engine::Mysql) if !d.parse::<f64>().is_ok_and(|v| v.is_finite()) => {
+            "NULL".to_string()
+        }
+        (Cell::Decimal(d), _) => d.clone(),
+        (Cell::Bytes(b), Engine::Mysql) => format!("X'{}'", to_hex(b)),
+        (Cell::Bytes(b), Engine::Postgres) => format!("'\\x{}'::bytea", to_hex(b)),
+        // DATETIME has no offset; the value is already in UTC
+        (Cell::TimestampTz(dt), Engine::Mysql) => {
+            quote_string(&datetime_text(&dt.naive_utc()), dialect)
+        }
+        (Cell::Json(j), Engine::Postgres) => format!("{}::jsonb", quote_string(j, dialect)),
+        (other, _) => quote_string(&other.to_text().unwrap_or_default(), dialect),
+    }
+}
+
+// Column type for a CREATE TABLE in `dialect`, derived from the result
+// metadata. Sizes that aren't known from the result fall back to the widest
+// type so nothing is truncated.
+pub fn column_type(column: &ColumnInfo, dialect: Engine) -> String {
+    let source = column.type_name.as_str();
+
+    match (column.kind, dialect) {
+        (CellKind::Bool, Engine::Mysql) => "TINYINT(1)".to_string(),
+        (CellKind::Bool, Engine::Postgres) => "BOOLEAN".to_string(),
+        (CellKind::Int, Engine::Mysql) => match source {
+            "tiny" => "TINYINT",
+            "short" | "int2" | "year" => "SMALLINT",
+            "int24" => "MEDIUMINT",
+            "long" | "int4" => "INT",
+            _ => "BIGINT",
+        }
+        .to_string(),
+        (CellKind::Int, Engine::Postgres) => match source {
+            "tiny" | "short" | "int2" | "year" => "SMALLINT",
+            "int24" | "long" | "int4" => "INTEGER",
+            _ => "BIGINT",
+        }
+        .to_string(),
+        (CellKind::UInt, Engine::Mysql) => match source {
+            "tiny" => "TINYINT UNSIGNED",
+            "short" | "year" => "SMALLINT UNSIGNED",
+            "int24" => "MEDIUMINT UNSIGNED",
+            "long" | "oid" => "INT UNSIGNED",
+            _ => "BIGINT UNSIGNED",
+        }
+        .to_string(),
+        // PostgreSQL has no unsigned types; use the next wider signed one
+        (CellKind::UInt, Engine::Postgres) => match source {
+            "tiny" => "SMALLINT",
+            "short" | "year" | "int24" => "INTEGER",
+            "long" | "oid" | "bit" => "BIGINT",
+            _ => "NUMERIC(20)",
+        }
+        .to_string(),
+        (CellKind::Float, Engine::Mysql) => match source {
+            "float" | "float4" => "FLOAT",
+            _ => "DOUBLE",
+        }
+        .to_string(),
+        (CellKind::Float, Engine::Postgres) => match source {
+            "float" | "float4" => "REAL",
+            _ => "DOUBLE PRECISION",
+        }
+        .to_string(),
+        (CellKind::Decimal, Engine::Mysql) => match (column.precision, column.scale) {
+            (Some(p), Some(s)) => format!("DECIMAL({},{})", p, s),
+            _ => "DECIMAL(65,30)".to_string(),
+        },
+        (CellKind::Decimal, Engine::Postgres) => match (column.precision, column.scale) {
+            (Some(p), Some(s)) => format!("NUMERIC({},{})", p, s),
+            _ => "NUMERIC".to_string(),
+        },
+        (CellKind::Text, Engine::Mysql) => "LONGTEXT".to_string(),
+        (CellKind::Text, Engine::Postgres) => "TEXT".to_string(),
+        (CellKind::Bytes, Engine::Mysql) => "LONGBLOB".to_string(),
+        (CellKind::Bytes, Engine::Postgres) => "BYTEA".to_string(),
+        (CellKind::Date, _) => "DATE".to_string(),
+        (CellKind::Time, Engine::Mysql) => "TIME(6)".to_string(),
+        (CellKind::Time, Engine::Postgres) => "TIME".to_string(),
+        (CellKind::DateTime, Engine::Mysql) => "DATETIME(6)".to_string(),
+        (CellKind::DateTime, Engine::Postgres) => "TIMESTAMP".to_string(),
+        // MySQL's TIMESTAMP ends in 2038, so the UTC values go in a DATETIME
+        (CellKind::TimestampTz, Engine::Mysql) => "DATETIME(6)".to_string(),
+        (CellKind::TimestampTz, Engine::Postgres) => "TIMESTAMPTZ".to_string(),
+        (CellKind::Json, Engine::Mysql) => "JSON".to_string(),
+        (CellKind::Json, Engine::Postgres) => "JSONB".to_string(),
+    }
+}
+
+pub fn create_table(
+    table: &str,
+    columns: &[ColumnInfo],
+    primary_key: &[String],
+    dialect: Engine,
+) -> String {
+    let mut lines: Vec<String> = columns
+        .iter()
+        .map(|column| {
+            format!(
+                "  {} {}{}",
+                quote_ident(&column.name, dialect),
+                column_type(column, dialect),
+                if column.nullable { "" } else { " NOT NULL" }
+            )
+        })
+        .collect();
+
+    if !primary_key.is_empty() {
+        lines.push(format!(
+            "  PRIMARY KEY ({})",
+            column_list(primary_key, dialect)
+        ));
+    }
+
+    format!(
+        "CREATE TABLE {} (\n{}\n);",
+        quote_table(table, dialect),
+        lines.join(",\n")
+    )
+}
+
+pub fn column_list<S: AsRef<str>>(columns: &[S], dialect: Engine) -> String {
+    columns
+        .iter()
+        .map(|c| quote_ident(c.as_ref(), dialect))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Declared type for `column`, read from a `from` table, in a `to` table.
+// Within one engine the declared type is kept as is. `indexed` columns get a
+// bounded type on MySQL, which can't index TEXT or BLOB without a prefix.
+pub fn translate_type(column: &ColumnDef, from: Engine, to: Engine, indexed: bool) -> String {
+    if from == to {
+        return column.column_type.clone();
+    }
+    match to {
+        Engine::Postgres => mysql_to_postgres(column),
+        Engine::Mysql => postgres_to_mysql(column, indexed),
+    }
+}
+
+fn mysql_to_postgres(column: &ColumnDef) -> String {
+    let unsigned = column.unsigned;
+    let length = column.max_length.unwrap_or(255);
+    let fraction = column.datetime_precision.unwrap_or(0).min(6);
+
+    match column.data_type.as_str() {
+        "tinyint" if column.column_type.to_lowercase().starts_with("tinyint(1)") => {
+            "BOOLEAN".to_string()
+        }
+        "tinyint" | "year" => "SMALLINT".to_string(),
+        "smallint" if unsigned => "INTEGER".to_string(),
+        "smallint" => "SMALLINT".to_string(),
+        "mediumint" => "INTEGER".to_string(),
+        "int" | "integer" if unsigned => "BIGINT".to_string(),
+        "int" | "integer" => "INTEGER".to_string(),
+        "bigint" if unsigned => "NUMERIC(20)".to_string(),
+        "bigint" | "bit" => "BIGINT".to_string(),
+        "decimal" | "numeric" => format!(
+            "NUMERIC({},{})",
+            column.precision.unwrap_or(10),
+            column.scale.unwrap_or(0)
+        ),
+        "float" => "REAL".to_string(),
+        "double" | "real" => "DOUBLE PRECISION".to_string(),
+        "char" => format!("CHAR({})", length),
+        "varchar" => format!("VARCHAR({})", length),
+        "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob" => {
+            "BYTEA".to_string()
+        }
+        "date" => "DATE".to_string(),
+        "datetime" | "timestamp" => format!("TIMESTAMP({})", fraction),
+        "time" => format!("TIME({})", fraction),
+        "json" => "JSONB".to_string(),
+        "geometry" | "point" | "linestring" | "polygon" | "multipoint" | "multilinestring"
+        | "multipolygon" | "geometrycollection" => "BYTEA".to_string(),
+        // text types, enum and set
+        _ => "TEXT".to_string(),
+    }
+}
+
+fn postgres_to_mysql(column: &ColumnDef, indexed: bool) -> String {
+    // Unbounded text that is part of a key is capped at 255 characters
+    let text = if indexed { "VARCHAR(255)" } else { "LONGTEXT" };
+    let fraction = column.datetime_precision.unwrap_or(6).min(6);
+
+    match column.data_type.as_str() {
+        "bool" => "TINYINT(1)".to_string(),
+        "int2" => "SMALLINT".to_string(),
+        "int4" => "INT".to_string(),
+        "int8" => "BIGINT".to_string(),
+        "oid" => "INT UNSIGNED".to_string(),
+        "float4" => "FLOAT".to_string(),
+        "float8" => "DOUBLE".to_string(),
+        "numeric" => match (column.precision, column.scale) {
+            (Some(p), Some(s)) if p <= 65 && s <= 30 => format!("DECIMAL({},{})", p, s),
+            _ => "DECIMAL(65,30)".to_string(),
+        },
+        "bpchar" => match column.max_length {
+            Some(n) if n <= 255 => format!("CHAR({})", n),
+            _ => text.to_string(),
+        },
+        "varchar" => match column.max_length {
+            Some(n) if n <= 16383 => format!("VARCHAR({})", n),
+            _ => text.to_string(),
+        },
+        "bytea" if indexed => "VARBINARY(255)".to_string(),
+        "bytea" => "LONGBLOB".to_string(),
+        "date" => "DATE".to_string(),
+        "timestamp" | "timestamptz" => format!("DATETIME({})", fraction),
+        "time" | "timetz" => format!("TIME({})", fraction),
+        "json" | "jsonb" => "JSON".to_string(),
+        "uuid" => "CHAR(36)".to_string(),
+        // text, citext, enums, arrays and everything else read as text
+        _ => text.to_string(),
+    }
+}
+
+// Select-list entry for a column, converting the types the row readers
+// can't decode: MySQL BIT values arrive as bytes and are read as numbers,
+// PostgreSQL arrays, ranges, intervals and the like are read as text.
+pub fn select_column(column: &ColumnDef, dialect: Engine) -> String {
+    let quoted = quote_ident(&column.name, dialect);
+    match dialect {
+        Engine::Mysql if column.data_type == "bit" => {
+            format!("CAST({} AS UNSIGNED) AS {}", quoted, quoted)
+        }
+        Engine::Postgres if !PG_READABLE_TYPES.contains(&column.data_type.as_str()) => {
+            format!("{}::text AS {}", quoted, quoted)
+        }
+        _ => quoted,
+    }
+}
+
+// ORDER BY entry that sorts a column the way its values from
+// `select_column` compare as bytes: text by its encoding rather than the
+// column's collation.
+pub fn order_column(column: &ColumnDef, dialect: Engine) -> String {
+    let quoted = quote_ident(&column.name, dialect);
+    let data_type = column.data_type.as_str();
+    match dialect {
+        Engine::Mysql
+            if matches!(
+                data_type,
+                "char"
+                    | "varchar"
+                    | "tinytext"
+                    | "text"
+                    | "mediumtext"
+                    | "longtext"
+                    | "enum"
+                    | "set"
+            ) =>
+        {
+            format!("CAST({} AS BINARY)", quoted)
+        }
+        Engine::Postgres if matches!(data_type, "text" | "varchar" | "bpchar" | "name") => {
+            format!("{} COLLATE \"C\"", quoted)
+        }
+        Engine::Postgres if !PG_READABLE_TYPES.contains(&data_type) => {
+            format!("{}::text COLLATE \"C\"", quoted)
+        }
+        _ => quoted,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resultset::pg_column;
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use postgres::types::Type;
+
+    #[test]
+    fn escapes_identifiers() {
+        assert_eq!(quote_ident("a`b", Engine::Mysql), "`a``b`");
+        assert_eq!(quote_ident("a\"b", Engine::Postgres), "\"a\"\"b\"");
+        assert_eq!(
+            quote_table("public.us\"ers", Engine::Postgres),
+            "\"public\".\"us\"\"ers\""
+        );
+    }
+
+    #[test]
+    fn escapes_strings_for_mysql() {
+        let cell = Cell::Text("it's C:\\temp\0\n\r\u{1a}".to_string());
+        assert_eq!(
+            literal(&cell, Engine::Mysql),
+            "'it\\'s C:\\\\temp\\0\\n\\r\\Z'"
+        );
+    }
+
+    #[test]
+    fn escapes_strings_for_postgres() {
+        let cell = Cell::Text("it's C:\\temp".to_string());
+        assert_eq!(literal(&cell, Engine::Postgres), "'it''s C:\\temp'");
+    }
+
+    #[test]
+    fn writes_bytes() {
+        let cell = Cell::Bytes(vec![0, 0x27, 0xff]);
+        assert_eq!(literal(&cell, Engine::Mysql), "X'0027ff'");
+        assert_eq!(literal(&cell, Engine::Postgres), "'\\x0027ff'::bytea");
+    }
+
+    #[test]
+    fn writes_non_finite_numbers() {
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(literal(&Cell::Float(value), Engine::Mysql), "NULL");
+        }
+        assert_eq!(
+            literal(&Cell::Float(f64::NAN), Engine::Postgres),
+            "'NaN'::float8"
+        );
+        assert_eq!(
+            literal(&Cell::Float(f64::NEG_INFINITY), Engine::Postgres),
+            "'-Infinity'::float8"
+        );
+
+        for value in ["NaN", "Infinity", "-Infinity"] {
+            let cell = Cell::Decimal(value.to_string());
+            assert_eq!(literal(&cell, Engine::Mysql), "NULL");
+            assert_eq!(
+                literal(&cell, Engine::Postgres),
+                format!("'{}'::numeric", value)
+            );
+        }
+        assert_eq!(
+            literal(&Cell::Decimal("-1.50".to_string()), Engine::Mysql),
+            "-1.50"
+        );
+    }
+
+    #[test]
+    fn writes_timestamptz_as_utc() {
+        let dt = Utc.with_ymd_and_hms(2024, 3, 1, 12, 30, 0).unwrap();
+        let cell = Cell::TimestampTz(dt);
+        assert_eq!(literal(&cell, Engine::Postgres), "'2024-03-01 12:30:00+00'");
+        assert_eq!(literal(&cell, Engine::Mysql), "'2024-03-01 12:30:00'");
+
+        let naive = NaiveDate::from_ymd_opt(2024, 3, 1)
+            .unwrap()
+            .and_hms_opt(12, 30, 0)
+            .unwrap();
+        assert_eq!(
+            literal(&Cell::DateTime(naive), Engine::Postgres),
+            "'2024-03-01 12:30:00'"
+        );
+
+        let column = pg_column("created_at", &Type::TIMESTAMPTZ);
+        assert_eq!(column.kind, CellKind::TimestampTz);
+        assert_eq!(column_type(&column, Engine::Postgres), "TIMESTAMPTZ");
+        assert_eq!(column_type(&column, Engine::Mysql), "DATETIME(6)");
+        let column = pg_column("created_at", &Type::TIMESTAMP);
+        assert_eq!(column_type(&column, Engine::Postgres), "TIMESTAMP");
+    }
+}
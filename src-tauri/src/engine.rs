@@ -0,0 +1,122 @@
+use mysql::prelude::*;
+use mysql::{Opts, Pool, PooledConn};
+use serde::{Deserialize, Serialize};
+
+use crate::mysqlcmd;
+use crate::pgcmd::{self, ClientPool, PooledClient};
+use crate::resultset::{stream_mysql, stream_postgres, RowSink};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    Mysql,
+    Postgres,
+}
+
+// Connection string without its password, used to label where a statement
+// ran. Handles both URLs and libpq-style `key=value` strings.
+pub fn connection_label(connection_string: &str) -> String {
+    if let Some((scheme, rest)) = connection_string.split_once("://") {
+        let (authority, path) = match rest.find('/') {
+            Some(pos) => rest.split_at(pos),
+            None => (rest, ""),
+        };
+        let path = path.split('?').next().unwrap_or_default();
+        let authority = match authority.rsplit_once('@') {
+            Some((user_info, host)) => {
+                let user = user_info.split(':').next().unwrap_or_default();
+                format!("{}@{}", user, host)
+            }
+            None => authority.to_string(),
+        };
+        format!("{}://{}{}", scheme, authority, path)
+    } else {
+        connection_string
+            .split_whitespace()
+            .filter(|part| !part.to_lowercase().starts_with("password="))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+// The current session of one engine, captured on the async side so the
+// connection itself can be opened on the blocking pool.
+#[derive(Clone)]
+pub enum Session {
+    Mysql(Pool, String),
+    Postgres(ClientPool),
+}
+
+impl Session {
+    pub fn open(
+        engine: Engine,
+        mysql_state: &mysqlcmd::DbConnection,
+        pg_state: &pgcmd::DbConnection,
+    ) -> Result<Self, String> {
+        Ok(match engine {
+            Engine::Mysql => {
+                let (pool, db) = mysql_state.session()?;
+                Session::Mysql(pool, db)
+            }
+            Engine::Postgres => Session::Postgres(pg_state.session()?),
+        })
+    }
+
+    // A session on another server. Blocking: the MySQL pool connects as it
+    // is created. `database` overrides the one in the connection string.
+    pub fn from_connection_string(
+        engine: Engine,
+        connection_string: &str,
+        database: Option<&str>,
+    ) -> Result<Self, String> {
+        Ok(match engine {
+            Engine::Mysql => {
+                let opts = Opts::from_url(connection_string).map_err(|e| e.to_string())?;
+                let db = database
+                    .map(str::to_string)
+                    .or_else(|| opts.get_db_name().map(str::to_string))
+                    .ok_or_else(|| "The connection string names no database".to_string())?;
+                let pool = Pool::new(opts).map_err(|e| e.to_string())?;
+                Session::Mysql(pool, db)
+            }
+            Engine::Postgres => {
+                Session::Postgres(ClientPool::standalone(connection_string.to_string()))
+            }
+        })
+    }
+
+    pub fn connect(&self) -> Result<Connection, String> {
+        Ok(match self {
+            Session::Mysql(pool, db) => Connection::Mysql(mysqlcmd::get_conn(pool, db)?),
+            Session::Postgres(pool) => Connection::Postgres(pool.get()?),
+        })
+    }
+}
+
+pub enum Connection {
+    Mysql(PooledConn),
+    Postgres(PooledClient),
+}
+
+impl Connection {
+    pub fn engine(&self) -> Engine {
+        match self {
+            Connection::Mysql(_) => Engine::Mysql,
+            Connection::Postgres(_) => Engine::Postgres,
+        }
+    }
+
+    pub fn execute(&mut self, sql: &str) -> Result<(), String> {
+        match self {
+            Connection::Mysql(conn) => conn.query_drop(sql).map_err(|e| e.to_string()),
+            Connection::Postgres(client) => client.batch_execute(sql).map_err(|e| e.to_string()),
+        }
+    }
+
+    pub fn stream(&mut self, query: &str, sink: &mut dyn RowSink) -> Result<u64, String> {
+        match self {
+            Connection::Mysql(conn) => stream_mysql(conn, query, sink),
+            Connection::Postgres(client) => stream_postgres(&mut **client, query, sink),
+        }
+    }
+}
@@ -0,0 +1,717 @@
+use mysql::prelude::*;
+use mysql::TxOpts;
+use postgres::types::{FromSql, Type};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::time::SystemTime;
+use tauri::State;
+
+use crate::engine::Engine;
+use crate::history::{Execution, HistoryStore, Outcome};
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::runtime::run_blocking;
+
+#[derive(Serialize, Default)]
+pub struct PlanBuffers {
+    shared_hit: Option<u64>,
+    shared_read: Option<u64>,
+    shared_dirtied: Option<u64>,
+    shared_written: Option<u64>,
+    temp_read: Option<u64>,
+    temp_written: Option<u64>,
+}
+
+#[derive(Serialize, Default)]
+pub struct PlanNode {
+    node_type: String,
+    relation: Option<String>,
+    estimated_rows: Option<f64>,
+    actual_rows: Option<f64>,
+    startup_cost: Option<f64>,
+    total_cost: Option<f64>,
+    actual_time_ms: Option<f64>,
+    loops: Option<f64>,
+    buffers: Option<PlanBuffers>,
+    details: Map<String, Value>,
+    children: Vec<PlanNode>,
+}
+
+#[derive(Serialize)]
+pub struct QueryPlan {
+    engine: Engine,
+    analyzed: bool,
+    planning_time_ms: Option<f64>,
+    execution_time_ms: Option<f64>,
+    root: PlanNode,
+    raw: String,
+}
+
+#[tauri::command]
+pub async fn explain_query(
+    engine: Engine,
+    query: String,
+    analyze: bool,
+    buffers: bool,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+    history: State<'_, HistoryStore>,
+) -> Result<QueryPlan, String> {
+    let execution = Execution::current(engine, &query, &mysql_state, &pg_state)?;
+    let started = SystemTime::now();
+
+    let statement = query.clone();
+    let result = match engine {
+        Engine::Mysql => {
+            let (pool, db) = mysql_state.session()?;
+            run_blocking(move || {
+                let mut conn = mysqlcmd::get_conn(&pool, &db)?;
+                explain_mysql(&mut conn, &statement, analyze)
+            })
+            .await
+        }
+        Engine::Postgres => {
+            let pool = pg_state.session()?;
+            // The session's own client, so EXPLAIN sees its temporary tables
+            // and settings like the query would
+            run_blocking(move || {
+                let mut client = pool.pinned()?;
+                explain_postgres(&mut client, &statement, analyze, buffers)
+            })
+            .await
+        }
+    };
+
+    // Only ANALYZE executes the statement, but plain plans are worth
+    // finding again too
+    history.record(execution, started, &result);
+    result
+}
+
+impl Outcome for QueryPlan {
+    fn duration_ms(&self) -> Option<f64> {
+        self.execution_time_ms
+    }
+
+    fn row_count(&self) -> Option<u64> {
+        self.root.actual_rows.map(|rows| rows as u64)
+    }
+}
+
+pub fn explain_postgres(
+    client: &mut postgres::Client,
+    query: &str,
+    analyze: bool,
+    buffers: bool,
+) -> Result<QueryPlan, String> {
+    let mut options = vec!["FORMAT JSON"];
+    if analyze {
+        options.push("ANALYZE");
+        if buffers {
+            options.push("BUFFERS");
+        }
+    }
+    let sql = format!("EXPLAIN ({}) {}", options.join(", "), strip_semicolon(query));
+
+    // The extended protocol refuses several statements in one string, so
+    // `SELECT 1; DROP TABLE t` can't slip a second statement past EXPLAIN.
+    // ANALYZE really executes the statement, so it is always rolled back.
+    // Inside the user's own transaction a BEGIN would be ignored and a
+    // ROLLBACK would throw away their work, so a savepoint is used there.
+    let rows = if analyze {
+        let in_transaction: bool = client
+            .query_one(
+                "SELECT transaction_timestamp() <> statement_timestamp()",
+                &[],
+            )
+            .map_err(|e| e.to_string())?
+            .get(0);
+        let (begin, rollback) = if in_transaction {
+            (
+                "SAVEPOINT explain_analyze",
+                "ROLLBACK TO SAVEPOINT explain_analyze; RELEASE SAVEPOINT explain_analyze",
+            )
+        } else {
+            ("BEGIN", "ROLLBACK")
+        };
+        client.batch_execute(begin).map_err(|e| e.to_string())?;
+        let rows = client.query(&sql, &[]);
+        client.batch_execute(rollback).map_err(|e| e.to_string())?;
+        rows.map_err(|e| e.to_string())?
+    } else {
+        client.query(&sql, &[]).map_err(|e| e.to_string())?
+    };
+
+    let mut raw = String::new();
+    for row in &rows {
+        let JsonText(text) = row.try_get(0).map_err(|e| e.to_string())?;
+        raw.push_str(&text);
+    }
+
+    pg_plan(raw, analyze)
+}
+
+fn pg_plan(raw: String, analyze: bool) -> Result<QueryPlan, String> {
+    let parsed: Value = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+    let top = parsed
+        .get(0)
+        .and_then(Value::as_object)
+        .ok_or("Unexpected EXPLAIN output")?;
+    let plan = top
+        .get("Plan")
+        .and_then(Value::as_object)
+        .ok_or("Unexpected EXPLAIN output")?;
+
+    Ok(QueryPlan {
+        engine: Engine::Postgres,
+        analyzed: analyze,
+        planning_time_ms: top.get("Planning Time").and_then(Value::as_f64),
+        execution_time_ms: top.get("Execution Time").and_then(Value::as_f64),
+        root: pg_node(plan),
+        raw,
+    })
+}
+
+// EXPLAIN (FORMAT JSON) returns a `json` column, which the driver only reads
+// into a String with the serde feature.
+struct JsonText(String);
+
+impl<'a> FromSql<'a> for JsonText {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, Box<dyn std::error::Error + Sync + Send>> {
+        Ok(JsonText(std::str::from_utf8(raw)?.to_string()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::JSON || *ty == Type::TEXT
+    }
+}
+
+fn pg_node(plan: &Map<String, Value>) -> PlanNode {
+    let number = |key: &str| plan.get(key).and_then(Value::as_f64);
+    let count = |key: &str| plan.get(key).and_then(Value::as_u64);
+
+    let buffers = if plan.contains_key("Shared Hit Blocks") {
+        Some(PlanBuffers {
+            shared_hit: count("Shared Hit Blocks"),
+            shared_read: count("Shared Read Blocks"),
+            shared_dirtied: count("Shared Dirtied Blocks"),
+            shared_written: count("Shared Written Blocks"),
+            temp_read: count("Temp Read Blocks"),
+            temp_written: count("Temp Written Blocks"),
+        })
+    } else {
+        None
+    };
+
+    let children = plan
+        .get("Plans")
+        .and_then(Value::as_array)
+        .map(|plans| {
+            plans
+                .iter()
+                .filter_map(Value::as_object)
+                .map(pg_node)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let details = plan
+        .iter()
+        .filter(|(key, _)| !PG_NORMALIZED_KEYS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    PlanNode {
+        node_type: plan
+            .get("Node Type")
+            .and_then(Value::as_str)
+            .unwrap_or("Unknown")
+            .to_string(),
+        relation: plan
+            .get("Relation Name")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        estimated_rows: number("Plan Rows"),
+        actual_rows: number("Actual Rows"),
+        startup_cost: number("Startup Cost"),
+        total_cost: number("Total Cost"),
+        actual_time_ms: number("Actual Total Time"),
+        loops: number("Actual Loops"),
+        buffers,
+        details,
+        children,
+    }
+}
+
+const PG_NORMALIZED_KEYS: &[&str] = &[
+    "Node Type",
+    "Relation Name",
+    "Plan Rows",
+    "Actual Rows",
+    "Startup Cost",
+    "Total Cost",
+    "Actual Total Time",
+    "Actual Loops",
+    "Shared Hit Blocks",
+    "Shared Read Blocks",
+    "Shared Dirtied Blocks",
+    "Shared Written Blocks",
+    "Temp Read Blocks",
+    "Temp Written Blocks",
+    "Plans",
+];
+
+pub fn explain_mysql(
+    conn: &mut mysql::PooledConn,
+    query: &str,
+    analyze: bool,
+) -> Result<QueryPlan, String> {
+    let query = single_mysql_statement(query)?;
+
+    if analyze {
+        // EXPLAIN ANALYZE executes the statement; keep it in a transaction
+        // that is always rolled back
+        let mut transaction = conn
+            .start_transaction(TxOpts::default())
+            .map_err(|e| e.to_string())?;
+        let lines: Vec<String> = transaction
+            .query(format!("EXPLAIN ANALYZE {}", query))
+            .map_err(|e| e.to_string())?;
+        transaction.rollback().map_err(|e| e.to_string())?;
+
+        let raw = lines.join("\n");
+        let root = parse_mysql_tree(&raw);
+
+        Ok(QueryPlan {
+            engine: Engine::Mysql,
+            analyzed: true,
+            planning_time_ms: None,
+            execution_time_ms: root.actual_time_ms,
+            root,
+            raw,
+        })
+    } else {
+        let raw: String = conn
+            .query_first(format!("EXPLAIN FORMAT=JSON {}", query))
+            .map_err(|e| e.to_string())?
+            .ok_or("Unexpected EXPLAIN output")?;
+
+        let parsed: Value = serde_json::from_str(&raw).map_err(|e| e.to_string())?;
+        let block = parsed
+            .get("query_block")
+            .and_then(Value::as_object)
+            .ok_or("Unexpected EXPLAIN output")?;
+
+        Ok(QueryPlan {
+            engine: Engine::Mysql,
+            analyzed: false,
+            planning_time_ms: None,
+            execution_time_ms: None,
+            root: mysql_node("query_block", block),
+            raw,
+        })
+    }
+}
+
+// Objects in MySQL's JSON plan that become nodes of the tree; everything
+// else on an object is reported as a detail.
+const MYSQL_PLAN_KEYS: &[&str] = &[
+    "query_block",
+    "table",
+    "ordering_operation",
+    "grouping_operation",
+    "duplicates_removal",
+    "union_result",
+    "windowing",
+    "buffer_result",
+    "materialized_from_subquery",
+];
+
+fn mysql_node(key: &str, object: &Map<String, Value>) -> PlanNode {
+    let cost_info = object.get("cost_info").and_then(Value::as_object);
+    let cost = |name: &str| {
+        cost_info
+            .and_then(|info| info.get(name))
+            .and_then(mysql_number)
+    };
+
+    let mut node = PlanNode {
+        node_type: match object.get("access_type").and_then(Value::as_str) {
+            Some(access_type) => format!("{} ({})", key, access_type),
+            None => key.to_string(),
+        },
+        relation: object
+            .get("table_name")
+            .and_then(Value::as_str)
+            .map(str::to_string),
+        estimated_rows: object
+            .get("rows_examined_per_scan")
+            .or_else(|| object.get("rows_produced_per_join"))
+            .and_then(mysql_number),
+        total_cost: cost("query_cost").or_else(|| cost("prefix_cost")),
+        ..Default::default()
+    };
+
+    for (name, value) in object {
+        match value {
+            Value::Object(child) if MYSQL_PLAN_KEYS.contains(&name.as_str()) => {
+                node.children.push(mysql_node(name, child));
+            }
+            // nested_loop, query_specifications, attached_subqueries, ...
+            Value::Array(items) if items.iter().any(is_mysql_wrapper) => {
+                for item in items.iter().filter_map(Value::as_object) {
+                    for (child_name, child) in item {
+                        if let Value::Object(child) = child {
+                            if MYSQL_PLAN_KEYS.contains(&child_name.as_str()) {
+                                node.children.push(mysql_node(child_name, child));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                node.details.insert(name.clone(), value.clone());
+            }
+        }
+    }
+
+    node
+}
+
+fn is_mysql_wrapper(item: &Value) -> bool {
+    item.as_object().is_some_and(|object| {
+        object
+            .keys()
+            .any(|key| MYSQL_PLAN_KEYS.contains(&key.as_str()))
+    })
+}
+
+// MySQL reports most numbers in its JSON plan as strings.
+fn mysql_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => s.parse().ok(),
+        other => other.as_f64(),
+    }
+}
+
+// Parses the indented tree printed by EXPLAIN ANALYZE, e.g.
+// `-> Table scan on c  (cost=1.75 rows=5) (actual time=0.048..0.060 rows=5 loops=1)`
+fn parse_mysql_tree(text: &str) -> PlanNode {
+    let mut roots: Vec<PlanNode> = Vec::new();
+    let mut stack: Vec<(usize, PlanNode)> = Vec::new();
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let Some(body) = trimmed.strip_prefix("->") else {
+            continue;
+        };
+        let depth = line.len() - trimmed.len();
+        let node = parse_mysql_tree_line(body.trim());
+
+        while stack.last().is_some_and(|(d, _)| *d >= depth) {
+            let (_, done) = stack.pop().unwrap();
+            attach_node(&mut stack, &mut roots, done);
+        }
+        stack.push((depth, node));
+    }
+    while let Some((_, done)) = stack.pop() {
+        attach_node(&mut stack, &mut roots, done);
+    }
+
+    if roots.len() == 1 {
+        roots.pop().unwrap()
+    } else {
+        PlanNode {
+            node_type: "Query".to_string(),
+            children: roots,
+            ..Default::default()
+        }
+    }
+}
+
+fn attach_node(stack: &mut [(usize, PlanNode)], roots: &mut Vec<PlanNode>, node: PlanNode) {
+    match stack.last_mut() {
+        Some((_, parent)) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+fn parse_mysql_tree_line(line: &str) -> PlanNode {
+    let description_end = ["  (cost=", " (cost=", " (actual time=", " (never executed)"]
+        .iter()
+        .filter_map(|marker| line.find(marker))
+        .min()
+        .unwrap_or(line.len());
+    let description = line[..description_end].trim();
+
+    let mut node = PlanNode {
+        node_type: description.to_string(),
+        ..Default::default()
+    };
+
+    // "Table scan on c", "Index lookup on o using PRIMARY (id=c.id)"
+    if let Some(pos) = description.find(" on ") {
+        if !description[..pos].contains(':') {
+            node.relation = description[pos + 4..]
+                .split_whitespace()
+                .next()
+                .map(str::to_string);
+        }
+    }
+
+    if let Some(section) = paren_section(line, "(cost=") {
+        for (i, token) in section.split_whitespace().enumerate() {
+            if i == 0 {
+                let (startup, total) = split_range(token);
+                node.startup_cost = startup;
+                node.total_cost = total;
+            } else if let Some(rows) = token.strip_prefix("rows=") {
+                node.estimated_rows = rows.parse().ok();
+            }
+        }
+    }
+
+    if let Some(section) = paren_section(line, "(actual time=") {
+        for (i, token) in section.split_whitespace().enumerate() {
+            if i == 0 {
+                node.actual_time_ms = split_range(token).1;
+            } else if let Some(rows) = token.strip_prefix("rows=") {
+                node.actual_rows = rows.parse().ok();
+            } else if let Some(loops) = token.strip_prefix("loops=") {
+                node.loops = loops.parse().ok();
+            }
+        }
+    }
+
+    node
+}
+
+fn paren_section<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let start = line.find(marker)? + marker.len();
+    let end = line[start..].find(')')? + start;
+    Some(&line[start..end])
+}
+
+// "0.048..0.060" -> (0.048, 0.060); a single number is the total.
+fn split_range(token: &str) -> (Option<f64>, Option<f64>) {
+    match token.split_once("..") {
+        Some((start, end)) => (start.parse().ok(), end.parse().ok()),
+        None => (None, token.parse().ok()),
+    }
+}
+
+fn strip_semicolon(query: &str) -> &str {
+    query.trim().trim_end_matches(';')
+}
+
+// The text protocol runs every statement in the string, and the mysql crate
+// enables multi-statements, so in `SELECT 1; DROP TABLE t` the DROP would
+// run outside EXPLAIN (and commit implicitly). Input with more than one
+// statement is refused. Whether a backslash escapes a quote depends on
+// NO_BACKSLASH_ESCAPES, so the query has to split the same way either way.
+fn single_mysql_statement(query: &str) -> Result<&str, String> {
+    let end = mysql_statement_end(query, true)?;
+    if mysql_statement_end(query, false)? != end {
+        return Err("EXPLAIN takes a single statement".to_string());
+    }
+    Ok(query[..end].trim())
+}
+
+// Where the first statement ends: at its first `;` outside quotes and
+// comments, or at the end. Anything but comments after it is an error.
+fn mysql_statement_end(query: &str, backslash_escapes: bool) -> Result<usize, String> {
+    let bytes = query.as_bytes();
+    let skip_to = |from: usize, pattern: &str| {
+        query[from..]
+            .find(pattern)
+            .map_or(bytes.len(), |pos| from + pos + pattern.len())
+    };
+    let mut end = None;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'#' => i = skip_to(i, "\n"),
+            b'-' if bytes.get(i + 1) == Some(&b'-')
+                && matches!(bytes.get(i + 2), None | Some(b' ' | b'\t' | b'\n' | b'\r')) =>
+            {
+                i = skip_to(i, "\n")
+            }
+            // `/*!` and `/*+` comments hold code the server runs
+            b'/' if bytes.get(i + 1) == Some(&b'*')
+                && !matches!(bytes.get(i + 2), Some(b'!' | b'+')) =>
+            {
+                i = skip_to(i + 2, "*/")
+            }
+            b';' => {
+                end.get_or_insert(i);
+                i += 1;
+            }
+            c if c.is_ascii_whitespace() => i += 1,
+            _ if end.is_some() => return Err("EXPLAIN takes a single statement".to_string()),
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() {
+                    let escaped = bytes[i] == b'\\' && backslash_escapes && quote != b'`';
+                    if escaped || (bytes[i] == quote && bytes.get(i + 1) == Some(&quote)) {
+                        i += 2;
+                    } else if bytes[i] == quote {
+                        break;
+                    } else {
+                        i += 1;
+                    }
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    Ok(end.unwrap_or(bytes.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // EXPLAIN ANALYZE output of a join on MySQL 8.0
+    const MYSQL_ANALYZE: &str = "\
+-> Nested loop inner join  (cost=2.50 rows=5) (actual time=0.081..0.112 rows=5 loops=1)
+    -> Table scan on c  (cost=0.75 rows=5) (actual time=0.048..0.060 rows=5 loops=1)
+    -> Single-row index lookup on o using PRIMARY (id=c.order_id)  (cost=0.27 rows=1) (actual time=0.009..0.009 rows=1 loops=5)
+";
+
+    #[test]
+    fn parses_mysql_analyze_tree() {
+        let root = parse_mysql_tree(MYSQL_ANALYZE);
+        assert_eq!(root.node_type, "Nested loop inner join");
+        assert_eq!(root.startup_cost, None);
+        assert_eq!(root.total_cost, Some(2.5));
+        assert_eq!(root.estimated_rows, Some(5.0));
+        assert_eq!(root.actual_time_ms, Some(0.112));
+        assert_eq!(root.children.len(), 2);
+
+        let scan = &root.children[0];
+        assert_eq!(scan.node_type, "Table scan on c");
+        assert_eq!(scan.relation.as_deref(), Some("c"));
+        assert_eq!(scan.actual_rows, Some(5.0));
+
+        let lookup = &root.children[1];
+        assert_eq!(lookup.relation.as_deref(), Some("o"));
+        assert_eq!(lookup.loops, Some(5.0));
+        assert!(lookup.children.is_empty());
+    }
+
+    #[test]
+    fn accepts_a_single_mysql_statement() {
+        let accepted = [
+            ("SELECT 1", "SELECT 1"),
+            ("  SELECT 1;; ", "SELECT 1"),
+            ("SELECT ';' FROM t; -- done", "SELECT ';' FROM t"),
+            (
+                "SELECT `a;b` FROM t # note; more",
+                "SELECT `a;b` FROM t # note; more",
+            ),
+            (
+                "SELECT 'it''s;' /* ; */ FROM t;",
+                "SELECT 'it''s;' /* ; */ FROM t",
+            ),
+        ];
+        for (query, statement) in accepted {
+            assert_eq!(single_mysql_statement(query), Ok(statement), "{}", query);
+        }
+    }
+
+    #[test]
+    fn rejects_several_mysql_statements() {
+        let rejected = [
+            "SELECT 1; DROP TABLE t",
+            "SELECT 1;\nDROP TABLE t;",
+            "SELECT 1 /* ; */; DELETE FROM t",
+            "SELECT 1; /*!50000 DROP TABLE t */",
+            // A second statement only with NO_BACKSLASH_ESCAPES
+            "SELECT '\\'; DROP TABLE t; -- '",
+            // ... and only without it
+            "SELECT '\\'' ; DROP TABLE t; -- '",
+        ];
+        for query in rejected {
+            assert!(single_mysql_statement(query).is_err(), "{}", query);
+        }
+    }
+
+    #[test]
+    fn mysql_tree_with_several_roots_gets_a_query_node() {
+        let root = parse_mysql_tree(
+            "-> Rows fetched before execution  (cost=0.00 rows=1)\n\
+             -> Table scan on t  (never executed)\n",
+        );
+        assert_eq!(root.node_type, "Query");
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[1].node_type, "Table scan on t");
+        assert_eq!(root.children[1].actual_rows, None);
+    }
+
+    // EXPLAIN (ANALYZE, BUFFERS, FORMAT JSON) on PostgreSQL 16
+    const PG_ANALYZE: &str = r#"[
+      {
+        "Plan": {
+          "Node Type": "Hash Join",
+          "Join Type": "Inner",
+          "Startup Cost": 1.11,
+          "Total Cost": 2.26,
+          "Plan Rows": 5,
+          "Actual Total Time": 0.051,
+          "Actual Rows": 5,
+          "Actual Loops": 1,
+          "Hash Cond": "(o.customer_id = c.id)",
+          "Shared Hit Blocks": 2,
+          "Shared Read Blocks": 0,
+          "Shared Dirtied Blocks": 0,
+          "Shared Written Blocks": 0,
+          "Temp Read Blocks": 0,
+          "Temp Written Blocks": 0,
+          "Plans": [
+            {
+              "Node Type": "Seq Scan",
+              "Relation Name": "orders",
+              "Alias": "o",
+              "Startup Cost": 0.0,
+              "Total Cost": 1.05,
+              "Plan Rows": 5,
+              "Actual Total Time": 0.010,
+              "Actual Rows": 5,
+              "Actual Loops": 1
+            }
+          ]
+        },
+        "Planning Time": 0.210,
+        "Execution Time": 0.093
+      }
+    ]"#;
+
+    #[test]
+    fn parses_postgres_json_plan() {
+        let plan = pg_plan(PG_ANALYZE.to_string(), true).unwrap();
+        assert_eq!(plan.planning_time_ms, Some(0.21));
+        assert_eq!(plan.execution_time_ms, Some(0.093));
+
+        let root = &plan.root;
+        assert_eq!(root.node_type, "Hash Join");
+        assert_eq!(root.total_cost, Some(2.26));
+        assert_eq!(root.actual_rows, Some(5.0));
+        assert_eq!(root.buffers.as_ref().and_then(|b| b.shared_hit), Some(2));
+        assert_eq!(
+            root.details.get("Hash Cond").and_then(Value::as_str),
+            Some("(o.customer_id = c.id)")
+        );
+        assert!(!root.details.contains_key("Plans"));
+
+        let scan = &root.children[0];
+        assert_eq!(scan.relation.as_deref(), Some("orders"));
+        assert!(scan.buffers.is_none());
+        assert!(scan.children.is_empty());
+    }
+
+    #[test]
+    fn rejects_unexpected_postgres_output() {
+        assert!(pg_plan("[]".to_string(), false).is_err());
+    }
+}
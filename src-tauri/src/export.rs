@@ -0,0 +1,555 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter, State};
+
+use crate::dialect;
+use crate::engine::Engine;
+use crate::history::{Execution, HistoryStore, Outcome};
+use crate::mysqlcmd;
+use crate::parquetexport::ParquetSink;
+use crate::pgcmd;
+use crate::resultset::{stream_mysql, stream_postgres, Cell, ColumnInfo, RowSink};
+use crate::runtime::run_blocking;
+use crate::xlsxexport::XlsxSink;
+
+const PROGRESS_EVERY: u64 = 1000;
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Tsv,
+    Json,
+    Ndjson,
+    Sql,
+    Xlsx,
+    Parquet,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum QuoteStyle {
+    Necessary,
+    Always,
+    NonNumeric,
+    Never,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CsvOptions {
+    delimiter: char,
+    quote_style: QuoteStyle,
+    header: bool,
+    null_value: String,
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            delimiter: ',',
+            quote_style: QuoteStyle::Necessary,
+            header: true,
+            null_value: String::new(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum UpsertMode {
+    // Overwrite the non-key columns of an existing row
+    Update,
+    // Keep the existing row
+    Ignore,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct SqlOptions {
+    dialect: Engine,
+    table: String,
+    // Rows per INSERT statement
+    batch_size: usize,
+    upsert: Option<UpsertMode>,
+    // Conflict target for PostgreSQL upserts, and the primary key of the
+    // CREATE TABLE prelude
+    key_columns: Vec<String>,
+    create_table: bool,
+}
+
+impl Default for SqlOptions {
+    fn default() -> Self {
+        SqlOptions {
+            dialect: Engine::Mysql,
+            table: String::new(),
+            batch_size: 100,
+            upsert: None,
+            key_columns: Vec::new(),
+            create_table: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ParquetOptions {
+    row_group_size: usize,
+}
+
+impl Default for ParquetOptions {
+    fn default() -> Self {
+        ParquetOptions {
+            row_group_size: 100_000,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct ExportProgress {
+    path: String,
+    rows: u64,
+    done: bool,
+}
+
+#[derive(Serialize)]
+pub struct ExportSummary {
+    path: String,
+    rows: u64,
+    bytes: u64,
+    warnings: Vec<String>,
+}
+
+// Runs `query` and streams the rows straight into `path`; the result set is
+// never held in memory. Progress is reported on the `export-progress` event.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_query(
+    app: AppHandle,
+    engine: Engine,
+    query: String,
+    path: String,
+    format: ExportFormat,
+    csv_options: Option<CsvOptions>,
+    sql_options: Option<SqlOptions>,
+    parquet_options: Option<ParquetOptions>,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+    history: State<'_, HistoryStore>,
+) -> Result<ExportSummary, String> {
+    let csv_options = csv_options.unwrap_or_default();
+    let sql_options = sql_options.unwrap_or_default();
+    let parquet_options = parquet_options.unwrap_or_default();
+    if matches!(format, ExportFormat::Sql) && sql_options.table.trim().is_empty() {
+        return Err("A target table name is required for SQL export".to_string());
+    }
+
+    let execution = Execution::current(engine, &query, &mysql_state, &pg_state)?;
+    let started = SystemTime::now();
+
+    let statement = query.clone();
+    let result = match engine {
+        Engine::Mysql => {
+            let (pool, db) = mysql_state.session()?;
+            run_blocking(move || {
+                let mut writer =
+                    open_writer(&path, format, csv_options, sql_options, parquet_options)?;
+                let mut sink = ProgressSink::new(&app, &path, writer.as_mut());
+                let mut conn = mysqlcmd::get_conn(&pool, &db)?;
+                let rows = stream_mysql(&mut conn, &statement, &mut sink)?;
+                summary(path, rows, writer.warnings())
+            })
+            .await
+        }
+        Engine::Postgres => {
+            let pool = pg_state.session()?;
+            run_blocking(move || {
+                let mut writer =
+                    open_writer(&path, format, csv_options, sql_options, parquet_options)?;
+                let mut sink = ProgressSink::new(&app, &path, writer.as_mut());
+                // The session's own client, so the export sees its temporary
+                // tables and settings like the query would
+                let mut client = pool.pinned()?;
+                let rows = stream_postgres(&mut *client, &statement, &mut sink)?;
+                summary(path, rows, writer.warnings())
+            })
+            .await
+        }
+    };
+
+    history.record(execution, started, &result);
+    result
+}
+
+impl Outcome for ExportSummary {
+    fn row_count(&self) -> Option<u64> {
+        Some(self.rows)
+    }
+}
+
+fn summary(path: String, rows: u64, warnings: Vec<String>) -> Result<ExportSummary, String> {
+    let bytes = fs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+    Ok(ExportSummary {
+        path,
+        rows,
+        bytes,
+        warnings,
+    })
+}
+
+fn open_writer(
+    path: &str,
+    format: ExportFormat,
+    csv_options: CsvOptions,
+    sql_options: SqlOptions,
+    parquet_options: ParquetOptions,
+) -> Result<Box<dyn RowSink + Send>, String> {
+    let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+
+    Ok(match format {
+        ExportFormat::Csv => Box::new(CsvSink::new(BufWriter::new(file), csv_options)?),
+        ExportFormat::Tsv => Box::new(CsvSink::new(
+            BufWriter::new(file),
+            CsvOptions {
+                delimiter: '\t',
+                ..csv_options
+            },
+        )?),
+        ExportFormat::Json => Box::new(JsonSink::new(BufWriter::new(file), false)),
+        ExportFormat::Ndjson => Box::new(JsonSink::new(BufWriter::new(file), true)),
+        ExportFormat::Sql => Box::new(SqlSink::new(BufWriter::new(file), sql_options)),
+        // The workbook is assembled in temp files and saved over `path` at
+        // the end
+        ExportFormat::Xlsx => Box::new(XlsxSink::new(path)),
+        ExportFormat::Parquet => Box::new(ParquetSink::new(file, parquet_options.row_group_size)),
+    })
+}
+
+// Wraps a writer and emits `export-progress` as rows go through.
+pub struct ProgressSink<'a> {
+    app: &'a AppHandle,
+    path: &'a str,
+    inner: &'a mut dyn RowSink,
+    rows: u64,
+}
+
+impl<'a> ProgressSink<'a> {
+    pub fn new(app: &'a AppHandle, path: &'a str, inner: &'a mut dyn RowSink) -> Self {
+        ProgressSink {
+            app,
+            path,
+            inner,
+            rows: 0,
+        }
+    }
+
+    fn emit(&self, done: bool) {
+        let _ = self.app.emit(
+            "export-progress",
+            ExportProgress {
+                path: self.path.to_string(),
+                rows: self.rows,
+                done,
+            },
+        );
+    }
+}
+
+impl RowSink for ProgressSink<'_> {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String> {
+        self.emit(false);
+        self.inner.start(columns)
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        self.inner.row(row)?;
+        self.rows += 1;
+        if self.rows % PROGRESS_EVERY == 0 {
+            self.emit(false);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.inner.finish()?;
+        self.emit(true);
+        Ok(())
+    }
+
+    fn multiple_result_sets(&self) -> bool {
+        self.inner.multiple_result_sets()
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.inner.warnings()
+    }
+}
+
+struct CsvSink {
+    writer: csv::Writer<BufWriter<File>>,
+    options: CsvOptions,
+}
+
+impl CsvSink {
+    fn new(file: BufWriter<File>, options: CsvOptions) -> Result<Self, String> {
+        if !options.delimiter.is_ascii() {
+            return Err("The delimiter must be a single ASCII character".to_string());
+        }
+        let writer = csv::WriterBuilder::new()
+            .delimiter(options.delimiter as u8)
+            .quote_style(match options.quote_style {
+                QuoteStyle::Necessary => csv::QuoteStyle::Necessary,
+                QuoteStyle::Always => csv::QuoteStyle::Always,
+                QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+                QuoteStyle::Never => csv::QuoteStyle::Never,
+            })
+            .from_writer(file);
+        Ok(CsvSink { writer, options })
+    }
+}
+
+impl RowSink for CsvSink {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String> {
+        if self.options.header {
+            self.writer
+                .write_record(columns.iter().map(|c| c.name.as_str()))
+                .map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        let fields = row.iter().map(|cell| {
+            cell.to_text()
+                .unwrap_or_else(|| self.options.null_value.clone())
+        });
+        self.writer.write_record(fields).map_err(|e| e.to_string())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+// JSON array of objects, or one object per line for NDJSON. Objects are
+// written by hand so keys keep the column order.
+struct JsonSink {
+    writer: BufWriter<File>,
+    lines: bool,
+    columns: Vec<String>,
+    first: bool,
+}
+
+impl JsonSink {
+    fn new(writer: BufWriter<File>, lines: bool) -> Self {
+        JsonSink {
+            writer,
+            lines,
+            columns: Vec::new(),
+            first: true,
+        }
+    }
+}
+
+impl RowSink for JsonSink {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String> {
+        self.columns = columns
+            .iter()
+            .map(|c| serde_json::to_string(&c.name))
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?;
+        if !self.lines {
+            self.writer.write_all(b"[").map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        let mut object = String::from("{");
+        for (i, (name, cell)) in self.columns.iter().zip(&row).enumerate() {
+            if i > 0 {
+                object.push(',');
+            }
+            object.push_str(name);
+            object.push(':');
+            object.push_str(&cell.to_json().to_string());
+        }
+        object.push('}');
+
+        let separator = match (self.lines, self.first) {
+            (true, _) => "",
+            (false, true) => "\n",
+            (false, false) => ",\n",
+        };
+        self.first = false;
+
+        write!(self.writer, "{}{}", separator, object).map_err(|e| e.to_string())?;
+        if self.lines {
+            self.writer.write_all(b"\n").map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        if !self.lines {
+            self.writer.write_all(b"\n]\n").map_err(|e| e.to_string())?;
+        }
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+}
+
+// INSERT statements for `options.table`, `batch_size` rows at a time, with
+// literals rendered for the target dialect rather than the source engine.
+struct SqlSink {
+    writer: BufWriter<File>,
+    options: SqlOptions,
+    columns: Vec<String>,
+    // Everything from the table name to VALUES, rendered once
+    insert: String,
+    // ON CONFLICT / ON DUPLICATE KEY clause, if any
+    conflict: String,
+    batch: Vec<String>,
+}
+
+impl SqlSink {
+    fn new(writer: BufWriter<File>, options: SqlOptions) -> Self {
+        SqlSink {
+            writer,
+            options,
+            columns: Vec::new(),
+            insert: String::new(),
+            conflict: String::new(),
+            batch: Vec::new(),
+        }
+    }
+
+    fn conflict_clause(&self) -> Result<String, String> {
+        let dialect = self.options.dialect;
+        let keys = &self.options.key_columns;
+        let updates: Vec<String> = self
+            .columns
+            .iter()
+            .filter(|c| !keys.contains(c))
+            .map(|c| {
+                let name = dialect::quote_ident(c, dialect);
+                match dialect {
+                    Engine::Mysql => format!("{} = VALUES({})", name, name),
+                    Engine::Postgres => format!("{} = EXCLUDED.{}", name, name),
+                }
+            })
+            .collect();
+
+        Ok(match (self.options.upsert, dialect) {
+            (None, _) | (Some(UpsertMode::Ignore), Engine::Mysql) => String::new(),
+            (Some(UpsertMode::Ignore), Engine::Postgres) => " ON CONFLICT DO NOTHING".to_string(),
+            (Some(UpsertMode::Update), Engine::Mysql) if updates.is_empty() => {
+                // Every column is part of the key; updating a key column to
+                // itself is the idiomatic no-op
+                let name = dialect::quote_ident(&self.columns[0], dialect);
+                format!(" ON DUPLICATE KEY UPDATE {} = {}", name, name)
+            }
+            (Some(UpsertMode::Update), Engine::Mysql) => {
+                format!(" ON DUPLICATE KEY UPDATE {}", updates.join(", "))
+            }
+            (Some(UpsertMode::Update), Engine::Postgres) => {
+                if keys.is_empty() {
+                    return Err(
+                        "PostgreSQL upserts need the key columns of a unique constraint"
+                            .to_string(),
+                    );
+                }
+                let target = dialect::column_list(keys, dialect);
+                if updates.is_empty() {
+                    format!(" ON CONFLICT ({}) DO NOTHING", target)
+                } else {
+                    format!(
+                        " ON CONFLICT ({}) DO UPDATE SET {}",
+                        target,
+                        updates.join(", ")
+                    )
+                }
+            }
+        })
+    }
+
+    fn flush_batch(&mut self) -> Result<(), String> {
+        if self.batch.is_empty() {
+            return Ok(());
+        }
+        write!(
+            self.writer,
+            "{}\n  {}{};\n",
+            self.insert,
+            self.batch.join(",\n  "),
+            self.conflict
+        )
+        .map_err(|e| e.to_string())?;
+        self.batch.clear();
+        Ok(())
+    }
+}
+
+impl RowSink for SqlSink {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String> {
+        let dialect = self.options.dialect;
+        self.columns = columns.iter().map(|c| c.name.clone()).collect();
+
+        if let Some(missing) = self
+            .options
+            .key_columns
+            .iter()
+            .find(|k| !self.columns.contains(k))
+        {
+            return Err(format!("Key column {} is not in the result", missing));
+        }
+        if self.columns.is_empty() {
+            return Err("The query returned no columns".to_string());
+        }
+
+        if let Some(prelude) = dialect::script_prelude(dialect) {
+            writeln!(self.writer, "{}\n", prelude).map_err(|e| e.to_string())?;
+        }
+        if self.options.create_table {
+            let ddl = dialect::create_table(
+                &self.options.table,
+                columns,
+                &self.options.key_columns,
+                dialect,
+            );
+            writeln!(self.writer, "{}\n", ddl).map_err(|e| e.to_string())?;
+        }
+
+        let ignore = matches!(
+            (self.options.upsert, dialect),
+            (Some(UpsertMode::Ignore), Engine::Mysql)
+        );
+        self.insert = format!(
+            "INSERT {}INTO {} ({}) VALUES",
+            if ignore { "IGNORE " } else { "" },
+            dialect::quote_table(&self.options.table, dialect),
+            dialect::column_list(&self.columns, dialect)
+        );
+        self.conflict = self.conflict_clause()?;
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        let values: Vec<String> = row
+            .iter()
+            .map(|cell| dialect::literal(cell, self.options.dialect))
+            .collect();
+        self.batch.push(format!("({})", values.join(", ")));
+
+        if self.batch.len() >= self.options.batch_size.max(1) {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.flush_batch()?;
+        self.writer.flush().map_err(|e| e.to_string())
+    }
+}
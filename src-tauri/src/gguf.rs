@@ -0,0 +1,442 @@
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"GGUF";
+
+// Strings longer than this are taken as a corrupt header rather than
+// allocated
+const MAX_STRING_LEN: u64 = 1024 * 1024;
+
+// Arrays of arrays are allowed, but not without end
+const MAX_ARRAY_DEPTH: u32 = 8;
+
+// Value types of the key/value section
+const TYPE_UINT8: u32 = 0;
+const TYPE_INT8: u32 = 1;
+const TYPE_UINT16: u32 = 2;
+const TYPE_INT16: u32 = 3;
+const TYPE_UINT32: u32 = 4;
+const TYPE_INT32: u32 = 5;
+const TYPE_FLOAT32: u32 = 6;
+const TYPE_BOOL: u32 = 7;
+const TYPE_STRING: u32 = 8;
+const TYPE_ARRAY: u32 = 9;
+const TYPE_UINT64: u32 = 10;
+const TYPE_INT64: u32 = 11;
+const TYPE_FLOAT64: u32 = 12;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct GgufInfo {
+    pub version: u32,
+    pub architecture: Option<String>,
+    pub name: Option<String>,
+    // e.g. `Q4_K_M`, from `general.file_type`
+    pub quantization: Option<String>,
+    pub context_length: Option<u64>,
+    pub tensor_count: u64,
+    pub size: u64,
+}
+
+enum Value {
+    Int(u64),
+    Text(String),
+    Other,
+}
+
+// Reads the header of a GGUF file: the magic, the version and the metadata
+// the model picker shows. Tensor data is not touched.
+pub fn inspect(path: &Path) -> Result<GgufInfo, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let size = file.metadata().map_err(|e| e.to_string())?.len();
+    read_header(BufReader::new(file), size, &path.display().to_string())
+}
+
+// Reads the header from `inner`, a file of `size` bytes called `name` in
+// error messages.
+fn read_header<R: Read>(inner: R, size: u64, name: &str) -> Result<GgufInfo, String> {
+    let mut reader = Reader {
+        inner,
+        version: 0,
+        remaining: size,
+    };
+
+    let mut magic = [0u8; 4];
+    reader
+        .read(&mut magic)
+        .map_err(|_| format!("{} is not a GGUF file", name))?;
+    if &magic != MAGIC {
+        return Err(format!("{} is not a GGUF file", name));
+    }
+    let version = reader.u32()?;
+    if !(1..=3).contains(&version) {
+        return Err(format!(
+            "{} uses GGUF version {}, which is not supported",
+            name, version
+        ));
+    }
+    reader.version = version;
+    let tensor_count = reader.count()?;
+    let kv_count = reader.count()?;
+
+    let mut architecture = None;
+    let mut name = None;
+    let mut file_type = None;
+    // Keyed by architecture, which comes first in practice but isn't
+    // required to
+    let mut context_lengths = Vec::new();
+    for _ in 0..kv_count {
+        let key = reader.string()?;
+        let value_type = reader.u32()?;
+        let value = reader.value(value_type, 0)?;
+        match (key.as_str(), value) {
+            ("general.architecture", Value::Text(text)) => architecture = Some(text),
+            ("general.name", Value::Text(text)) => name = Some(text),
+            ("general.file_type", Value::Int(n)) => file_type = Some(n),
+            (key, Value::Int(n)) if key.ends_with(".context_length") => {
+                context_lengths.push((key.trim_end_matches(".context_length").to_string(), n))
+            }
+            _ => {}
+        }
+    }
+
+    let context_length = architecture.as_ref().and_then(|arch| {
+        context_lengths
+            .iter()
+            .find(|(prefix, _)| prefix == arch)
+            .map(|(_, n)| *n)
+    });
+    Ok(GgufInfo {
+        version,
+        architecture,
+        name,
+        quantization: file_type.map(quantization_name),
+        context_length,
+        tensor_count,
+        size,
+    })
+}
+
+// llama.cpp's names for `general.file_type`
+fn quantization_name(file_type: u64) -> String {
+    let name = match file_type {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        n => return format!("type {}", n),
+    };
+    name.to_string()
+}
+
+struct Reader<R> {
+    inner: R,
+    version: u32,
+    // Bytes left in the file, which bound the length an array can claim
+    remaining: u64,
+}
+
+impl<R: Read> Reader<R> {
+    fn read(&mut self, buffer: &mut [u8]) -> Result<(), String> {
+        self.inner
+            .read_exact(buffer)
+            .map_err(|e| format!("Truncated GGUF header: {}", e))?;
+        self.remaining = self.remaining.saturating_sub(buffer.len() as u64);
+        Ok(())
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let mut buffer = [0u8; N];
+        self.read(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    // Counts and lengths were 32-bit in version 1
+    fn count(&mut self) -> Result<u64, String> {
+        if self.version == 1 {
+            self.u32().map(u64::from)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.count()?;
+        if len > MAX_STRING_LEN {
+            return Err(format!("Corrupt GGUF header: string of {} bytes", len));
+        }
+        let mut buffer = vec![0u8; len as usize];
+        self.read(&mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+
+    fn skip(&mut self, len: u64) -> Result<(), String> {
+        let skipped = io::copy(&mut (&mut self.inner).take(len), &mut io::sink())
+            .map_err(|e| format!("Truncated GGUF header: {}", e))?;
+        if skipped < len {
+            return Err("Truncated GGUF header: unexpected end of file".to_string());
+        }
+        self.remaining = self.remaining.saturating_sub(len);
+        Ok(())
+    }
+
+    fn value(&mut self, value_type: u32, depth: u32) -> Result<Value, String> {
+        let value = match value_type {
+            TYPE_UINT8 => Value::Int(u64::from(self.bytes::<1>()?[0])),
+            TYPE_UINT16 => Value::Int(u64::from(u16::from_le_bytes(self.bytes()?))),
+            TYPE_UINT32 => Value::Int(u64::from(self.u32()?)),
+            TYPE_UINT64 => Value::Int(self.u64()?),
+            TYPE_INT8 => signed(i8::from_le_bytes(self.bytes()?).into()),
+            TYPE_INT16 => signed(i16::from_le_bytes(self.bytes()?).into()),
+            TYPE_INT32 => signed(i32::from_le_bytes(self.bytes()?).into()),
+            TYPE_INT64 => signed(i64::from_le_bytes(self.bytes()?)),
+            TYPE_BOOL => self.skip(1).map(|_| Value::Other)?,
+            TYPE_FLOAT32 => self.skip(4).map(|_| Value::Other)?,
+            TYPE_FLOAT64 => self.skip(8).map(|_| Value::Other)?,
+            TYPE_STRING => Value::Text(self.string()?),
+            // Vocabularies are arrays of many thousand strings, so arrays
+            // are skipped without being kept
+            TYPE_ARRAY => {
+                if depth == MAX_ARRAY_DEPTH {
+                    return Err(format!(
+                        "Corrupt GGUF header: arrays nested more than {} deep",
+                        MAX_ARRAY_DEPTH
+                    ));
+                }
+                let item_type = self.u32()?;
+                let len = self.count()?;
+                // Strings and arrays take at least their length
+                let count_width = if self.version == 1 { 4 } else { 8 };
+                let width = item_width(item_type);
+                if len.saturating_mul(width.unwrap_or(count_width)) > self.remaining {
+                    return Err(format!(
+                        "Corrupt GGUF header: array of {} items is larger than the file",
+                        len
+                    ));
+                }
+                match width {
+                    Some(width) => self.skip(len * width)?,
+                    None => {
+                        for _ in 0..len {
+                            self.value(item_type, depth + 1)?;
+                        }
+                    }
+                }
+                Value::Other
+            }
+            n => return Err(format!("Corrupt GGUF header: unknown value type {}", n)),
+        };
+        Ok(value)
+    }
+}
+
+fn signed(n: i64) -> Value {
+    u64::try_from(n).map_or(Value::Other, Value::Int)
+}
+
+// Size of a fixed-width value type
+fn item_width(value_type: u32) -> Option<u64> {
+    match value_type {
+        TYPE_UINT8 | TYPE_INT8 | TYPE_BOOL => Some(1),
+        TYPE_UINT16 | TYPE_INT16 => Some(2),
+        TYPE_UINT32 | TYPE_INT32 | TYPE_FLOAT32 => Some(4),
+        TYPE_UINT64 | TYPE_INT64 | TYPE_FLOAT64 => Some(8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A header under construction. Counts are 32-bit for version 1, as
+    // they were in that version.
+    struct Fixture {
+        version: u32,
+        bytes: Vec<u8>,
+    }
+
+    impl Fixture {
+        fn new(version: u32, tensor_count: u64, kv_count: u64) -> Self {
+            let mut fixture = Fixture {
+                version,
+                bytes: MAGIC.to_vec(),
+            };
+            fixture.bytes.extend(version.to_le_bytes());
+            fixture.count(tensor_count);
+            fixture.count(kv_count);
+            fixture
+        }
+
+        fn count(&mut self, n: u64) -> &mut Self {
+            if self.version == 1 {
+                self.bytes.extend((n as u32).to_le_bytes());
+            } else {
+                self.bytes.extend(n.to_le_bytes());
+            }
+            self
+        }
+
+        fn u32(&mut self, n: u32) -> &mut Self {
+            self.bytes.extend(n.to_le_bytes());
+            self
+        }
+
+        fn string(&mut self, text: &str) -> &mut Self {
+            self.count(text.len() as u64);
+            self.bytes.extend(text.as_bytes());
+            self
+        }
+
+        fn text(&mut self, key: &str, value: &str) -> &mut Self {
+            self.string(key).u32(TYPE_STRING).string(value)
+        }
+
+        fn read(&self) -> Result<GgufInfo, String> {
+            read_header(&self.bytes[..], self.bytes.len() as u64, "model.gguf")
+        }
+    }
+
+    fn valid(version: u32) -> Fixture {
+        let mut fixture = Fixture::new(version, 291, 6);
+        fixture
+            .text("general.architecture", "llama")
+            .text("general.name", "sqlcoder-7b")
+            .string("general.file_type")
+            .u32(TYPE_UINT32)
+            .u32(15)
+            .string("llama.context_length")
+            .u32(TYPE_UINT32)
+            .u32(4096)
+            .string("tokenizer.ggml.tokens")
+            .u32(TYPE_ARRAY)
+            .u32(TYPE_STRING)
+            .count(2)
+            .string("<s>")
+            .string("</s>")
+            .string("tokenizer.ggml.token_type")
+            .u32(TYPE_ARRAY)
+            .u32(TYPE_INT32)
+            .count(2)
+            .u32(3)
+            .u32(3);
+        fixture
+    }
+
+    #[test]
+    fn reads_metadata() {
+        let info = valid(3).read().unwrap();
+        assert_eq!(info.version, 3);
+        assert_eq!(info.architecture.as_deref(), Some("llama"));
+        assert_eq!(info.name.as_deref(), Some("sqlcoder-7b"));
+        assert_eq!(info.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(info.context_length, Some(4096));
+        assert_eq!(info.tensor_count, 291);
+    }
+
+    #[test]
+    fn reads_version_1_counts() {
+        let fixture = valid(1);
+        assert_eq!(fixture.bytes.len(), valid(3).bytes.len() - 4 * 14);
+        let info = fixture.read().unwrap();
+        assert_eq!(info.version, 1);
+        assert_eq!(info.architecture.as_deref(), Some("llama"));
+        assert_eq!(info.context_length, Some(4096));
+        assert_eq!(info.tensor_count, 291);
+    }
+
+    #[test]
+    fn rejects_truncated_headers() {
+        let fixture = valid(3);
+        for len in 0..fixture.bytes.len() {
+            let bytes = &fixture.bytes[..len];
+            assert!(read_header(bytes, len as u64, "model.gguf").is_err());
+        }
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let bytes = b"PK\x03\x04 not a model";
+        let error = read_header(&bytes[..], bytes.len() as u64, "model.gguf").unwrap_err();
+        assert_eq!(error, "model.gguf is not a GGUF file");
+
+        let mut fixture = valid(3);
+        fixture.bytes[4..8].copy_from_slice(&4u32.to_le_bytes());
+        assert!(fixture.read().unwrap_err().contains("version 4"));
+    }
+
+    #[test]
+    fn rejects_unknown_value_types() {
+        let mut fixture = Fixture::new(3, 0, 1);
+        fixture.string("general.foo").u32(13).u32(0);
+        let error = fixture.read().unwrap_err();
+        assert!(error.contains("unknown value type 13"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_arrays_larger_than_the_file() {
+        let mut fixture = Fixture::new(3, 0, 1);
+        fixture
+            .string("tokenizer.ggml.tokens")
+            .u32(TYPE_ARRAY)
+            .u32(TYPE_STRING)
+            .count(u64::MAX);
+        let error = fixture.read().unwrap_err();
+        assert!(error.contains("larger than the file"), "{}", error);
+
+        let mut fixture = Fixture::new(3, 0, 1);
+        fixture
+            .string("tokenizer.ggml.scores")
+            .u32(TYPE_ARRAY)
+            .u32(TYPE_FLOAT32)
+            .count(1 << 62);
+        assert!(fixture.read().is_err());
+    }
+
+    #[test]
+    fn rejects_deeply_nested_arrays() {
+        let mut fixture = Fixture::new(3, 0, 1);
+        fixture.string("general.nested").u32(TYPE_ARRAY);
+        for _ in 0..MAX_ARRAY_DEPTH {
+            fixture.u32(TYPE_ARRAY).count(1);
+        }
+        fixture.u32(TYPE_UINT8).count(0);
+        let error = fixture.read().unwrap_err();
+        assert!(error.contains("nested"), "{}", error);
+    }
+}
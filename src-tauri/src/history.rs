@@ -0,0 +1,421 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::engine::Engine;
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::stats::QueryResponse;
+
+const HISTORY_FILE: &str = "history.jsonl";
+const SETTINGS_FILE: &str = "history_settings.json";
+
+// Entries kept; older ones are dropped as new ones are recorded
+const MAX_ENTRIES: usize = 10_000;
+// Dropped entries stay in history.jsonl until this many have piled up, so
+// the file isn't rewritten on every statement
+const COMPACT_AFTER: usize = 1_000;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    id: u64,
+    query: String,
+    engine: Engine,
+    connection: Option<String>,
+    database: Option<String>,
+    executed_at: u64,
+    duration_ms: f64,
+    row_count: Option<u64>,
+    success: bool,
+    error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistorySettings {
+    enabled: bool,
+    // Statements containing any of these (case-insensitive) are not recorded
+    exclude_patterns: Vec<String>,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        HistorySettings {
+            enabled: true,
+            exclude_patterns: vec!["password".to_string(), "identified by".to_string()],
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+pub struct HistoryFilter {
+    text: Option<String>,
+    connection: Option<String>,
+    from: Option<u64>,
+    to: Option<u64>,
+    limit: Option<usize>,
+}
+
+// What was run, and where, for one history entry.
+pub struct Execution<'a> {
+    pub engine: Engine,
+    pub connection: Option<String>,
+    pub database: Option<String>,
+    pub query: &'a str,
+}
+
+impl<'a> Execution<'a> {
+    // `query` on the current connection of `engine`.
+    pub fn current(
+        engine: Engine,
+        query: &'a str,
+        mysql_state: &mysqlcmd::DbConnection,
+        pg_state: &pgcmd::DbConnection,
+    ) -> Result<Self, String> {
+        let (connection, database) = match engine {
+            Engine::Mysql => (mysql_state.label()?, mysql_state.selected_db()?),
+            Engine::Postgres => (pg_state.label()?, pg_state.selected_db()?),
+        };
+        Ok(Execution {
+            engine,
+            connection,
+            database,
+            query,
+        })
+    }
+}
+
+// What a history entry keeps from a command's result.
+pub trait Outcome {
+    // Time the statement itself took, when the command measured it
+    fn duration_ms(&self) -> Option<f64> {
+        None
+    }
+
+    fn row_count(&self) -> Option<u64>;
+}
+
+impl Outcome for QueryResponse {
+    fn duration_ms(&self) -> Option<f64> {
+        Some(self.stats.wall_time_ms)
+    }
+
+    fn row_count(&self) -> Option<u64> {
+        Some(self.stats.rows_affected.unwrap_or(self.stats.rows_returned))
+    }
+}
+
+// Writes to history.jsonl, done in order on the writer thread
+enum FileWrite {
+    Append(HistoryEntry),
+    // Replaces the file; the result is sent back when someone waits for it
+    Rewrite(Vec<HistoryEntry>, Option<Sender<Result<(), String>>>),
+}
+
+struct HistoryData {
+    entries: Vec<HistoryEntry>,
+    settings: HistorySettings,
+    next_id: u64,
+    // Lines in history.jsonl, including entries already dropped from memory
+    file_entries: usize,
+}
+
+pub struct HistoryStore {
+    dir: PathBuf,
+    data: Mutex<HistoryData>,
+    app: AppHandle,
+    writer: Mutex<Sender<FileWrite>>,
+}
+
+impl HistoryStore {
+    pub fn load(dir: PathBuf, app: AppHandle) -> Result<Self, String> {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+
+        let mut entries = Vec::new();
+        if let Ok(file) = File::open(dir.join(HISTORY_FILE)) {
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| e.to_string())?;
+                // Skip lines that were cut short by a crash rather than
+                // refusing to start
+                if let Ok(entry) = serde_json::from_str::<HistoryEntry>(&line) {
+                    entries.push(entry);
+                }
+            }
+        }
+        let file_entries = entries.len();
+        let excess = entries.len().saturating_sub(MAX_ENTRIES);
+        entries.drain(..excess);
+
+        let settings = fs::read_to_string(dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+
+        let next_id = entries.iter().map(|e| e.id).max().unwrap_or(0) + 1;
+
+        let (writer, writes) = mpsc::channel::<FileWrite>();
+        let path = dir.join(HISTORY_FILE);
+        let writer_app = app.clone();
+        thread::spawn(move || {
+            for write in writes {
+                let result = match write {
+                    FileWrite::Append(entry) => append(&path, &entry),
+                    FileWrite::Rewrite(entries, None) => rewrite(&path, &entries),
+                    FileWrite::Rewrite(entries, Some(reply)) => {
+                        let _ = reply.send(rewrite(&path, &entries));
+                        Ok(())
+                    }
+                };
+                if let Err(e) = result {
+                    report(&writer_app, &e);
+                }
+            }
+        });
+
+        Ok(HistoryStore {
+            dir,
+            data: Mutex::new(HistoryData {
+                entries,
+                settings,
+                next_id,
+                file_entries,
+            }),
+            app,
+            writer: Mutex::new(writer),
+        })
+    }
+
+    // Recording is best effort; a failure here never fails the command
+    // itself and is reported on `history-error` instead. The file is written
+    // on the writer thread.
+    pub fn record<T: Outcome>(
+        &self,
+        execution: Execution,
+        started: SystemTime,
+        result: &Result<T, String>,
+    ) {
+        if let Err(e) = self.try_record(execution, started, result) {
+            report(&self.app, &e);
+        }
+    }
+
+    fn try_record<T: Outcome>(
+        &self,
+        execution: Execution,
+        started: SystemTime,
+        result: &Result<T, String>,
+    ) -> Result<(), String> {
+        let mut data = self.data.lock().map_err(|e| e.to_string())?;
+        if !data.settings.enabled || is_excluded(&data.settings, execution.query) {
+            return Ok(());
+        }
+
+        let elapsed_ms = started.elapsed().unwrap_or_default().as_secs_f64() * 1000.0;
+        let (duration_ms, row_count, error) = match result {
+            Ok(outcome) => (
+                outcome.duration_ms().unwrap_or(elapsed_ms),
+                outcome.row_count(),
+                None,
+            ),
+            Err(e) => (elapsed_ms, None, Some(e.clone())),
+        };
+
+        let entry = HistoryEntry {
+            id: data.next_id,
+            query: execution.query.to_string(),
+            engine: execution.engine,
+            connection: execution.connection,
+            database: execution.database,
+            executed_at: epoch_millis(started),
+            duration_ms,
+            row_count,
+            success: error.is_none(),
+            error,
+        };
+
+        data.next_id += 1;
+        data.entries.push(entry.clone());
+        self.write(FileWrite::Append(entry))?;
+        data.file_entries += 1;
+
+        if data.entries.len() > MAX_ENTRIES {
+            let excess = data.entries.len() - MAX_ENTRIES;
+            data.entries.drain(..excess);
+        }
+        if data.file_entries > MAX_ENTRIES + COMPACT_AFTER {
+            self.write(FileWrite::Rewrite(data.entries.clone(), None))?;
+            data.file_entries = data.entries.len();
+        }
+        Ok(())
+    }
+
+    fn write(&self, write: FileWrite) -> Result<(), String> {
+        let writer = self.writer.lock().map_err(|e| e.to_string())?;
+        writer
+            .send(write)
+            .map_err(|_| "The history writer has stopped".to_string())
+    }
+
+    // Replaces the file with `entries` once the writes queued before it are
+    // done.
+    fn rewrite(&self, entries: Vec<HistoryEntry>) -> Result<(), String> {
+        let (reply, result) = mpsc::channel();
+        self.write(FileWrite::Rewrite(entries, Some(reply)))?;
+        result
+            .recv()
+            .map_err(|_| "The history writer has stopped".to_string())?
+    }
+
+    fn entry(&self, id: u64) -> Result<HistoryEntry, String> {
+        let data = self.data.lock().map_err(|e| e.to_string())?;
+        data.entries
+            .iter()
+            .find(|e| e.id == id)
+            .cloned()
+            .ok_or_else(|| format!("History entry {} not found", id))
+    }
+}
+
+fn append(path: &Path, entry: &HistoryEntry) -> Result<(), String> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| e.to_string())?;
+    let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
+
+fn rewrite(path: &Path, entries: &[HistoryEntry]) -> Result<(), String> {
+    let tmp_path = path.with_extension("jsonl.tmp");
+
+    let mut writer = BufWriter::new(File::create(&tmp_path).map_err(|e| e.to_string())?);
+    for entry in entries {
+        let line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        writeln!(writer, "{}", line).map_err(|e| e.to_string())?;
+    }
+    writer.flush().map_err(|e| e.to_string())?;
+    drop(writer);
+
+    fs::rename(&tmp_path, path).map_err(|e| e.to_string())
+}
+
+fn report(app: &AppHandle, error: &str) {
+    let _ = app.emit(
+        "history-error",
+        format!("Failed to record query history: {}", error),
+    );
+}
+
+fn is_excluded(settings: &HistorySettings, query: &str) -> bool {
+    let query = query.to_lowercase();
+    settings
+        .exclude_patterns
+        .iter()
+        .filter(|p| !p.is_empty())
+        .any(|p| query.contains(&p.to_lowercase()))
+}
+
+pub fn epoch_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[tauri::command]
+pub fn search_history(
+    filter: HistoryFilter,
+    history: State<'_, HistoryStore>,
+) -> Result<Vec<HistoryEntry>, String> {
+    let data = history.data.lock().map_err(|e| e.to_string())?;
+    let text = filter.text.map(|t| t.to_lowercase());
+
+    let entries = data
+        .entries
+        .iter()
+        .rev()
+        .filter(|e| {
+            text.as_ref()
+                .map_or(true, |t| e.query.to_lowercase().contains(t))
+        })
+        .filter(|e| {
+            filter
+                .connection
+                .as_ref()
+                .map_or(true, |c| e.connection.as_ref() == Some(c))
+        })
+        .filter(|e| filter.from.map_or(true, |from| e.executed_at >= from))
+        .filter(|e| filter.to.map_or(true, |to| e.executed_at <= to))
+        .take(filter.limit.unwrap_or(500))
+        .cloned()
+        .collect();
+
+    Ok(entries)
+}
+
+// Re-runs an entry on the current connection for its engine.
+#[tauri::command]
+pub async fn rerun_history_entry(
+    id: u64,
+    history: State<'_, HistoryStore>,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+) -> Result<QueryResponse, String> {
+    let entry = history.entry(id)?;
+
+    match entry.engine {
+        Engine::Mysql => mysqlcmd::execute_query(entry.query, mysql_state, history).await,
+        Engine::Postgres => pgcmd::pg_execute_query(entry.query, pg_state, history).await,
+    }
+}
+
+// Removes the given entries, everything before `before`, or (with neither)
+// the whole history.
+#[tauri::command]
+pub fn purge_history(
+    ids: Option<Vec<u64>>,
+    before: Option<u64>,
+    history: State<'_, HistoryStore>,
+) -> Result<usize, String> {
+    let mut data = history.data.lock().map_err(|e| e.to_string())?;
+    let count = data.entries.len();
+
+    match (&ids, before) {
+        (None, None) => data.entries.clear(),
+        _ => data.entries.retain(|e| {
+            let listed = ids.as_ref().is_some_and(|ids| ids.contains(&e.id));
+            let old = before.is_some_and(|before| e.executed_at < before);
+            !(listed || old)
+        }),
+    }
+
+    history.rewrite(data.entries.clone())?;
+    data.file_entries = data.entries.len();
+
+    Ok(count - data.entries.len())
+}
+
+#[tauri::command]
+pub fn get_history_settings(history: State<'_, HistoryStore>) -> Result<HistorySettings, String> {
+    let data = history.data.lock().map_err(|e| e.to_string())?;
+    Ok(data.settings.clone())
+}
+
+#[tauri::command]
+pub fn set_history_settings(
+    settings: HistorySettings,
+    history: State<'_, HistoryStore>,
+) -> Result<(), String> {
+    let mut data = history.data.lock().map_err(|e| e.to_string())?;
+
+    let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+    fs::write(history.dir.join(SETTINGS_FILE), json).map_err(|e| e.to_string())?;
+
+    data.settings = settings;
+    Ok(())
+}
@@ -0,0 +1,1021 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use mysql::prelude::*;
+use mysql::LocalInfileHandler;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, State};
+
+use crate::dialect;
+use crate::engine::{Connection, Engine, Session};
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::resultset::{datetime_text, to_hex, Cell, CellKind, ColumnInfo};
+use crate::runtime::run_blocking;
+
+pub const PROGRESS_EVERY: u64 = 1000;
+pub const PREVIEW_ROWS: usize = 20;
+// Widest DECIMAL MySQL accepts; wider numbers are imported as text
+const MAX_DECIMAL_PRECISION: u32 = 65;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ImportColumn {
+    // Field name in the file
+    pub source: String,
+    pub target: String,
+    pub kind: CellKind,
+    // Column type used when the table is created
+    pub sql_type: String,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MysqlLoadMethod {
+    #[default]
+    Insert,
+    LoadData,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ImportOptions {
+    pub table: String,
+    // The mapping from the preview, possibly edited; inferred when absent
+    pub columns: Option<Vec<ImportColumn>>,
+    pub create_table: bool,
+    pub batch_size: usize,
+    pub mysql_method: MysqlLoadMethod,
+    // Defaults to `<file>.rejects.csv`
+    pub reject_path: Option<String>,
+    pub sample_rows: usize,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            table: String::new(),
+            columns: None,
+            create_table: false,
+            batch_size: 1000,
+            mysql_method: MysqlLoadMethod::Insert,
+            reject_path: None,
+            sample_rows: 1000,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CsvReadOptions {
+    delimiter: char,
+    quote: char,
+    header: bool,
+    // Fields equal to this are NULL
+    null_value: String,
+}
+
+impl Default for CsvReadOptions {
+    fn default() -> Self {
+        CsvReadOptions {
+            delimiter: ',',
+            quote: '"',
+            header: true,
+            null_value: String::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ImportPreview {
+    pub columns: Vec<ImportColumn>,
+    pub rows: Vec<Vec<String>>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ImportProgress {
+    path: String,
+    rows_read: u64,
+    imported: u64,
+    rejected: u64,
+    done: bool,
+}
+
+#[derive(Serialize)]
+pub struct ImportSummary {
+    pub rows_read: u64,
+    pub imported: u64,
+    pub rejected: u64,
+    pub reject_path: Option<String>,
+    pub table_created: bool,
+}
+
+// Reads the header and a sample, and returns the inferred column mapping
+// with the first rows as they are in the file.
+#[tauri::command]
+pub async fn preview_csv(
+    engine: Engine,
+    path: String,
+    csv_options: Option<CsvReadOptions>,
+    sample_rows: Option<usize>,
+) -> Result<ImportPreview, String> {
+    let csv_options = csv_options.unwrap_or_default();
+    let sample_rows = sample_rows.unwrap_or(ImportOptions::default().sample_rows);
+
+    run_blocking(move || {
+        let (headers, sample) = read_csv_sample(&path, &csv_options, sample_rows)?;
+        let columns = infer_csv_columns(&headers, &sample, &csv_options, engine);
+        let rows = sample
+            .into_iter()
+            .take(PREVIEW_ROWS)
+            .map(|record| record.iter().map(String::from).collect())
+            .collect();
+        Ok(ImportPreview { columns, rows })
+    })
+    .await
+}
+
+// Loads a CSV file into `options.table` on the current connection. Rows that
+// don't parse or that the server refuses go to the reject file; the rest are
+// committed batch by batch. Progress is reported on `import-progress`.
+#[tauri::command]
+pub async fn import_csv(
+    app: AppHandle,
+    engine: Engine,
+    path: String,
+    csv_options: Option<CsvReadOptions>,
+    options: ImportOptions,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+) -> Result<ImportSummary, String> {
+    let csv_options = csv_options.unwrap_or_default();
+    if options.table.trim().is_empty() {
+        return Err("A target table name is required".to_string());
+    }
+
+    let session = Session::open(engine, &mysql_state, &pg_state)?;
+    run_blocking(move || {
+        let connection = session.connect()?;
+        import_csv_file(&app, connection, &path, &csv_options, options)
+    })
+    .await
+}
+
+fn csv_reader(path: &str, options: &CsvReadOptions) -> Result<csv::Reader<File>, String> {
+    if !options.delimiter.is_ascii() || !options.quote.is_ascii() {
+        return Err("The delimiter and quote must be single ASCII characters".to_string());
+    }
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+    Ok(csv::ReaderBuilder::new()
+        .delimiter(options.delimiter as u8)
+        .quote(options.quote as u8)
+        .has_headers(options.header)
+        // Ragged rows are rejected one by one instead of failing the file
+        .flexible(true)
+        .from_reader(file))
+}
+
+// Header names (`column_N` without a header row) and up to `limit` records.
+fn read_csv_sample(
+    path: &str,
+    options: &CsvReadOptions,
+    limit: usize,
+) -> Result<(Vec<String>, Vec<csv::StringRecord>), String> {
+    let mut reader = csv_reader(path, options)?;
+
+    let mut sample = Vec::new();
+    for record in reader.records().take(limit) {
+        match record {
+            Ok(record) => sample.push(record),
+            Err(e) if e.is_io_error() => return Err(e.to_string()),
+            // Bad records are reported by the import itself
+            Err(_) => {}
+        }
+    }
+
+    let headers = if options.header {
+        reader
+            .headers()
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(String::from)
+            .collect()
+    } else {
+        let width = sample.iter().map(|r| r.len()).max().unwrap_or(0);
+        (1..=width).map(|i| format!("column_{}", i)).collect()
+    };
+
+    Ok((headers, sample))
+}
+
+fn infer_csv_columns(
+    headers: &[String],
+    sample: &[csv::StringRecord],
+    options: &CsvReadOptions,
+    engine: Engine,
+) -> Vec<ImportColumn> {
+    headers
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let values: Vec<&str> = sample
+                .iter()
+                .filter_map(|record| record.get(i))
+                .filter(|value| *value != options.null_value)
+                .collect();
+            import_column(name, infer_column(name, &values), engine)
+        })
+        .collect()
+}
+
+pub fn import_column(source: &str, column: ColumnInfo, engine: Engine) -> ImportColumn {
+    ImportColumn {
+        source: source.to_string(),
+        target: source.to_string(),
+        kind: column.kind,
+        sql_type: dialect::column_type(&column, engine),
+    }
+}
+
+fn import_csv_file(
+    app: &AppHandle,
+    connection: Connection,
+    path: &str,
+    csv_options: &CsvReadOptions,
+    options: ImportOptions,
+) -> Result<ImportSummary, String> {
+    let engine = connection.engine();
+    let (headers, sample) = read_csv_sample(path, csv_options, options.sample_rows)?;
+    let columns = match &options.columns {
+        Some(columns) => columns.clone(),
+        None => infer_csv_columns(&headers, &sample, csv_options, engine),
+    };
+    drop(sample);
+
+    // Position of each mapped column in the file
+    let positions = columns
+        .iter()
+        .map(|column| {
+            headers
+                .iter()
+                .position(|h| *h == column.source)
+                .ok_or_else(|| format!("Column {} is not in the file", column.source))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let reject_path = options
+        .reject_path
+        .clone()
+        .unwrap_or_else(|| format!("{}.rejects.csv", path));
+    let rejects = RejectWriter::new(reject_path, headers.clone());
+    let mut loader = TableLoader::new(connection, &options, &columns, rejects)?;
+
+    let mut reader = csv_reader(path, csv_options)?;
+    let mut rows_read = 0;
+    for record in reader.records() {
+        rows_read += 1;
+        match record {
+            Err(e) if e.is_io_error() => return Err(e.to_string()),
+            Err(e) => loader.reject(rows_read, &[], &e.to_string())?,
+            Ok(record) => {
+                let raw: Vec<String> = record.iter().map(String::from).collect();
+                if raw.len() != headers.len() {
+                    let error = format!("Expected {} fields, found {}", headers.len(), raw.len());
+                    loader.reject(rows_read, &raw, &error)?;
+                } else {
+                    let cells = positions
+                        .iter()
+                        .zip(&columns)
+                        .map(|(&i, column)| {
+                            if raw[i] == csv_options.null_value {
+                                Ok(Cell::Null)
+                            } else {
+                                parse_value(&raw[i], column.kind)
+                            }
+                        })
+                        .collect::<Result<Vec<_>, _>>();
+                    match cells {
+                        Ok(cells) => loader.push(rows_read, cells, raw)?,
+                        Err(e) => loader.reject(rows_read, &raw, &e)?,
+                    }
+                }
+            }
+        }
+
+        if rows_read % PROGRESS_EVERY == 0 {
+            loader.emit_progress(app, path, rows_read);
+        }
+    }
+
+    let summary = loader.finish(rows_read)?;
+    summary.emit(app, path);
+    Ok(summary)
+}
+
+// Picks the narrowest kind every sampled value parses as. Numbers with
+// leading zeros (zip codes, account numbers) stay text.
+pub fn infer_column(name: &str, values: &[&str]) -> ColumnInfo {
+    let mut is_bool = true;
+    let mut is_int = true;
+    let mut is_decimal = true;
+    let mut is_float = true;
+    let mut is_date = true;
+    let mut is_datetime = true;
+    let mut is_json = true;
+    let (mut whole_digits, mut scale) = (0, 0);
+
+    let values: Vec<&str> = values.iter().copied().filter(|v| !v.is_empty()).collect();
+    for value in &values {
+        let numeric = !has_leading_zero(value);
+        is_bool &= parse_bool(value).is_some() && value.len() > 1;
+        is_int &= numeric && value.parse::<i64>().is_ok();
+        match decimal_digits(value) {
+            Some((whole, fraction)) if numeric => {
+                whole_digits = whole_digits.max(whole);
+                scale = scale.max(fraction);
+            }
+            _ => is_decimal = false,
+        }
+        is_float &=
+            numeric && value.bytes().any(|b| b.is_ascii_digit()) && value.parse::<f64>().is_ok();
+        is_date &= parse_date(value).is_some();
+        is_datetime &= parse_datetime(value).is_some();
+        is_json &= (value.starts_with('{') || value.starts_with('['))
+            && serde_json::from_str::<serde_json::Value>(value).is_ok();
+    }
+
+    let precision = (whole_digits + scale).max(1);
+    let (kind, type_name) = if values.is_empty() {
+        (CellKind::Text, "")
+    } else if is_bool {
+        (CellKind::Bool, "")
+    } else if is_int {
+        (CellKind::Int, "longlong")
+    } else if is_decimal && precision <= MAX_DECIMAL_PRECISION {
+        (CellKind::Decimal, "")
+    } else if is_float {
+        (CellKind::Float, "double")
+    } else if is_date {
+        (CellKind::Date, "")
+    } else if is_datetime {
+        (CellKind::DateTime, "")
+    } else if is_json {
+        (CellKind::Json, "")
+    } else {
+        (CellKind::Text, "")
+    };
+
+    let decimal = kind == CellKind::Decimal;
+    ColumnInfo {
+        name: name.to_string(),
+        type_name: type_name.to_string(),
+        kind,
+        // A sample can't prove a column never holds NULL
+        nullable: true,
+        precision: decimal.then_some(precision),
+        scale: decimal.then_some(scale),
+    }
+}
+
+fn has_leading_zero(value: &str) -> bool {
+    let mut digits = value.trim_start_matches('-').chars();
+    digits.next() == Some('0') && digits.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+// Whole and fractional digit counts of a plain decimal like `-12.50`.
+fn decimal_digits(value: &str) -> Option<(u32, u32)> {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let all_digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+        return None;
+    }
+    Some((whole.len() as u32, fraction.len() as u32))
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "t" | "yes" | "y" | "1" => Some(true),
+        "false" | "f" | "no" | "n" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+}
+
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    [
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    // Offsets are normalized to UTC
+    .or_else(|| {
+        DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|dt| dt.naive_utc())
+    })
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// Parses one field as `kind`.
+pub fn parse_value(value: &str, kind: CellKind) -> Result<Cell, String> {
+    let cell = match kind {
+        CellKind::Bool => parse_bool(value).map(Cell::Bool),
+        CellKind::Int => value.parse().ok().map(Cell::Int),
+        CellKind::UInt => value.parse().ok().map(Cell::UInt),
+        CellKind::Float => value.parse().ok().map(Cell::Float),
+        CellKind::Decimal => decimal_digits(value).map(|_| Cell::Decimal(value.to_string())),
+        CellKind::Text => Some(Cell::Text(value.to_string())),
+        CellKind::Bytes => match value.strip_prefix("0x").or(value.strip_prefix("\\x")) {
+            Some(hex) => from_hex(hex).map(Cell::Bytes),
+            None => Some(Cell::Bytes(value.as_bytes().to_vec())),
+        },
+        CellKind::Date => parse_date(value).map(Cell::Date),
+        // MySQL TIME goes past 24 hours, so it's left for the server to check
+        CellKind::Time => Some(Cell::Time(value.to_string())),
+        CellKind::DateTime => parse_datetime(value).map(Cell::DateTime),
+        CellKind::TimestampTz => parse_datetime(value).map(|dt| Cell::TimestampTz(dt.and_utc())),
+        CellKind::Json => serde_json::from_str::<serde_json::Value>(value)
+            .ok()
+            .map(|_| Cell::Json(value.to_string())),
+    };
+
+    let kind_name = format!("{:?}", kind).to_lowercase();
+    cell.ok_or_else(|| format!("{:?} is not a valid {}", value, kind_name))
+}
+
+// Rows that couldn't be imported, with the record number and the reason.
+// The file is only created once something is rejected.
+pub struct RejectWriter {
+    path: String,
+    headers: Vec<String>,
+    writer: Option<csv::Writer<File>>,
+    count: u64,
+}
+
+impl RejectWriter {
+    pub fn new(path: String, headers: Vec<String>) -> Self {
+        RejectWriter {
+            path,
+            headers,
+            writer: None,
+            count: 0,
+        }
+    }
+
+    fn write(&mut self, record: u64, raw: &[String], error: &str) -> Result<(), String> {
+        if self.writer.is_none() {
+            let mut writer = csv::Writer::from_path(&self.path)
+                .map_err(|e| format!("Failed to create {}: {}", self.path, e))?;
+            writer
+                .write_record(
+                    ["record", "error"]
+                        .into_iter()
+                        .chain(self.headers.iter().map(String::as_str)),
+                )
+                .map_err(|e| e.to_string())?;
+            self.writer = Some(writer);
+        }
+
+        if let Some(writer) = self.writer.as_mut() {
+            writer
+                .write_record(
+                    [record.to_string(), error.to_string()]
+                        .iter()
+                        .chain(raw)
+                        .map(String::as_str),
+                )
+                .map_err(|e| e.to_string())?;
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<Option<String>, String> {
+        match self.writer.as_mut() {
+            Some(writer) => {
+                writer.flush().map_err(|e| e.to_string())?;
+                Ok(Some(self.path.clone()))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+struct PendingRow {
+    record: u64,
+    cells: Vec<Cell>,
+    raw: Vec<String>,
+}
+
+// Why the server didn't take a batch. Only refused data is retried row by
+// row; anything else (a missing table, a lost connection) ends the import.
+enum LoadError {
+    Data(String),
+    Fatal(String),
+}
+
+// MySQL data errors that aren't in SQLSTATE classes 22 or 23: incorrect
+// values for a column's type, and failed CHECK constraints
+const MYSQL_DATA_ERRORS: &[u16] = &[1265, 1366, 1411, 3819];
+
+// SQLSTATE class 22 is a data exception, 23 a constraint violation
+fn is_data_state(state: &str) -> bool {
+    state.starts_with("22") || state.starts_with("23")
+}
+
+fn mysql_error(e: mysql::Error) -> LoadError {
+    match &e {
+        mysql::Error::MySqlError(err)
+            if is_data_state(&err.state) || MYSQL_DATA_ERRORS.contains(&err.code) =>
+        {
+            LoadError::Data(e.to_string())
+        }
+        _ => LoadError::Fatal(e.to_string()),
+    }
+}
+
+fn pg_error(e: postgres::Error) -> LoadError {
+    match e.code() {
+        Some(code) if is_data_state(code.code()) => LoadError::Data(e.to_string()),
+        _ => LoadError::Fatal(e.to_string()),
+    }
+}
+
+// Writes parsed rows into the target table in batches, using COPY on
+// PostgreSQL and multi-row INSERT or LOAD DATA LOCAL INFILE on MySQL. Each
+// batch commits on its own; when the server refuses one, its rows are
+// retried one at a time so only the offending rows are rejected.
+pub struct TableLoader {
+    connection: Connection,
+    engine: Engine,
+    table: String,
+    column_list: String,
+    batch_size: usize,
+    load_data: bool,
+    // Contents handed to the server when it asks for the LOAD DATA file
+    infile: Arc<Mutex<Vec<u8>>>,
+    pending: Vec<PendingRow>,
+    rejects: RejectWriter,
+    imported: u64,
+    table_created: bool,
+}
+
+impl TableLoader {
+    pub fn new(
+        mut connection: Connection,
+        options: &ImportOptions,
+        columns: &[ImportColumn],
+        rejects: RejectWriter,
+    ) -> Result<Self, String> {
+        if columns.is_empty() {
+            return Err("No columns to import".to_string());
+        }
+        let engine = connection.engine();
+        let table = dialect::quote_table(options.table.trim(), engine);
+        let targets: Vec<&str> = columns.iter().map(|c| c.target.as_str()).collect();
+
+        if options.create_table {
+            let definitions: Vec<String> = columns
+                .iter()
+                .map(|c| {
+                    format!(
+                        "  {} {}",
+                        dialect::quote_ident(&c.target, engine),
+                        c.sql_type
+                    )
+                })
+                .collect();
+            connection.execute(&format!(
+                "CREATE TABLE {} (\n{}\n)",
+                table,
+                definitions.join(",\n")
+            ))?;
+        }
+
+        // The tab-separated LOAD DATA format has no way to express binary
+        // values, so those imports use INSERT
+        let load_data = matches!(options.mysql_method, MysqlLoadMethod::LoadData)
+            && engine == Engine::Mysql
+            && columns.iter().all(|c| c.kind != CellKind::Bytes);
+
+        // The pool restores the default handler when the connection returns
+        let infile = Arc::new(Mutex::new(Vec::new()));
+        if let (true, Connection::Mysql(conn)) = (load_data, &mut connection) {
+            let data = infile.clone();
+            conn.set_local_infile_handler(Some(LocalInfileHandler::new(move |_, out| {
+                let data = data
+                    .lock()
+                    .map_err(|_| io::Error::new(io::ErrorKind::Other, "import buffer poisoned"))?;
+                out.write_all(&data)
+            })));
+        }
+
+        Ok(TableLoader {
+            connection,
+            engine,
+            table,
+            column_list: dialect::column_list(&targets, engine),
+            batch_size: options.batch_size.max(1),
+            load_data,
+            infile,
+            pending: Vec::new(),
+            rejects,
+            imported: 0,
+            table_created: options.create_table,
+        })
+    }
+
+    pub fn push(&mut self, record: u64, cells: Vec<Cell>, raw: Vec<String>) -> Result<(), String> {
+        self.pending.push(PendingRow { record, cells, raw });
+        if self.pending.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn reject(&mut self, record: u64, raw: &[String], error: &str) -> Result<(), String> {
+        self.rejects.write(record, raw, error)
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        let rows = mem::take(&mut self.pending);
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        match self.load_batch(&rows) {
+            Ok(()) => {
+                self.imported += rows.len() as u64;
+                return Ok(());
+            }
+            Err(LoadError::Data(_)) => {}
+            Err(LoadError::Fatal(e)) => return Err(e),
+        }
+
+        for row in &rows {
+            match self.insert(std::slice::from_ref(row)) {
+                Ok(()) => self.imported += 1,
+                Err(LoadError::Data(e)) => self.rejects.write(row.record, &row.raw, &e)?,
+                Err(LoadError::Fatal(e)) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    fn load_batch(&mut self, rows: &[PendingRow]) -> Result<(), LoadError> {
+        match &mut self.connection {
+            Connection::Postgres(client) => {
+                let mut data = Vec::new();
+                for row in rows {
+                    write_copy_row(&mut data, &row.cells);
+                }
+                let mut writer = client
+                    .copy_in(&format!(
+                        "COPY {} ({}) FROM STDIN WITH (FORMAT csv)",
+                        self.table, self.column_list
+                    ))
+                    .map_err(pg_error)?;
+                writer
+                    .write_all(&data)
+                    .map_err(|e| LoadError::Fatal(e.to_string()))?;
+                writer.finish().map_err(pg_error)?;
+                Ok(())
+            }
+            Connection::Mysql(conn) if self.load_data => {
+                {
+                    let mut data = self
+                        .infile
+                        .lock()
+                        .map_err(|e| LoadError::Fatal(e.to_string()))?;
+                    data.clear();
+                    for row in rows {
+                        write_load_data_row(&mut data, &row.cells);
+                    }
+                }
+                // LOCAL turns conversion errors and duplicate keys into
+                // warnings and loads what it can, so the batch runs in a
+                // transaction that is rolled back unless every row went in
+                // cleanly
+                conn.query_drop("START TRANSACTION").map_err(mysql_error)?;
+                let loaded = conn
+                    .query_drop(format!(
+                        "LOAD DATA LOCAL INFILE 'import' INTO TABLE {} CHARACTER SET utf8mb4 ({})",
+                        self.table, self.column_list
+                    ))
+                    .map_err(mysql_error)
+                    .and_then(|()| {
+                        if conn.warnings() == 0 && conn.affected_rows() == rows.len() as u64 {
+                            Ok(())
+                        } else {
+                            Err(LoadError::Data(format!(
+                                "LOAD DATA reported {} warning(s)",
+                                conn.warnings()
+                            )))
+                        }
+                    });
+                let end = if loaded.is_ok() { "COMMIT" } else { "ROLLBACK" };
+                conn.query_drop(end).map_err(mysql_error)?;
+                loaded
+            }
+            Connection::Mysql(_) => self.insert(rows),
+        }
+    }
+
+    fn insert(&mut self, rows: &[PendingRow]) -> Result<(), LoadError> {
+        let values: Vec<String> = rows
+            .iter()
+            .map(|row| {
+                let literals: Vec<String> = row
+                    .cells
+                    .iter()
+                    .map(|cell| dialect::literal(cell, self.engine))
+                    .collect();
+                format!("({})", literals.join(", "))
+            })
+            .collect();
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES {}",
+            self.table,
+            self.column_list,
+            values.join(", ")
+        );
+        match &mut self.connection {
+            Connection::Mysql(conn) => conn.query_drop(sql).map_err(mysql_error),
+            Connection::Postgres(client) => client.batch_execute(&sql).map_err(pg_error),
+        }
+    }
+
+    pub fn emit_progress(&self, app: &AppHandle, path: &str, rows_read: u64) {
+        emit_progress(
+            app,
+            path,
+            rows_read,
+            self.imported,
+            self.rejects.count,
+            false,
+        );
+    }
+
+    pub fn finish(mut self, rows_read: u64) -> Result<ImportSummary, String> {
+        self.flush()?;
+        let reject_path = self.rejects.finish()?;
+
+        Ok(ImportSummary {
+            rows_read,
+            imported: self.imported,
+            rejected: self.rejects.count,
+            reject_path,
+            table_created: self.table_created,
+        })
+    }
+}
+
+impl ImportSummary {
+    // Final `import-progress` event, with the counts after the last batch
+    pub fn emit(&self, app: &AppHandle, path: &str) {
+        emit_progress(
+            app,
+            path,
+            self.rows_read,
+            self.imported,
+            self.rejected,
+            true,
+        );
+    }
+}
+
+fn emit_progress(
+    app: &AppHandle,
+    path: &str,
+    rows_read: u64,
+    imported: u64,
+    rejected: u64,
+    done: bool,
+) {
+    let _ = app.emit(
+        "import-progress",
+        ImportProgress {
+            path: path.to_string(),
+            rows_read,
+            imported,
+            rejected,
+            done,
+        },
+    );
+}
+
+// One row in COPY's CSV format, where an unquoted empty field is NULL and
+// every value is quoted.
+fn write_copy_row(out: &mut Vec<u8>, cells: &[Cell]) {
+    for (i, cell) in cells.iter().enumerate() {
+        if i > 0 {
+            out.push(b',');
+        }
+        let text = match cell {
+            Cell::Null => continue,
+            Cell::Bytes(b) => format!("\\x{}", to_hex(b)),
+            other => other.to_text().unwrap_or_default(),
+        };
+        out.push(b'"');
+        out.extend_from_slice(text.replace('"', "\"\"").as_bytes());
+        out.push(b'"');
+    }
+    out.push(b'\n');
+}
+
+// One row in LOAD DATA's default format: tab-separated, backslash escapes
+// and \N for NULL.
+fn write_load_data_row(out: &mut Vec<u8>, cells: &[Cell]) {
+    for (i, cell) in cells.iter().enumerate() {
+        if i > 0 {
+            out.push(b'\t');
+        }
+        let text = match cell {
+            Cell::Null => {
+                out.extend_from_slice(b"\\N");
+                continue;
+            }
+            Cell::Bool(b) => (*b as u8).to_string(),
+            // DATETIME has no offset; the value is already in UTC
+            Cell::TimestampTz(dt) => datetime_text(&dt.naive_utc()),
+            other => other.to_text().unwrap_or_default(),
+        };
+        for c in text.chars() {
+            match c {
+                '\\' => out.extend_from_slice(b"\\\\"),
+                '\t' => out.extend_from_slice(b"\\t"),
+                '\n' => out.extend_from_slice(b"\\n"),
+                '\r' => out.extend_from_slice(b"\\r"),
+                '\0' => out.extend_from_slice(b"\\0"),
+                c => {
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+        }
+    }
+    out.push(b'\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn kind(values: &[&str]) -> CellKind {
+        infer_column("c", values).kind
+    }
+
+    #[test]
+    fn infers_column_kinds() {
+        assert_eq!(kind(&["1", "-2", ""]), CellKind::Int);
+        assert_eq!(kind(&["true", "no"]), CellKind::Bool);
+        // Single digits are numbers rather than flags
+        assert_eq!(kind(&["1", "0"]), CellKind::Int);
+        assert_eq!(kind(&["1e3", "2.5"]), CellKind::Float);
+        assert_eq!(kind(&["2024-03-01"]), CellKind::Date);
+        assert_eq!(
+            kind(&["2024-03-01 10:00:00", "2024-03-01T10:00:00Z"]),
+            CellKind::DateTime
+        );
+        assert_eq!(kind(&["{\"a\": 1}", "[1]"]), CellKind::Json);
+        assert_eq!(kind(&["{not json"]), CellKind::Text);
+        // Zip codes and account numbers keep their zeros
+        assert_eq!(kind(&["007", "12"]), CellKind::Text);
+        assert_eq!(kind(&["-0.5", "0"]), CellKind::Decimal);
+    }
+
+    #[test]
+    fn treats_empty_samples_as_text() {
+        assert_eq!(kind(&[]), CellKind::Text);
+        assert_eq!(kind(&["", ""]), CellKind::Text);
+        assert!(infer_column("c", &["1"]).nullable);
+    }
+
+    #[test]
+    fn infers_decimal_precision() {
+        let column = infer_column("price", &["1.5", "-12.25", "300"]);
+        assert_eq!(column.kind, CellKind::Decimal);
+        assert_eq!((column.precision, column.scale), (Some(5), Some(2)));
+
+        // Past i64, integers become decimals rather than floats
+        let column = infer_column("id", &["9223372036854775807", "9223372036854775808"]);
+        assert_eq!(column.kind, CellKind::Decimal);
+        assert_eq!((column.precision, column.scale), (Some(19), Some(0)));
+
+        // ... up to the widest DECIMAL MySQL has
+        let wide = "1".repeat(66);
+        assert_eq!(kind(&[wide.as_str()]), CellKind::Float);
+    }
+
+    #[test]
+    fn counts_decimal_digits() {
+        assert_eq!(decimal_digits("-12.50"), Some((2, 2)));
+        assert_eq!(decimal_digits("7"), Some((1, 0)));
+        assert_eq!(decimal_digits("12."), Some((2, 0)));
+        assert_eq!(decimal_digits(".5"), None);
+        assert_eq!(decimal_digits("1e5"), None);
+        assert_eq!(decimal_digits("+1"), None);
+        assert_eq!(decimal_digits("-"), None);
+        assert_eq!(decimal_digits(""), None);
+    }
+
+    #[test]
+    fn parses_values() {
+        assert_eq!(
+            parse_value("", CellKind::Text),
+            Ok(Cell::Text(String::new()))
+        );
+        assert_eq!(parse_value("Y", CellKind::Bool), Ok(Cell::Bool(true)));
+        assert_eq!(
+            parse_value("abc", CellKind::Int),
+            Err("\"abc\" is not a valid int".to_string())
+        );
+        assert!(parse_value("9223372036854775808", CellKind::Int).is_err());
+        assert_eq!(
+            parse_value("9223372036854775808", CellKind::UInt),
+            Ok(Cell::UInt(9223372036854775808))
+        );
+        assert_eq!(
+            parse_value("-0.50", CellKind::Decimal),
+            Ok(Cell::Decimal("-0.50".to_string()))
+        );
+        assert!(parse_value("1.2.3", CellKind::Decimal).is_err());
+        assert_eq!(
+            parse_value("0x0aFF", CellKind::Bytes),
+            Ok(Cell::Bytes(vec![0x0a, 0xff]))
+        );
+        assert_eq!(
+            parse_value("\\x01", CellKind::Bytes),
+            Ok(Cell::Bytes(vec![1]))
+        );
+        assert!(parse_value("0x123", CellKind::Bytes).is_err());
+        assert_eq!(
+            parse_value("ab", CellKind::Bytes),
+            Ok(Cell::Bytes(b"ab".to_vec()))
+        );
+        assert_eq!(
+            parse_value("2024-03-01T14:30:00+02:00", CellKind::TimestampTz),
+            Ok(Cell::TimestampTz(
+                Utc.with_ymd_and_hms(2024, 3, 1, 12, 30, 0).unwrap()
+            ))
+        );
+        assert!(parse_value("{", CellKind::Json).is_err());
+    }
+
+    #[test]
+    fn writes_copy_rows() {
+        let mut out = Vec::new();
+        write_copy_row(
+            &mut out,
+            &[
+                Cell::Null,
+                Cell::Text(String::new()),
+                Cell::Text("say \"hi\",\nthen\\leave".to_string()),
+                Cell::Bytes(vec![1, 255]),
+                Cell::Int(-3),
+                Cell::Null,
+            ],
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            ",\"\",\"say \"\"hi\"\",\nthen\\leave\",\"\\x01ff\",\"-3\",\n"
+        );
+    }
+
+    #[test]
+    fn writes_load_data_rows() {
+        let mut out = Vec::new();
+        write_load_data_row(
+            &mut out,
+            &[
+                Cell::Null,
+                Cell::Text(String::new()),
+                Cell::Text("a\tb\\c\nd\r\0".to_string()),
+                Cell::Text("\\N".to_string()),
+                Cell::Bool(true),
+                Cell::TimestampTz(Utc.with_ymd_and_hms(2024, 3, 1, 12, 30, 0).unwrap()),
+            ],
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "\\N\t\ta\\tb\\\\c\\nd\\r\\0\t\\\\N\t1\t2024-03-01 12:30:00\n"
+        );
+    }
+}
@@ -0,0 +1,389 @@
+use serde::de::{self, Deserializer as _, SeqAccess, Visitor};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use tauri::{AppHandle, State};
+
+use crate::engine::{Connection, Engine, Session};
+use crate::import::{
+    import_column, infer_column, parse_value, ImportColumn, ImportOptions, ImportPreview,
+    ImportSummary, RejectWriter, TableLoader, PREVIEW_ROWS, PROGRESS_EVERY,
+};
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::resultset::Cell;
+use crate::runtime::run_blocking;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonFormat {
+    // An array when the file starts with `[`, NDJSON otherwise
+    #[default]
+    Auto,
+    Array,
+    Ndjson,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NestedMode {
+    // Nested object fields become columns named by their path, e.g.
+    // `address.city`
+    #[default]
+    Flatten,
+    // Nested objects are kept whole in a JSON/JSONB column
+    Json,
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct JsonReadOptions {
+    format: JsonFormat,
+    nested: NestedMode,
+}
+
+// A record that couldn't be read: its text and the reason.
+type InvalidRecord = (String, String);
+// Field path to value text; None for JSON null.
+type Fields = HashMap<String, Option<String>>;
+
+// Why reading the records stopped: the file couldn't be read or isn't valid
+// JSON, or the callback failed (e.g. the connection was lost while loading)
+enum ReadError {
+    File(String),
+    Callback(String),
+}
+
+impl ReadError {
+    fn into_message(self) -> String {
+        match self {
+            ReadError::File(e) | ReadError::Callback(e) => e,
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn preview_json(
+    engine: Engine,
+    path: String,
+    json_options: Option<JsonReadOptions>,
+    sample_rows: Option<usize>,
+) -> Result<ImportPreview, String> {
+    let json_options = json_options.unwrap_or_default();
+    let sample_rows = sample_rows.unwrap_or(ImportOptions::default().sample_rows);
+
+    run_blocking(move || {
+        let (paths, sample) = read_json_sample(&path, json_options, sample_rows)?;
+        let columns = infer_json_columns(&paths, &sample, engine);
+        let rows = sample
+            .iter()
+            .take(PREVIEW_ROWS)
+            .map(|record| {
+                paths
+                    .iter()
+                    .map(|p| record.get(p).cloned().flatten().unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+        Ok(ImportPreview { columns, rows })
+    })
+    .await
+}
+
+// Loads a JSON array or NDJSON file of objects into `options.table`. Same
+// batching, reject file and `import-progress` events as the CSV import.
+#[tauri::command]
+pub async fn import_json(
+    app: AppHandle,
+    engine: Engine,
+    path: String,
+    json_options: Option<JsonReadOptions>,
+    options: ImportOptions,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+) -> Result<ImportSummary, String> {
+    let json_options = json_options.unwrap_or_default();
+    if options.table.trim().is_empty() {
+        return Err("A target table name is required".to_string());
+    }
+
+    let session = Session::open(engine, &mysql_state, &pg_state)?;
+    run_blocking(move || {
+        let connection = session.connect()?;
+        import_json_file(&app, connection, &path, json_options, options)
+    })
+    .await
+}
+
+fn import_json_file(
+    app: &AppHandle,
+    connection: Connection,
+    path: &str,
+    json_options: JsonReadOptions,
+    options: ImportOptions,
+) -> Result<ImportSummary, String> {
+    let columns = match &options.columns {
+        Some(columns) => columns.clone(),
+        None => {
+            let (paths, sample) = read_json_sample(path, json_options, options.sample_rows)?;
+            infer_json_columns(&paths, &sample, connection.engine())
+        }
+    };
+
+    let reject_path = options
+        .reject_path
+        .clone()
+        .unwrap_or_else(|| format!("{}.rejects.csv", path));
+    let rejects = RejectWriter::new(reject_path, vec!["json".to_string()]);
+    let mut loader = TableLoader::new(connection, &options, &columns, rejects)?;
+
+    let mut rows_read = 0;
+    let read = for_each_record(path, json_options.format, None, |record| {
+        rows_read += 1;
+        match record {
+            Ok(value) => {
+                let raw = vec![value.to_string()];
+                match flatten_record(&value, json_options.nested)
+                    .and_then(|fields| record_cells(&fields, &columns))
+                {
+                    Ok(cells) => loader.push(rows_read, cells, raw)?,
+                    Err(e) => loader.reject(rows_read, &raw, &e)?,
+                }
+            }
+            Err((raw, error)) => loader.reject(rows_read, &[raw], &error)?,
+        }
+
+        if rows_read % PROGRESS_EVERY == 0 {
+            loader.emit_progress(app, path, rows_read);
+        }
+        Ok(())
+    });
+
+    // A syntax error ends an array file early; the records before it are
+    // still loaded, and the error says how many went in. A loader that
+    // failed can't take any more.
+    let file_error = match read {
+        Ok(()) => None,
+        Err(ReadError::File(e)) => Some(e),
+        Err(ReadError::Callback(e)) => return Err(e),
+    };
+    let summary = loader.finish(rows_read)?;
+    summary.emit(app, path);
+    match file_error {
+        None => Ok(summary),
+        Some(e) => Err(format!(
+            "{} ({} of {} records read before the error were imported)",
+            e, summary.imported, summary.rows_read
+        )),
+    }
+}
+
+fn record_cells(fields: &Fields, columns: &[ImportColumn]) -> Result<Vec<Cell>, String> {
+    columns
+        .iter()
+        .map(|column| match fields.get(&column.source) {
+            Some(Some(text)) => {
+                parse_value(text, column.kind).map_err(|e| format!("{}: {}", column.source, e))
+            }
+            _ => Ok(Cell::Null),
+        })
+        .collect()
+}
+
+// Field paths in order of first appearance, and up to `limit` flattened
+// records. Records that aren't objects are left for the import to reject.
+fn read_json_sample(
+    path: &str,
+    json_options: JsonReadOptions,
+    limit: usize,
+) -> Result<(Vec<String>, Vec<Fields>), String> {
+    let mut paths: Vec<String> = Vec::new();
+    let mut sample = Vec::new();
+
+    for_each_record(path, json_options.format, Some(limit), |record| {
+        let Ok(value) = record else {
+            return Ok(());
+        };
+        let Ok(fields) = flatten_record(&value, json_options.nested) else {
+            return Ok(());
+        };
+        let mut names: Vec<&String> = fields.keys().filter(|k| !paths.contains(k)).collect();
+        // HashMap order is arbitrary; keep new fields in a stable order
+        names.sort();
+        paths.extend(names.into_iter().cloned());
+        sample.push(fields);
+        Ok(())
+    })
+    .map_err(ReadError::into_message)?;
+
+    Ok((paths, sample))
+}
+
+fn infer_json_columns(paths: &[String], sample: &[Fields], engine: Engine) -> Vec<ImportColumn> {
+    paths
+        .iter()
+        .map(|path| {
+            let values: Vec<&str> = sample
+                .iter()
+                .filter_map(|record| record.get(path)?.as_deref())
+                .collect();
+            import_column(path, infer_column(path, &values), engine)
+        })
+        .collect()
+}
+
+// Field path to text for one object. Strings are taken as they are so
+// dates and numbers in strings are inferred like CSV fields; arrays, and
+// objects when not flattening, are kept as JSON text.
+fn flatten_record(value: &Value, nested: NestedMode) -> Result<Fields, String> {
+    let Value::Object(object) = value else {
+        return Err("Expected a JSON object".to_string());
+    };
+    let mut fields = HashMap::new();
+    flatten_into(&mut fields, "", object, nested);
+    Ok(fields)
+}
+
+fn flatten_into(
+    fields: &mut Fields,
+    prefix: &str,
+    object: &Map<String, Value>,
+    nested: NestedMode,
+) {
+    for (key, value) in object {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        let text = match value {
+            Value::Object(inner) if matches!(nested, NestedMode::Flatten) => {
+                flatten_into(fields, &path, inner, nested);
+                continue;
+            }
+            Value::Null => None,
+            Value::String(s) => Some(s.clone()),
+            other => Some(other.to_string()),
+        };
+        fields.insert(path, text);
+    }
+}
+
+// Calls `f` with each top-level record of the file, stopping after `limit`.
+fn for_each_record<F>(
+    path: &str,
+    format: JsonFormat,
+    limit: Option<usize>,
+    mut f: F,
+) -> Result<(), ReadError>
+where
+    F: FnMut(Result<Value, InvalidRecord>) -> Result<(), String>,
+{
+    let file =
+        File::open(path).map_err(|e| ReadError::File(format!("Failed to open {}: {}", path, e)))?;
+    let mut reader = BufReader::new(file);
+
+    let is_array = match format {
+        JsonFormat::Array => true,
+        JsonFormat::Ndjson => false,
+        JsonFormat::Auto => starts_with_bracket(&mut reader).map_err(ReadError::File)?,
+    };
+
+    if is_array {
+        let invalid = |e: serde_json::Error| ReadError::File(format!("Invalid JSON: {}", e));
+        let mut stop = None;
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        let read = deserializer.deserialize_seq(RecordVisitor {
+            limit,
+            f: &mut f,
+            stop: &mut stop,
+        });
+        return match stop {
+            Some(Stop::Limit) => Ok(()),
+            Some(Stop::Callback(e)) => Err(ReadError::Callback(e)),
+            None => {
+                read.map_err(invalid)?;
+                deserializer.end().map_err(invalid)
+            }
+        };
+    }
+
+    let mut count = 0;
+    for line in reader.lines() {
+        if limit.is_some_and(|limit| count >= limit) {
+            break;
+        }
+        let line = line.map_err(|e| ReadError::File(e.to_string()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        count += 1;
+        let record = serde_json::from_str::<Value>(&line)
+            .map_err(|e| (line, format!("Invalid JSON: {}", e)));
+        f(record).map_err(ReadError::Callback)?;
+    }
+    Ok(())
+}
+
+// Looks at the first non-whitespace byte without consuming it.
+fn starts_with_bracket<R: Read>(reader: &mut BufReader<R>) -> Result<bool, String> {
+    loop {
+        let buffer = reader.fill_buf().map_err(|e| e.to_string())?;
+        let Some(&byte) = buffer.first() else {
+            return Ok(false);
+        };
+        if byte.is_ascii_whitespace() {
+            reader.consume(1);
+        } else {
+            return Ok(byte == b'[');
+        }
+    }
+}
+
+// Why `RecordVisitor` stopped before the end of the array. It returns an
+// error to stop serde, so the reason is passed back here instead.
+enum Stop {
+    Limit,
+    Callback(String),
+}
+
+// Streams the elements of a top-level array to the callback one at a time,
+// so the file is never held in memory. At the limit it stops without
+// reading the rest of the file.
+struct RecordVisitor<'a, F> {
+    limit: Option<usize>,
+    f: &'a mut F,
+    stop: &'a mut Option<Stop>,
+}
+
+impl<'de, F> Visitor<'de> for RecordVisitor<'_, F>
+where
+    F: FnMut(Result<Value, InvalidRecord>) -> Result<(), String>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of JSON objects")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        let mut count = 0;
+        loop {
+            if matches!(self.limit, Some(limit) if count >= limit) {
+                *self.stop = Some(Stop::Limit);
+                return Err(de::Error::custom("record limit reached"));
+            }
+            let Some(value) = seq.next_element::<Value>()? else {
+                return Ok(());
+            };
+            if let Err(e) = (self.f)(Ok(value)) {
+                *self.stop = Some(Stop::Callback(e));
+                return Err(de::Error::custom("stopped by the callback"));
+            }
+            count += 1;
+        }
+    }
+}
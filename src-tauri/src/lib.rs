@@ -1,14 +1,59 @@
+mod aiclient;
+mod datadiff;
+mod dialect;
+mod engine;
+mod explain;
+mod export;
+mod gguf;
+mod history;
+mod import;
+mod jsonimport;
+mod localmodels;
+mod modelmanager;
 mod mysqlcmd;
+mod parquetexport;
 mod pgcmd;
+mod pythondiscovery;
+mod pythonjobs;
 mod pythonmanager;
+mod pythonrequirements;
+mod resultset;
+mod runtime;
+mod savedqueries;
+mod schema;
+mod schemadiff;
+mod sidecar;
+mod sidecarlog;
+mod stats;
+mod tablecopy;
+mod xlsxexport;
 
 use mysqlcmd::DbConnection;
+use tauri::{Manager, RunEvent};
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .manage(DbConnection::new())
         .manage(pgcmd::DbConnection::new())
+        .manage(pythonjobs::VenvJobs::new())
+        .manage(sidecar::Sidecar::new())
+        .manage(aiclient::AiClient::new())
+        .setup(|app| {
+            let data_dir = app.path().app_data_dir()?;
+            app.manage(history::HistoryStore::load(
+                data_dir.clone(),
+                app.handle().clone(),
+            )?);
+            app.manage(pythondiscovery::PythonSettingsStore::load(
+                data_dir.clone(),
+            )?);
+            app.manage(savedqueries::SavedQueryStore::load(data_dir.clone())?);
+            app.manage(localmodels::LocalModelStore::load(data_dir.clone())?);
+            app.manage(modelmanager::ModelManager::new(data_dir));
+            app.manage(sidecarlog::SidecarLogStore::new(app.path().app_log_dir()?));
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             mysqlcmd::connect_to_mysql,
             mysqlcmd::show_databases,
@@ -18,16 +63,69 @@ pub fn run() {
             pgcmd::pg_show_databases,
             pgcmd::pg_select_database,
             pgcmd::pg_execute_query,
+            explain::explain_query,
+            export::export_query,
+            import::preview_csv,
+            import::import_csv,
+            jsonimport::preview_json,
+            jsonimport::import_json,
+            tablecopy::copy_tables,
+            schemadiff::diff_schemas,
+            datadiff::diff_table_data,
+            history::search_history,
+            history::rerun_history_entry,
+            history::purge_history,
+            history::get_history_settings,
+            history::set_history_settings,
+            savedqueries::create_saved_query,
+            savedqueries::update_saved_query,
+            savedqueries::delete_saved_query,
+            savedqueries::list_saved_queries,
+            savedqueries::search_saved_queries,
+            savedqueries::export_saved_queries,
+            savedqueries::import_saved_queries,
             pythonmanager::check_python_installation,
             pythonmanager::install_python,
             pythonmanager::manage_venv,
             pythonmanager::activate_venv,
             pythonmanager::get_installation_status,
-            pythonmanager::start_python_script,
-            pythonmanager::stop_python_script,
+            sidecar::start_python_script,
+            sidecar::stop_python_script,
+            sidecar::sidecar_status,
+            sidecar::ai_endpoint,
+            aiclient::ai_set_repository,
+            aiclient::ai_generate_sql,
+            aiclient::ai_set_schema,
+            aiclient::ai_list_models,
+            aiclient::ai_load_model,
+            aiclient::ai_load_local_model,
+            aiclient::ai_unload_model,
+            sidecarlog::get_sidecar_logs,
             pythonmanager::delete_venv,
-            pythonmanager::update_venv_requirements
+            pythonmanager::update_venv_requirements,
+            pythonjobs::cancel_venv_job,
+            pythonrequirements::check_venv_requirements,
+            pythonrequirements::detect_torch_variant,
+            pythondiscovery::list_python_interpreters,
+            pythondiscovery::get_python_settings,
+            pythondiscovery::set_python_settings,
+            pythondiscovery::select_python_interpreter,
+            modelmanager::list_remote_models,
+            modelmanager::download_model,
+            modelmanager::cancel_model_download,
+            modelmanager::list_cached_models,
+            modelmanager::delete_cached_model,
+            localmodels::list_local_models,
+            localmodels::register_local_model,
+            localmodels::unregister_local_model,
+            localmodels::inspect_model_file
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // Closing the last window requests an exit too
+            if let RunEvent::ExitRequested { .. } | RunEvent::Exit = event {
+                sidecar::shutdown(app);
+            }
+        });
 }
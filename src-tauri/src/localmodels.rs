@@ -0,0 +1,173 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::gguf::{self, GgufInfo};
+use crate::modelmanager::{collect_files, is_model};
+use crate::runtime::run_blocking;
+
+const LOCAL_MODELS_FILE: &str = "local_models.json";
+
+#[derive(Serialize)]
+pub struct LocalModel {
+    path: String,
+    // The registered file or folder the model was found through
+    source: String,
+    metadata: Option<GgufInfo>,
+    // Why the file can't be used, when it isn't a readable GGUF file
+    error: Option<String>,
+}
+
+// Model files and folders registered from disk or a network share, kept in
+// local_models.json. Folders are scanned for .gguf files each time they are
+// listed, so models copied into them show up without registering again.
+pub struct LocalModelStore {
+    dir: PathBuf,
+    sources: Mutex<Vec<PathBuf>>,
+}
+
+impl LocalModelStore {
+    pub fn load(dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let sources = fs::read_to_string(dir.join(LOCAL_MODELS_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Ok(LocalModelStore {
+            dir,
+            sources: Mutex::new(sources),
+        })
+    }
+
+    pub fn sources(&self) -> Result<Vec<PathBuf>, String> {
+        let sources = self.sources.lock().map_err(|e| e.to_string())?;
+        Ok(sources.clone())
+    }
+
+    fn save(&self, sources: Vec<PathBuf>) -> Result<(), String> {
+        let mut current = self.sources.lock().map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(&sources).map_err(|e| e.to_string())?;
+        fs::write(self.dir.join(LOCAL_MODELS_FILE), json).map_err(|e| e.to_string())?;
+        *current = sources;
+        Ok(())
+    }
+
+    // Whether `path` is a registered file or lies in a registered folder.
+    // Only its folder is resolved for the folder check, so a symlinked model
+    // listed in a registered folder is accepted wherever the link points.
+    pub fn contains(&self, path: &Path) -> Result<bool, String> {
+        let resolved = canonical(path)?;
+        let listed = match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+                canonical(parent)?.join(name)
+            }
+            _ => resolved.clone(),
+        };
+        Ok(self
+            .sources()?
+            .iter()
+            .any(|source| resolved == *source || (source.is_dir() && listed.starts_with(source))))
+    }
+}
+
+fn canonical(path: &Path) -> Result<PathBuf, String> {
+    fs::canonicalize(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+}
+
+// The models a source provides: the file itself, or the .gguf files anywhere
+// under the folder. A source that has gone away is reported as an error.
+fn scan(source: &Path) -> Vec<LocalModel> {
+    let model = |path: &Path, result: Result<GgufInfo, String>| {
+        let (metadata, error) = match result {
+            Ok(info) => (Some(info), None),
+            Err(e) => (None, Some(e)),
+        };
+        LocalModel {
+            path: path.to_string_lossy().into_owned(),
+            source: source.to_string_lossy().into_owned(),
+            metadata,
+            error,
+        }
+    };
+
+    if source.is_file() {
+        return vec![model(source, gguf::inspect(source))];
+    }
+    if !source.is_dir() {
+        return vec![model(
+            source,
+            Err(format!("{} no longer exists", source.display())),
+        )];
+    }
+    let mut files = Vec::new();
+    collect_files(source, &mut files);
+    files.retain(|file| is_model(file));
+    files.sort();
+    files
+        .iter()
+        .map(|file| model(file, gguf::inspect(file)))
+        .collect()
+}
+
+#[tauri::command]
+pub async fn list_local_models(
+    store: State<'_, LocalModelStore>,
+) -> Result<Vec<LocalModel>, String> {
+    let sources = store.sources()?;
+    run_blocking(move || Ok(sources.iter().flat_map(|source| scan(source)).collect())).await
+}
+
+// Registers a GGUF file, or a folder of them, and returns the models it
+// provides. A file must be a readable GGUF file to be registered.
+#[tauri::command]
+pub async fn register_local_model(
+    path: String,
+    store: State<'_, LocalModelStore>,
+) -> Result<Vec<LocalModel>, String> {
+    let source = canonical(Path::new(&path))?;
+    let models = {
+        let source = source.clone();
+        run_blocking(move || {
+            if source.is_file() {
+                gguf::inspect(&source)?;
+            } else if !source.is_dir() {
+                return Err(format!("{} is not a file or folder", source.display()));
+            }
+            Ok(scan(&source))
+        })
+        .await?
+    };
+
+    let mut sources = store.sources()?;
+    if !sources.contains(&source) {
+        sources.push(source);
+        store.save(sources)?;
+    }
+    Ok(models)
+}
+
+#[tauri::command]
+pub fn unregister_local_model(
+    path: String,
+    store: State<'_, LocalModelStore>,
+) -> Result<(), String> {
+    let path = PathBuf::from(path);
+    let mut sources = store.sources()?;
+    let before = sources.len();
+    // Sources that no longer exist can't be canonicalized, so the path as
+    // listed is matched too
+    let canonical = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+    sources.retain(|source| *source != path && *source != canonical);
+    if sources.len() == before {
+        return Err(format!("{} is not registered", path.display()));
+    }
+    store.save(sources)
+}
+
+// Reads the GGUF header of any file, registered or not.
+#[tauri::command]
+pub async fn inspect_model_file(path: String) -> Result<GgufInfo, String> {
+    run_blocking(move || gguf::inspect(Path::new(&path))).await
+}
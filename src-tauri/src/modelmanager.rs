@@ -0,0 +1,705 @@
+use reqwest::header::RANGE;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Notify;
+
+use crate::history::epoch_millis;
+use crate::runtime::run_blocking;
+
+const HUB_URL: &str = "https://huggingface.co";
+const MODELS_DIR: &str = "models";
+const MODEL_EXTENSION: &str = "gguf";
+const PART_SUFFIX: &str = ".part";
+const CANCELLED: &str = "Cancelled";
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const LIST_TIMEOUT: Duration = Duration::from_secs(30);
+// Silence from the server before a download is given up on; it can be
+// resumed afterwards
+const STALL_TIMEOUT: Duration = Duration::from_secs(60);
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+struct Repository {
+    repo: String,
+    token: Option<String>,
+}
+
+// Set by `cancel`. The notify wakes a download that is waiting on the
+// server, so it stops without waiting for the next chunk.
+#[derive(Default)]
+struct CancelFlag {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+impl CancelFlag {
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct RemoteModel {
+    pub file: String,
+    size: u64,
+    // SHA-256 of the file, for files stored in Git LFS
+    sha256: Option<String>,
+    cached: bool,
+}
+
+#[derive(Serialize)]
+pub struct CachedModel {
+    repo: String,
+    file: String,
+    path: String,
+    size: u64,
+    modified: u64,
+    // False for a download that was interrupted and can be resumed
+    complete: bool,
+}
+
+#[derive(Serialize)]
+pub struct CachedModels {
+    dir: String,
+    models: Vec<CachedModel>,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadPhase {
+    Downloading,
+    Verifying,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum DownloadStatus {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DownloadProgress {
+    repo: String,
+    file: String,
+    phase: DownloadPhase,
+    downloaded: u64,
+    total: Option<u64>,
+    percent: Option<u32>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct DownloadFinished {
+    repo: String,
+    file: String,
+    status: DownloadStatus,
+    message: String,
+}
+
+// An entry of the Hub's tree listing
+#[derive(Deserialize)]
+struct TreeEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    path: String,
+    #[serde(default)]
+    size: u64,
+    lfs: Option<LfsInfo>,
+}
+
+#[derive(Deserialize)]
+struct LfsInfo {
+    oid: String,
+    size: u64,
+}
+
+// GGUF models from a Hugging Face repository, downloaded into the app data
+// directory as models/<owner>--<name>/<file>. Downloads resume from the
+// `.part` file an interrupted one leaves behind, and are checked against the
+// SHA-256 the Hub reports before they are used.
+pub struct ModelManager {
+    dir: PathBuf,
+    http: reqwest::Client,
+    // Set from the AI settings; the token is optional for public repositories
+    repository: Mutex<Option<Repository>>,
+    // Cancel flags of the downloads in progress, by repository and file
+    downloads: Mutex<HashMap<(String, String), Arc<CancelFlag>>>,
+}
+
+impl ModelManager {
+    pub fn new(data_dir: PathBuf) -> Self {
+        ModelManager {
+            dir: data_dir.join(MODELS_DIR),
+            http: reqwest::Client::builder()
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            repository: Mutex::new(None),
+            downloads: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_repository(&self, repo: &str, token: &str) -> Result<(), String> {
+        let repo = repo.trim().trim_matches('/');
+        if !valid_repo(repo) {
+            return Err(format!(
+                "Expected a repository in the form owner/name, got '{}'",
+                repo
+            ));
+        }
+        let token = token.trim();
+        *self.repository.lock().map_err(|e| e.to_string())? = Some(Repository {
+            repo: repo.to_string(),
+            token: (!token.is_empty()).then(|| token.to_string()),
+        });
+        Ok(())
+    }
+
+    fn repository(&self) -> Result<(String, Option<String>), String> {
+        let repository = self.repository.lock().map_err(|e| e.to_string())?;
+        let repository = repository
+            .as_ref()
+            .ok_or_else(|| "Hugging Face token or repo not set".to_string())?;
+        Ok((repository.repo.clone(), repository.token.clone()))
+    }
+
+    fn request(&self, url: &str, token: Option<&str>) -> reqwest::RequestBuilder {
+        let request = self.http.get(url);
+        match token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    // The GGUF files of the current repository.
+    pub async fn list_remote(&self) -> Result<Vec<RemoteModel>, String> {
+        let (repo, token) = self.repository()?;
+        self.list_files(&repo, token.as_deref()).await
+    }
+
+    async fn list_files(
+        &self,
+        repo: &str,
+        token: Option<&str>,
+    ) -> Result<Vec<RemoteModel>, String> {
+        let url = format!(
+            "{}/api/models/{}/tree/main?recursive=true",
+            HUB_URL,
+            url_path(repo)
+        );
+        let response = self
+            .request(&url, token)
+            .timeout(LIST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Hugging Face: {}", e))?;
+        if !response.status().is_success() {
+            return Err(hub_error(repo, response.status()));
+        }
+        let entries: Vec<TreeEntry> = response
+            .json()
+            .await
+            .map_err(|e| format!("Unexpected response from Hugging Face: {}", e))?;
+
+        let repo_dir = self.repo_dir(repo);
+        let mut models: Vec<RemoteModel> = entries
+            .into_iter()
+            .filter(|entry| entry.kind == "file" && is_model(Path::new(&entry.path)))
+            .map(|entry| {
+                let (size, sha256) = match entry.lfs {
+                    Some(lfs) => (lfs.size, Some(lfs.oid)),
+                    None => (entry.size, None),
+                };
+                let cached = fs::metadata(repo_dir.join(&entry.path))
+                    .is_ok_and(|metadata| metadata.len() == size);
+                RemoteModel {
+                    file: entry.path,
+                    size,
+                    sha256,
+                    cached,
+                }
+            })
+            .collect();
+        models.sort_by(|a, b| a.file.cmp(&b.file));
+        Ok(models)
+    }
+
+    fn repo_dir(&self, repo: &str) -> PathBuf {
+        self.dir.join(repo.replace('/', "--"))
+    }
+
+    // Where `file` of `repo` is kept, refusing paths that would leave the
+    // models directory.
+    fn model_path(&self, repo: &str, file: &str) -> Result<PathBuf, String> {
+        let relative = Path::new(file);
+        if !valid_repo(repo) {
+            return Err(format!("Invalid repository '{}'", repo));
+        }
+        let valid = !file.is_empty()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !valid {
+            return Err(format!("Invalid model file name '{}'", file));
+        }
+        Ok(self.repo_dir(repo).join(relative))
+    }
+
+    // Downloads `file` from the current repository unless it is already
+    // cached, and returns its path. Progress is emitted on
+    // `model-download-progress` and the outcome on `model-download-finished`.
+    pub async fn download(&self, app: &AppHandle, file: &str) -> Result<PathBuf, String> {
+        let (repo, token) = self.repository()?;
+        let target = self.model_path(&repo, file)?;
+        let key = (repo.clone(), file.to_string());
+
+        let cancel = Arc::new(CancelFlag::default());
+        {
+            let mut downloads = self.downloads.lock().map_err(|e| e.to_string())?;
+            if downloads.contains_key(&key) {
+                return Err(format!("{} is already being downloaded", file));
+            }
+            downloads.insert(key.clone(), cancel.clone());
+        }
+
+        let download = Download {
+            manager: self,
+            app,
+            repo: &repo,
+            file,
+            token: token.as_deref(),
+            target: &target,
+            cancel: &cancel,
+        };
+        let result = download.run().await;
+        if let Ok(mut downloads) = self.downloads.lock() {
+            downloads.remove(&key);
+        }
+
+        let (status, message) = match &result {
+            Ok(_) => (DownloadStatus::Succeeded, format!("{} downloaded", file)),
+            Err(e) if cancel.is_cancelled() => (DownloadStatus::Cancelled, e.clone()),
+            Err(e) => (DownloadStatus::Failed, e.clone()),
+        };
+        let _ = app.emit(
+            "model-download-finished",
+            DownloadFinished {
+                repo: repo.clone(),
+                file: file.to_string(),
+                status,
+                message,
+            },
+        );
+        result.map(|_| target)
+    }
+
+    // Cancels the download of `file` from `repo`, which need not be the
+    // current repository any more.
+    pub fn cancel(&self, repo: &str, file: &str) -> Result<(), String> {
+        let downloads = self.downloads.lock().map_err(|e| e.to_string())?;
+        let cancel = downloads
+            .get(&(repo.to_string(), file.to_string()))
+            .ok_or_else(|| format!("{} is not being downloaded", file))?;
+        cancel.cancel();
+        Ok(())
+    }
+
+    // Models in the models directory, including interrupted downloads.
+    pub fn list_cached(&self) -> Result<Vec<CachedModel>, String> {
+        let mut models = Vec::new();
+        let Ok(repos) = fs::read_dir(&self.dir) else {
+            return Ok(models);
+        };
+        for repo_dir in repos.flatten() {
+            let path = repo_dir.path();
+            if !path.is_dir() {
+                continue;
+            }
+            let repo = repo_dir.file_name().to_string_lossy().replace("--", "/");
+            let mut files = Vec::new();
+            collect_files(&path, &mut files);
+            for file in files {
+                let name = file.to_string_lossy();
+                let (complete, model) = match name.strip_suffix(PART_SUFFIX) {
+                    Some(model) => (false, PathBuf::from(model)),
+                    None => (true, file.clone()),
+                };
+                if !is_model(&model) {
+                    continue;
+                }
+                let Ok(metadata) = fs::metadata(&file) else {
+                    continue;
+                };
+                let relative = model.strip_prefix(&path).unwrap_or(&model);
+                models.push(CachedModel {
+                    repo: repo.clone(),
+                    file: relative.to_string_lossy().replace('\\', "/"),
+                    path: file.to_string_lossy().into_owned(),
+                    size: metadata.len(),
+                    modified: metadata.modified().map(epoch_millis).unwrap_or(0),
+                    complete,
+                });
+            }
+        }
+        models.sort_by(|a, b| (&a.repo, &a.file).cmp(&(&b.repo, &b.file)));
+        Ok(models)
+    }
+
+    // Removes a cached model along with any partial download of it.
+    pub fn delete(&self, repo: &str, file: &str) -> Result<(), String> {
+        let target = self.model_path(repo, file)?;
+        if self
+            .downloads
+            .lock()
+            .map_err(|e| e.to_string())?
+            .contains_key(&(repo.to_string(), file.to_string()))
+        {
+            return Err(format!("{} is being downloaded; cancel it first", file));
+        }
+
+        let mut found = false;
+        for path in [target.clone(), part_path(&target)] {
+            if path.exists() {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+                found = true;
+            }
+        }
+        if !found {
+            return Err(format!("{} is not cached", file));
+        }
+
+        // Folders left empty, up to the repository's
+        let repo_dir = self.repo_dir(repo);
+        let mut dir = target.parent();
+        while let Some(current) = dir {
+            if !current.starts_with(&repo_dir) || fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+        Ok(())
+    }
+}
+
+struct Download<'a> {
+    manager: &'a ModelManager,
+    app: &'a AppHandle,
+    repo: &'a str,
+    file: &'a str,
+    token: Option<&'a str>,
+    target: &'a Path,
+    cancel: &'a Arc<CancelFlag>,
+}
+
+impl Download<'_> {
+    async fn run(&self) -> Result<(), String> {
+        let remote = self
+            .manager
+            .list_files(self.repo, self.token)
+            .await?
+            .into_iter()
+            .find(|model| model.file == self.file)
+            .ok_or_else(|| format!("{} is not a GGUF file in {}", self.file, self.repo))?;
+        if fs::metadata(self.target).is_ok_and(|metadata| metadata.len() == remote.size) {
+            return Ok(());
+        }
+        if let Some(parent) = self.target.parent() {
+            fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+
+        let part = part_path(self.target);
+        let mut downloaded = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+        if downloaded > remote.size {
+            fs::remove_file(&part).map_err(|e| e.to_string())?;
+            downloaded = 0;
+        }
+        if downloaded < remote.size {
+            self.fetch(&part, downloaded, remote.size).await?;
+        }
+
+        // Cancelling after the last chunk keeps the complete file, which is
+        // verified when the download is started again
+        if let Some(expected) = remote.sha256 {
+            self.check_cancelled()?;
+            self.progress(DownloadPhase::Verifying, remote.size, Some(remote.size));
+            let path = part.clone();
+            let cancel = self.cancel.clone();
+            let actual = run_blocking(move || sha256_file(&path, &cancel)).await?;
+            if !actual.eq_ignore_ascii_case(&expected) {
+                // A corrupt partial file would fail again on resume
+                let _ = fs::remove_file(&part);
+                return Err(format!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    self.file, expected, actual
+                ));
+            }
+        }
+        self.check_cancelled()?;
+        fs::rename(&part, self.target)
+            .map_err(|e| format!("Failed to move {} into place: {}", self.file, e))
+    }
+
+    fn check_cancelled(&self) -> Result<(), String> {
+        if self.cancel.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        Ok(())
+    }
+
+    // Appends the rest of the file from byte `offset` to `part`.
+    async fn fetch(&self, part: &Path, mut offset: u64, total: u64) -> Result<(), String> {
+        let url = format!(
+            "{}/{}/resolve/main/{}",
+            HUB_URL,
+            url_path(self.repo),
+            url_path(self.file)
+        );
+        let mut request = self.manager.request(&url, self.token);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach Hugging Face: {}", e))?;
+        let resumed = match response.status() {
+            StatusCode::PARTIAL_CONTENT => true,
+            status if status.is_success() => false,
+            status => return Err(hub_error(self.repo, status)),
+        };
+        // The server may ignore the range and send the whole file
+        if !resumed {
+            offset = 0;
+        }
+        let mut out = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed)
+            .truncate(!resumed)
+            .open(part)
+            .await
+            .map_err(|e| format!("Failed to open {}: {}", part.display(), e))?;
+
+        let mut downloaded = offset;
+        let mut reported = Instant::now();
+        self.progress(DownloadPhase::Downloading, downloaded, Some(total));
+        loop {
+            let chunk = tokio::select! {
+                _ = self.cancel.notify.notified() => {
+                    // The partial file stays, so the download can be resumed
+                    let _ = out.flush().await;
+                    return Err(CANCELLED.to_string());
+                }
+                chunk = tokio::time::timeout(STALL_TIMEOUT, response.chunk()) => chunk,
+            };
+            let chunk = chunk
+                .map_err(|_| {
+                    format!(
+                        "The download of {} stalled for {}s",
+                        self.file,
+                        STALL_TIMEOUT.as_secs()
+                    )
+                })?
+                .map_err(|e| format!("Download of {} failed: {}", self.file, e))?;
+            let Some(chunk) = chunk else {
+                break;
+            };
+            out.write_all(&chunk)
+                .await
+                .map_err(|e| format!("Failed to write {}: {}", part.display(), e))?;
+            downloaded += chunk.len() as u64;
+            if reported.elapsed() >= PROGRESS_INTERVAL {
+                self.progress(DownloadPhase::Downloading, downloaded, Some(total));
+                reported = Instant::now();
+            }
+        }
+        out.flush().await.map_err(|e| e.to_string())?;
+        self.progress(DownloadPhase::Downloading, downloaded, Some(total));
+
+        if downloaded != total {
+            return Err(format!(
+                "Download of {} ended after {} of {} bytes; try again to resume",
+                self.file, downloaded, total
+            ));
+        }
+        Ok(())
+    }
+
+    fn progress(&self, phase: DownloadPhase, downloaded: u64, total: Option<u64>) {
+        let percent = match total {
+            Some(total) if total > 0 => Some((downloaded * 100 / total) as u32),
+            _ => None,
+        };
+        let _ = self.app.emit(
+            "model-download-progress",
+            DownloadProgress {
+                repo: self.repo.to_string(),
+                file: self.file.to_string(),
+                phase,
+                downloaded,
+                total,
+                percent,
+            },
+        );
+    }
+}
+
+// `owner/name`, with nothing that could step out of the models directory.
+fn valid_repo(repo: &str) -> bool {
+    let parts: Vec<&str> = repo.split('/').collect();
+    parts.len() == 2
+        && parts
+            .iter()
+            .all(|part| !part.is_empty() && !part.starts_with('.') && !part.contains('\\'))
+}
+
+// Percent-encodes each `/`-separated segment of a repository or file name
+// for use in a Hub URL.
+fn url_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| {
+            segment
+                .bytes()
+                .map(|b| match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        (b as char).to_string()
+                    }
+                    b => format!("%{:02X}", b),
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn is_model(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case(MODEL_EXTENSION))
+}
+
+fn part_path(target: &Path) -> PathBuf {
+    let mut part = target.as_os_str().to_owned();
+    part.push(PART_SUFFIX);
+    PathBuf::from(part)
+}
+
+// The files anywhere under `dir`. Symlinked files are kept, but symlinked
+// folders are not followed, so a link back up the tree can't loop.
+pub fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_dir() {
+            collect_files(&path, files);
+        } else if !(file_type.is_symlink() && path.is_dir()) {
+            files.push(path);
+        }
+    }
+}
+
+// Hex SHA-256 of the file; stops with "Cancelled" between reads.
+fn sha256_file(path: &Path, cancel: &CancelFlag) -> Result<String, String> {
+    let mut file =
+        File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        if cancel.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        let read = file.read(&mut buffer).map_err(|e| e.to_string())?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+fn hub_error(repo: &str, status: StatusCode) -> String {
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            format!(
+                "Access to {} was denied; check the Hugging Face token",
+                repo
+            )
+        }
+        StatusCode::NOT_FOUND => format!("{} was not found on Hugging Face", repo),
+        status => format!("Hugging Face answered {} for {}", status, repo),
+    }
+}
+
+// The GGUF files of the repository set with `ai_set_repository`.
+#[tauri::command]
+pub async fn list_remote_models(
+    models: State<'_, ModelManager>,
+) -> Result<Vec<RemoteModel>, String> {
+    models.list_remote().await
+}
+
+// Downloads `file` into the models directory and returns its path. An
+// interrupted or cancelled download resumes where it stopped.
+#[tauri::command]
+pub async fn download_model(
+    file: String,
+    app: AppHandle,
+    models: State<'_, ModelManager>,
+) -> Result<String, String> {
+    let path = models.download(&app, &file).await?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub fn cancel_model_download(
+    repo: String,
+    file: String,
+    models: State<'_, ModelManager>,
+) -> Result<String, String> {
+    models.cancel(&repo, &file)?;
+    Ok(format!("Download of {} cancelled", file))
+}
+
+#[tauri::command]
+pub fn list_cached_models(models: State<'_, ModelManager>) -> Result<CachedModels, String> {
+    Ok(CachedModels {
+        dir: models.dir.to_string_lossy().into_owned(),
+        models: models.list_cached()?,
+    })
+}
+
+#[tauri::command]
+pub fn delete_cached_model(
+    repo: String,
+    file: String,
+    models: State<'_, ModelManager>,
+) -> Result<String, String> {
+    models.delete(&repo, &file)?;
+    Ok(format!("{} deleted", file))
+}
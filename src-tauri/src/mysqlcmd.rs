@@ -3,8 +3,14 @@ use mysql::*;
 use serde::Deserialize;
 use serde::Serialize;
 use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
 use tauri::State;
 
+use crate::engine::{connection_label, Engine};
+use crate::history::{Execution, HistoryStore};
+use crate::runtime::run_blocking;
+use crate::stats::{millis, ExecutionStats, QueryResponse, QueryWarning};
+
 #[derive(Serialize, Deserialize)]
 pub struct ConnectionString {
     pub value: String,
@@ -42,6 +48,7 @@ pub struct DatabaseObjects {
 
 pub struct DbConnection {
     pool: Mutex<Option<Pool>>,
+    connection_string: Mutex<Option<String>>,
     selected_db: Mutex<Option<String>>,
 }
 
@@ -49,83 +56,124 @@ impl DbConnection {
     pub fn new() -> Self {
         DbConnection {
             pool: Mutex::new(None),
+            connection_string: Mutex::new(None),
             selected_db: Mutex::new(None),
         }
     }
 
-    fn get_conn(&self) -> Result<PooledConn, String> {
+    // Connection string with the password removed.
+    pub fn label(&self) -> Result<Option<String>, String> {
+        let connection_string = self.connection_string.lock().map_err(|e| e.to_string())?;
+        Ok(connection_string.as_deref().map(connection_label))
+    }
+
+    // The pool is cheap to clone, so the lock is only held long enough to
+    // copy the handle and every command checks out its own connection.
+    pub fn pool(&self) -> Result<Pool, String> {
         let pool = self.pool.lock().map_err(|e| e.to_string())?;
-        match &*pool {
-            Some(p) => p.get_conn().map_err(|e| e.to_string()),
-            None => Err("Database not connected".to_string()),
-        }
+        pool.clone().ok_or_else(|| "Database not connected".to_string())
+    }
+
+    pub fn selected_db(&self) -> Result<Option<String>, String> {
+        let selected_db = self.selected_db.lock().map_err(|e| e.to_string())?;
+        Ok(selected_db.clone())
+    }
+
+    // Pool and selected database for commands that need both.
+    pub fn session(&self) -> Result<(Pool, String), String> {
+        let pool = self.pool()?;
+        let db = self
+            .selected_db()?
+            .ok_or_else(|| "No database selected. Please select a database first.".to_string())?;
+        Ok((pool, db))
     }
 }
 
+// Checks out a connection with the given database selected.
+pub fn get_conn(pool: &Pool, db: &str) -> Result<PooledConn, String> {
+    let mut conn = pool.get_conn().map_err(|e| e.to_string())?;
+    conn.query_drop(format!("USE {}", db))
+        .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
 #[tauri::command]
-pub fn connect_to_mysql(
+pub async fn connect_to_mysql(
     connection_string: ConnectionString,
     state: State<'_, DbConnection>,
 ) -> Result<String, String> {
     let opts = Opts::from_url(&connection_string.value).map_err(|e| e.to_string())?;
-    let pool = Pool::new(opts).map_err(|e| e.to_string())?;
+    let pool = run_blocking(move || Pool::new(opts).map_err(|e| e.to_string())).await?;
 
     let mut current_pool = state.pool.lock().map_err(|e| e.to_string())?;
     *current_pool = Some(pool);
 
+    let mut current_conn_string = state.connection_string.lock().map_err(|e| e.to_string())?;
+    *current_conn_string = Some(connection_string.value);
+
     Ok("Connected successfully".to_string())
 }
 
 #[tauri::command]
-pub fn show_databases(state: State<'_, DbConnection>) -> Result<Vec<String>, String> {
-    let mut conn = state.get_conn()?;
-    let databases: Vec<String> = conn
-        .query_map("SHOW DATABASES", |database: String| database)
-        .map_err(|e| e.to_string())?;
+pub async fn show_databases(state: State<'_, DbConnection>) -> Result<Vec<String>, String> {
+    let pool = state.pool()?;
 
-    Ok(databases)
+    run_blocking(move || {
+        let mut conn = pool.get_conn().map_err(|e| e.to_string())?;
+        let databases: Vec<String> = conn
+            .query_map("SHOW DATABASES", |database: String| database)
+            .map_err(|e| e.to_string())?;
+
+        Ok(databases)
+    })
+    .await
 }
 
 #[tauri::command]
-pub fn select_database(
+pub async fn select_database(
     db_name: DatabaseName,
     state: State<'_, DbConnection>,
 ) -> Result<DatabaseObjects, String> {
-    let mut conn = state.get_conn()?;
-    
-    // Select the database
-    conn.query_drop(format!("USE {}", db_name.name))
-        .map_err(|e| e.to_string())?;
-    
+    let pool = state.pool()?;
+    let name = db_name.name.clone();
+
+    let objects = run_blocking(move || load_database_objects(&pool, &name)).await?;
+
     // Update the selected database
     let mut selected_db = state.selected_db.lock().map_err(|e| e.to_string())?;
-    *selected_db = Some(db_name.name.clone());
-    
+    *selected_db = Some(db_name.name);
+
+    Ok(objects)
+}
+
+fn load_database_objects(pool: &Pool, db_name: &str) -> Result<DatabaseObjects, String> {
+    let mut conn = get_conn(pool, db_name)?;
+
     // Get all tables
     let tables: Vec<String> = conn
         .query_map("SHOW TABLES", |table: String| table)
         .map_err(|e| e.to_string())?;
-    
+
     // Get all stored procedures
     let procedures: Vec<String> = conn
         .query_map(
-            format!("SELECT ROUTINE_NAME FROM INFORMATION_SCHEMA.ROUTINES WHERE ROUTINE_TYPE = 'PROCEDURE' AND ROUTINE_SCHEMA = '{}'", db_name.name),
+            format!("SELECT ROUTINE_NAME FROM INFORMATION_SCHEMA.ROUTINES WHERE ROUTINE_TYPE = 'PROCEDURE' AND ROUTINE_SCHEMA = '{}'", db_name),
             |procedure: String| procedure
         )
         .map_err(|e| e.to_string())?;
-    
+
     // Get all views
     let views: Vec<String> = conn
         .query_map(
-            format!("SELECT TABLE_NAME FROM INFORMATION_SCHEMA.VIEWS WHERE TABLE_SCHEMA = '{}'", db_name.name),
+            format!("SELECT TABLE_NAME FROM INFORMATION_SCHEMA.VIEWS WHERE TABLE_SCHEMA = '{}'", db_name),
             |view: String| view
         )
         .map_err(|e| e.to_string())?;
-    
+
     // Get all functions
     let functions: Vec<String> = conn
         .query_map(
-            format!("SELECT ROUTINE_NAME FROM INFORMATION_SCHEMA.ROUTINES WHERE ROUTINE_TYPE = 'FUNCTION' AND ROUTINE_SCHEMA = '{}'", db_name.name),
+            format!("SELECT ROUTINE_NAME FROM INFORMATION_SCHEMA.ROUTINES WHERE ROUTINE_TYPE = 'FUNCTION' AND ROUTINE_SCHEMA = '{}'", db_name),
             |function: String| function
         )
         .map_err(|e| e.to_string())?;
@@ -140,94 +188,146 @@ pub fn select_database(
 
 
 #[tauri::command]
-pub fn execute_query(
+pub async fn execute_query(
     query: String,
     state: State<'_, DbConnection>,
-) -> Result<String, String> {
-    // Lock the mutex to access the shared DbConnection
-    let mut conn = state.get_conn()?;
-
-    // Select the database
-    let selected_db = state.selected_db.lock().map_err(|e| e.to_string())?;
-    
-    // Check if a database is selected
-    if let Some(db) = &*selected_db {
-        // Select the database
-        conn.query_drop(format!("USE {}", db))
-            .map_err(|e| e.to_string())?;
-    } else {
-        return Err("No database selected. Please select a database first.".to_string());
-    }
+    history: State<'_, HistoryStore>,
+) -> Result<QueryResponse, String> {
+    let (pool, db) = state.session()?;
+    let execution = Execution {
+        engine: Engine::Mysql,
+        connection: state.label()?,
+        database: Some(db.clone()),
+        query: &query,
+    };
+    let started = SystemTime::now();
+
+    let statement = query.clone();
+    let result = run_blocking(move || run_query(&pool, &db, &statement)).await;
+
+    history.record(execution, started, &result);
+    result
+}
 
+fn run_query(pool: &Pool, db: &str, query: &str) -> Result<QueryResponse, String> {
+    let mut conn = get_conn(pool, db)?;
+    let mut stats = ExecutionStats::default();
+    let started = Instant::now();
 
     let query_type = query.trim().to_uppercase();
-    
-    if query_type.starts_with("SELECT COLUMN_NAME, DATA_TYPE, CHARACTER_MAXIMUM_LENGTH, IS_NULLABLE FROM INFORMATION_SCHEMA.COLUMNS") {
-            // Special handling for table structure query
-        let result: Vec<Row> =  conn
-        .query_iter(&query)
-        .map_err(|e| format!("Query execution error: {}", e))?
-        .flat_map(|result| result)
-        .collect();
-        
-        if result.is_empty() {
-            return Ok("No columns found for the specified table.".to_string());
-        }
-        
-        let mut output = String::from("Column Name | Data Type | Max Length | Nullable\n");
-        output.push_str("-----------|-----------|------------|----------\n");
-        
-        for row in result {
-            let column_name = value_to_string(&row[0]);
-            let data_type = value_to_string(&row[1]);
-            let max_length = value_to_string(&row[2]);
-            let is_nullable = value_to_string(&row[3]);
-            
-            output.push_str(&format!("{:<11}|{:<11}|{:<12}|{:<10}\n", 
-                column_name, data_type, max_length, is_nullable));
-        }
-        
-        Ok(output)
 
-    } else if query_type.starts_with("SELECT") || query_type.starts_with("SHOW") {
-
-        let result: Vec<Row> = conn
-            .query_iter(&query)
-            .map_err(|e| format!("Query execution error: {}", e))?
-            .flat_map(|result| result)
-            .collect();
-
-        let mut output = String::new();
+    let output = if query_type.starts_with("SELECT COLUMN_NAME, DATA_TYPE, CHARACTER_MAXIMUM_LENGTH, IS_NULLABLE FROM INFORMATION_SCHEMA.COLUMNS") {
+        // Special handling for table structure query
+        let result = fetch_rows(&mut conn, query, started, &mut stats)?;
+        stats.wall_time_ms = millis(started.elapsed());
 
         if result.is_empty() {
-            return Ok("Query executed successfully. No rows returned.".to_string());
+            "No columns found for the specified table.".to_string()
+        } else {
+            let mut output = String::from("Column Name | Data Type | Max Length | Nullable\n");
+            output.push_str("-----------|-----------|------------|----------\n");
+
+            for row in result {
+                let column_name = value_to_string(&row[0]);
+                let data_type = value_to_string(&row[1]);
+                let max_length = value_to_string(&row[2]);
+                let is_nullable = value_to_string(&row[3]);
+
+                output.push_str(&format!("{:<11}|{:<11}|{:<12}|{:<10}\n",
+                    column_name, data_type, max_length, is_nullable));
+            }
+
+            output
         }
+    } else if query_type.starts_with("SELECT") || query_type.starts_with("SHOW") {
+        let result = fetch_rows(&mut conn, query, started, &mut stats)?;
+        stats.wall_time_ms = millis(started.elapsed());
 
-        for row in result {
-            for (i, column) in row.columns_ref().iter().enumerate() {
-                if i>0 {
-                    output.push('\t');
+        if result.is_empty() {
+            "Query executed successfully. No rows returned.".to_string()
+        } else {
+            let mut output = String::new();
+
+            for row in result {
+                for (i, column) in row.columns_ref().iter().enumerate() {
+                    if i>0 {
+                        output.push('\t');
+                    }
+                    output.push_str(&column.name_str());
+                    output.push_str(": ");
+                    output.push_str(&value_to_string(&row[i]));
                 }
-                output.push_str(&column.name_str());
-                output.push_str(": ");
-                output.push_str(&value_to_string(&row[i]));
+                output.push('\n');
             }
-            output.push('\n');
+
+            output
         }
+    } else {
+        conn.query_drop(query)
+            .map_err(|e| format!("Query execution error: {}", e))?;
+        stats.wall_time_ms = millis(started.elapsed());
+        stats.rows_affected = Some(conn.affected_rows());
 
-        Ok(output)
+        format!("Query executed successfully. Rows affected: {}", conn.affected_rows())
+    };
 
-    } else {
-        let _result = conn.query_drop(query);
+    collect_server_stats(&mut conn, &mut stats);
 
-        if let Err(e) = _result {
-            return Err(format!("Query execution error: {}", e));
-        }
+    Ok(QueryResponse { output, stats })
+}
 
-        Ok(format!("Query executed successfully. Rows affected: {}", conn.affected_rows()))
+pub fn fetch_rows(
+    conn: &mut PooledConn,
+    query: &str,
+    started: Instant,
+    stats: &mut ExecutionStats,
+) -> Result<Vec<Row>, String> {
+    let mut rows = Vec::new();
+    let result = conn
+        .query_iter(query)
+        .map_err(|e| format!("Query execution error: {}", e))?;
+
+    for row in result {
+        let row = row.map_err(|e| format!("Query execution error: {}", e))?;
+        stats.mark_row(started);
+        rows.push(row);
     }
+
+    Ok(rows)
 }
 
+// Warnings and server-side statistics for the statement that just ran. Both
+// are best effort: SHOW WARNINGS has to run before anything else resets the
+// diagnostics area, and performance_schema may be disabled or not readable.
+pub fn collect_server_stats(conn: &mut PooledConn, stats: &mut ExecutionStats) {
+    if conn.warnings() > 0 {
+        if let Ok(warnings) = conn.query_map(
+            "SHOW WARNINGS",
+            |(level, code, message): (String, u32, String)| QueryWarning {
+                level,
+                code,
+                message,
+            },
+        ) {
+            stats.warnings = warnings;
+        }
+    }
+
+    let history: Option<(Option<u64>, Option<u64>)> = conn
+        .query_first(
+            "SELECT TIMER_WAIT, ROWS_EXAMINED FROM performance_schema.events_statements_history \
+             WHERE THREAD_ID = PS_CURRENT_THREAD_ID() AND SQL_TEXT NOT LIKE 'SHOW WARNINGS%' \
+             ORDER BY EVENT_ID DESC LIMIT 1",
+        )
+        .ok()
+        .flatten();
+
+    if let Some((timer_wait, rows_examined)) = history {
+        // TIMER_WAIT is in picoseconds
+        stats.server_time_ms = timer_wait.map(|t| t as f64 / 1_000_000_000.0);
+        stats.rows_examined = rows_examined;
+    }
+}
 
 fn value_to_string(value: &mysql::Value) -> String {
     match value {
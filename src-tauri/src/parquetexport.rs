@@ -0,0 +1,358 @@
+use arrow_array::builder::{
+    BinaryBuilder, BooleanBuilder, Date32Builder, Decimal128Builder, Float32Builder,
+    Float64Builder, Int16Builder, Int32Builder, Int64Builder, Int8Builder, StringBuilder,
+    TimestampMicrosecondBuilder, UInt16Builder, UInt32Builder, UInt64Builder, UInt8Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{Datelike, NaiveDate};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::sync::Arc;
+
+use crate::resultset::{Cell, CellKind, ColumnInfo, RowSink};
+
+// Widest precision a Decimal128 column holds
+const MAX_DECIMAL_PRECISION: u32 = 38;
+
+// Buffers `row_group_size` rows in Arrow builders and writes each batch as
+// one Parquet row group. The schema comes from the database column types.
+pub struct ParquetSink {
+    file: Option<File>,
+    row_group_size: usize,
+    schema: Option<SchemaRef>,
+    writer: Option<ArrowWriter<File>>,
+    builders: Vec<ColumnBuilder>,
+    buffered: usize,
+    replaced: Vec<Replaced>,
+}
+
+// Text values written as null in one column
+struct Replaced {
+    column: String,
+    count: u64,
+    first: Option<String>,
+}
+
+impl ParquetSink {
+    pub fn new(file: File, row_group_size: usize) -> Self {
+        ParquetSink {
+            file: Some(file),
+            row_group_size: row_group_size.max(1),
+            schema: None,
+            writer: None,
+            builders: Vec::new(),
+            buffered: 0,
+            replaced: Vec::new(),
+        }
+    }
+
+    fn flush_batch(&mut self) -> Result<(), String> {
+        if self.buffered == 0 {
+            return Ok(());
+        }
+        let (Some(schema), Some(writer)) = (&self.schema, &mut self.writer) else {
+            return Ok(());
+        };
+
+        let arrays: Vec<ArrayRef> = self
+            .builders
+            .iter_mut()
+            .map(ColumnBuilder::finish)
+            .collect();
+        let batch = RecordBatch::try_new(schema.clone(), arrays).map_err(|e| e.to_string())?;
+        writer.write(&batch).map_err(parquet_error)?;
+        writer.flush().map_err(parquet_error)?;
+        self.buffered = 0;
+        Ok(())
+    }
+}
+
+fn parquet_error(e: parquet::errors::ParquetError) -> String {
+    format!("Parquet export error: {}", e)
+}
+
+impl RowSink for ParquetSink {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String> {
+        let fields: Vec<Field> = columns
+            .iter()
+            .map(|column| {
+                let nullable = column.nullable || falls_back_to_text(column.kind);
+                Field::new(&column.name, data_type(column), nullable)
+            })
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+
+        self.builders = schema
+            .fields()
+            .iter()
+            .map(|field| ColumnBuilder::new(field.data_type()))
+            .collect::<Result<_, _>>()?;
+        self.replaced = columns
+            .iter()
+            .map(|column| Replaced {
+                column: column.name.clone(),
+                count: 0,
+                first: None,
+            })
+            .collect();
+
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(self.row_group_size)
+            .build();
+        let file = self
+            .file
+            .take()
+            .ok_or("The Parquet file was already started")?;
+        self.writer = Some(
+            ArrowWriter::try_new(file, schema.clone(), Some(properties)).map_err(parquet_error)?,
+        );
+        self.schema = Some(schema);
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        let columns = self.builders.iter_mut().zip(&mut self.replaced);
+        for ((builder, replaced), cell) in columns.zip(row) {
+            match cell {
+                Cell::Text(text) if !matches!(builder, ColumnBuilder::Utf8(_)) => {
+                    builder.append_null();
+                    replaced.count += 1;
+                    replaced.first.get_or_insert(text);
+                }
+                cell => builder.append(cell)?,
+            }
+        }
+        self.buffered += 1;
+        if self.buffered >= self.row_group_size {
+            self.flush_batch()?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.flush_batch()?;
+        if let Some(writer) = self.writer.take() {
+            writer.close().map_err(parquet_error)?;
+        }
+        Ok(())
+    }
+
+    fn warnings(&self) -> Vec<String> {
+        self.replaced
+            .iter()
+            .filter_map(|replaced| {
+                Some(format!(
+                    "Column {}: {} value(s) such as '{}' don't fit the column type and were written as null",
+                    replaced.column,
+                    replaced.count,
+                    replaced.first.as_ref()?
+                ))
+            })
+            .collect()
+    }
+}
+
+// MySQL values that don't parse as their column type (zero dates, numbers
+// out of range) arrive as text, and are written as null
+fn falls_back_to_text(kind: CellKind) -> bool {
+    matches!(
+        kind,
+        CellKind::Int | CellKind::UInt | CellKind::Float | CellKind::Date | CellKind::DateTime
+    )
+}
+
+fn data_type(column: &ColumnInfo) -> DataType {
+    let source = column.type_name.as_str();
+
+    match column.kind {
+        CellKind::Bool => DataType::Boolean,
+        CellKind::Int => match source {
+            "tiny" => DataType::Int8,
+            "short" | "int2" | "year" => DataType::Int16,
+            "int24" | "long" | "int4" => DataType::Int32,
+            _ => DataType::Int64,
+        },
+        CellKind::UInt => match source {
+            "tiny" => DataType::UInt8,
+            "short" | "year" => DataType::UInt16,
+            "int24" | "long" | "oid" => DataType::UInt32,
+            _ => DataType::UInt64,
+        },
+        CellKind::Float => match source {
+            "float" | "float4" => DataType::Float32,
+            _ => DataType::Float64,
+        },
+        // Unconstrained NUMERIC has no fixed scale, so it stays text
+        CellKind::Decimal => match (column.precision, column.scale) {
+            (Some(p), Some(s)) if p <= MAX_DECIMAL_PRECISION && s <= p => {
+                DataType::Decimal128(p as u8, s as i8)
+            }
+            _ => DataType::Utf8,
+        },
+        CellKind::Bytes => DataType::Binary,
+        CellKind::Date => DataType::Date32,
+        CellKind::DateTime => DataType::Timestamp(TimeUnit::Microsecond, None),
+        // Instants, so readers don't take them for local wall-clock times
+        CellKind::TimestampTz => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+        CellKind::Text | CellKind::Time | CellKind::Json => DataType::Utf8,
+    }
+}
+
+enum ColumnBuilder {
+    Boolean(BooleanBuilder),
+    Int8(Int8Builder),
+    Int16(Int16Builder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    UInt8(UInt8Builder),
+    UInt16(UInt16Builder),
+    UInt32(UInt32Builder),
+    UInt64(UInt64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Decimal(Decimal128Builder, i8),
+    Utf8(StringBuilder),
+    Binary(BinaryBuilder),
+    Date32(Date32Builder),
+    Timestamp(TimestampMicrosecondBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType) -> Result<Self, String> {
+        Ok(match data_type {
+            DataType::Boolean => ColumnBuilder::Boolean(BooleanBuilder::new()),
+            DataType::Int8 => ColumnBuilder::Int8(Int8Builder::new()),
+            DataType::Int16 => ColumnBuilder::Int16(Int16Builder::new()),
+            DataType::Int32 => ColumnBuilder::Int32(Int32Builder::new()),
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::new()),
+            DataType::UInt8 => ColumnBuilder::UInt8(UInt8Builder::new()),
+            DataType::UInt16 => ColumnBuilder::UInt16(UInt16Builder::new()),
+            DataType::UInt32 => ColumnBuilder::UInt32(UInt32Builder::new()),
+            DataType::UInt64 => ColumnBuilder::UInt64(UInt64Builder::new()),
+            DataType::Float32 => ColumnBuilder::Float32(Float32Builder::new()),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::new()),
+            DataType::Decimal128(p, s) => ColumnBuilder::Decimal(
+                Decimal128Builder::new()
+                    .with_precision_and_scale(*p, *s)
+                    .map_err(|e| e.to_string())?,
+                *s,
+            ),
+            DataType::Binary => ColumnBuilder::Binary(BinaryBuilder::new()),
+            DataType::Date32 => ColumnBuilder::Date32(Date32Builder::new()),
+            DataType::Timestamp(_, timezone) => ColumnBuilder::Timestamp(
+                TimestampMicrosecondBuilder::new().with_timezone_opt(timezone.clone()),
+            ),
+            _ => ColumnBuilder::Utf8(StringBuilder::new()),
+        })
+    }
+
+    fn append(&mut self, cell: Cell) -> Result<(), String> {
+        fn narrow<T: TryFrom<i128>>(value: i128) -> Result<T, String> {
+            T::try_from(value).map_err(|_| format!("{} is out of range for the column", value))
+        }
+
+        if cell == Cell::Null {
+            self.append_null();
+            return Ok(());
+        }
+
+        match (self, cell) {
+            (ColumnBuilder::Boolean(b), Cell::Bool(v)) => b.append_value(v),
+            (ColumnBuilder::Int8(b), Cell::Int(v)) => b.append_value(narrow(v as i128)?),
+            (ColumnBuilder::Int16(b), Cell::Int(v)) => b.append_value(narrow(v as i128)?),
+            (ColumnBuilder::Int32(b), Cell::Int(v)) => b.append_value(narrow(v as i128)?),
+            (ColumnBuilder::Int64(b), Cell::Int(v)) => b.append_value(v),
+            (ColumnBuilder::UInt8(b), Cell::UInt(v)) => b.append_value(narrow(v as i128)?),
+            (ColumnBuilder::UInt16(b), Cell::UInt(v)) => b.append_value(narrow(v as i128)?),
+            (ColumnBuilder::UInt32(b), Cell::UInt(v)) => b.append_value(narrow(v as i128)?),
+            (ColumnBuilder::UInt64(b), Cell::UInt(v)) => b.append_value(v),
+            (ColumnBuilder::Float32(b), Cell::Float(v)) => b.append_value(v as f32),
+            (ColumnBuilder::Float64(b), Cell::Float(v)) => b.append_value(v),
+            (ColumnBuilder::Decimal(b, scale), Cell::Decimal(v)) => b.append_value(
+                decimal_units(&v, *scale as u32)
+                    .ok_or_else(|| format!("{} does not fit the column's decimal type", v))?,
+            ),
+            (ColumnBuilder::Binary(b), Cell::Bytes(v)) => b.append_value(v),
+            (ColumnBuilder::Date32(b), Cell::Date(v)) => b.append_value(days_since_epoch(v)),
+            (ColumnBuilder::Timestamp(b), Cell::DateTime(v)) => {
+                b.append_value(v.and_utc().timestamp_micros())
+            }
+            (ColumnBuilder::Timestamp(b), Cell::TimestampTz(v)) => {
+                b.append_value(v.timestamp_micros())
+            }
+            (ColumnBuilder::Utf8(b), v) => b.append_value(v.to_text().unwrap_or_default()),
+            (_, v) => return Err(format!("Unexpected value {:?} for the column type", v)),
+        }
+        Ok(())
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            ColumnBuilder::Boolean(b) => b.append_null(),
+            ColumnBuilder::Int8(b) => b.append_null(),
+            ColumnBuilder::Int16(b) => b.append_null(),
+            ColumnBuilder::Int32(b) => b.append_null(),
+            ColumnBuilder::Int64(b) => b.append_null(),
+            ColumnBuilder::UInt8(b) => b.append_null(),
+            ColumnBuilder::UInt16(b) => b.append_null(),
+            ColumnBuilder::UInt32(b) => b.append_null(),
+            ColumnBuilder::UInt64(b) => b.append_null(),
+            ColumnBuilder::Float32(b) => b.append_null(),
+            ColumnBuilder::Float64(b) => b.append_null(),
+            ColumnBuilder::Decimal(b, _) => b.append_null(),
+            ColumnBuilder::Utf8(b) => b.append_null(),
+            ColumnBuilder::Binary(b) => b.append_null(),
+            ColumnBuilder::Date32(b) => b.append_null(),
+            ColumnBuilder::Timestamp(b) => b.append_null(),
+        }
+    }
+
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Boolean(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int16(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Int64(b) => Arc::new(b.finish()),
+            ColumnBuilder::UInt8(b) => Arc::new(b.finish()),
+            ColumnBuilder::UInt16(b) => Arc::new(b.finish()),
+            ColumnBuilder::UInt32(b) => Arc::new(b.finish()),
+            ColumnBuilder::UInt64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Float64(b) => Arc::new(b.finish()),
+            ColumnBuilder::Decimal(b, _) => Arc::new(b.finish()),
+            ColumnBuilder::Utf8(b) => Arc::new(b.finish()),
+            ColumnBuilder::Binary(b) => Arc::new(b.finish()),
+            ColumnBuilder::Date32(b) => Arc::new(b.finish()),
+            ColumnBuilder::Timestamp(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+fn days_since_epoch(date: NaiveDate) -> i32 {
+    // 1970-01-01 is day 719163 of the common era
+    date.num_days_from_ce() - 719_163
+}
+
+// Decimal text as an integer count of 10^-scale units, e.g. "-12.5" with
+// scale 2 is -1250. Extra fractional digits are rejected rather than rounded.
+fn decimal_units(value: &str, scale: u32) -> Option<i128> {
+    let (negative, digits) = match value.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, value),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > scale as usize {
+        return None;
+    }
+
+    let mut units = format!("{}{}", whole, fraction);
+    units.extend(std::iter::repeat('0').take(scale as usize - fraction.len()));
+    let units: i128 = units.parse().ok()?;
+    Some(if negative { -units } else { units })
+}
@@ -1,9 +1,17 @@
+use postgres::fallible_iterator::FallibleIterator;
 use postgres::types::Type;
-use postgres::{Client, NoTls};
+use postgres::{Client, NoTls, Row};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Instant, SystemTime};
 use tauri::State;
 
+use crate::engine::{connection_label, Engine};
+use crate::history::{Execution, HistoryStore};
+use crate::runtime::run_blocking;
+use crate::stats::{millis, ExecutionStats, QueryResponse};
+
 #[derive(Serialize, Deserialize)]
 pub struct ConnectionString {
     pub value: String,
@@ -27,71 +35,260 @@ pub struct DatabaseObjects {
     functions: Vec<String>,
 }
 
+// Idle clients kept for background jobs; more are closed when returned
+const MAX_IDLE: usize = 4;
+
+// Idle clients for the current connection string. `generation` is bumped
+// whenever the connection string changes so clients checked out against the
+// previous database are dropped instead of returned.
+struct PoolInner {
+    connection_string: Option<String>,
+    generation: u64,
+    idle: Vec<Client>,
+}
+
+// The client ad-hoc queries run on, with the generation it was opened for.
+// It is never reset, so transactions, `SET` and temporary tables carry over
+// from one command to the next like they would in psql.
+struct Pinned {
+    generation: u64,
+    client: Client,
+}
+
+#[derive(Clone)]
+pub struct ClientPool {
+    inner: Arc<Mutex<PoolInner>>,
+    pinned: Arc<Mutex<Option<Pinned>>>,
+}
+
+pub struct PinnedClient<'a> {
+    guard: MutexGuard<'a, Option<Pinned>>,
+}
+
+pub struct PooledClient {
+    client: Option<Client>,
+    generation: u64,
+    pool: ClientPool,
+}
+
+impl ClientPool {
+    fn new() -> Self {
+        ClientPool {
+            inner: Arc::new(Mutex::new(PoolInner {
+                connection_string: None,
+                generation: 0,
+                idle: Vec::new(),
+            })),
+            pinned: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // A pool of its own for a server other than the current connection;
+    // clients are opened on first use.
+    pub fn standalone(connection_string: String) -> Self {
+        let pool = ClientPool::new();
+        if let Ok(mut inner) = pool.inner.lock() {
+            inner.connection_string = Some(connection_string);
+        }
+        pool
+    }
+
+    pub fn connection_string(&self) -> Result<Option<String>, String> {
+        let inner = self.inner.lock().map_err(|e| e.to_string())?;
+        Ok(inner.connection_string.clone())
+    }
+
+    // Blocking: opens a new connection when no idle client is available.
+    // For background jobs; each checkout may be a different backend.
+    pub fn get(&self) -> Result<PooledClient, String> {
+        let (client, generation) = self.checkout()?;
+        Ok(PooledClient {
+            client: Some(client),
+            generation,
+            pool: self.clone(),
+        })
+    }
+
+    // Blocking: the session's own client, reconnected when the connection
+    // string changed or the server closed it. Held until the returned guard
+    // is dropped, so statements from concurrent commands queue up on it.
+    pub fn pinned(&self) -> Result<PinnedClient<'_>, String> {
+        let mut guard = self.pinned.lock().map_err(|e| e.to_string())?;
+        let generation = self.inner.lock().map_err(|e| e.to_string())?.generation;
+        let stale = match guard.as_ref() {
+            Some(pinned) => pinned.generation != generation || pinned.client.is_closed(),
+            None => true,
+        };
+        if stale {
+            let (client, generation) = self.checkout()?;
+            *guard = Some(Pinned { generation, client });
+        }
+        Ok(PinnedClient { guard })
+    }
+
+    fn checkout(&self) -> Result<(Client, u64), String> {
+        let (connection_string, generation) = {
+            let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+            let generation = inner.generation;
+            if let Some(client) = inner.idle.pop() {
+                return Ok((client, generation));
+            }
+            let connection_string = inner
+                .connection_string
+                .clone()
+                .ok_or("Database not connected")?;
+            (connection_string, generation)
+        };
+
+        let client = Client::connect(&connection_string, NoTls).map_err(|e| e.to_string())?;
+        Ok((client, generation))
+    }
+
+    fn reset(&self, connection_string: String, client: Client) -> Result<(), String> {
+        let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+        inner.connection_string = Some(connection_string);
+        inner.generation += 1;
+        inner.idle = vec![client];
+        Ok(())
+    }
+}
+
+impl Deref for PooledClient {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        self.client.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledClient {
+    fn deref_mut(&mut self) -> &mut Client {
+        self.client.as_mut().unwrap()
+    }
+}
+
+impl Deref for PinnedClient<'_> {
+    type Target = Client;
+
+    fn deref(&self) -> &Client {
+        &self.guard.as_ref().unwrap().client
+    }
+}
+
+impl DerefMut for PinnedClient<'_> {
+    fn deref_mut(&mut self) -> &mut Client {
+        &mut self.guard.as_mut().unwrap().client
+    }
+}
+
+// A job may leave its client inside a transaction (or an aborted one) or
+// with session settings changed, so clients are reset before they are
+// reused. DISCARD ALL can't run in a transaction block, hence two calls.
+fn reset_session(client: &mut Client) -> bool {
+    !client.is_closed()
+        && client.batch_execute("ROLLBACK").is_ok()
+        && client.batch_execute("DISCARD ALL").is_ok()
+}
+
+impl Drop for PooledClient {
+    fn drop(&mut self) {
+        if let Some(mut client) = self.client.take() {
+            if !reset_session(&mut client) {
+                return;
+            }
+            if let Ok(mut inner) = self.pool.inner.lock() {
+                if inner.generation == self.generation && inner.idle.len() < MAX_IDLE {
+                    inner.idle.push(client);
+                }
+            }
+        }
+    }
+}
+
 pub struct DbConnection {
-    client: Mutex<Option<Client>>,
-    connection_string: Mutex<Option<String>>,
+    pool: ClientPool,
     selected_db: Mutex<Option<String>>,
 }
 
 impl DbConnection {
     pub fn new() -> Self {
         DbConnection {
-            client: Mutex::new(None),
-            connection_string: Mutex::new(None),
+            pool: ClientPool::new(),
             selected_db: Mutex::new(None),
         }
     }
 
-    fn get_client(&self) -> Result<std::sync::MutexGuard<Option<Client>>, String> {
-        self.client.lock().map_err(|e| e.to_string())
+    pub fn pool(&self) -> ClientPool {
+        self.pool.clone()
+    }
+
+    // Connection string with the password removed.
+    pub fn label(&self) -> Result<Option<String>, String> {
+        Ok(self
+            .pool
+            .connection_string()?
+            .as_deref()
+            .map(connection_label))
+    }
+
+    // Pool for commands that run against the selected database.
+    pub fn session(&self) -> Result<ClientPool, String> {
+        if self.selected_db()?.is_none() {
+            return Err("No database selected. Please select a database first.".to_string());
+        }
+        Ok(self.pool())
+    }
+
+    pub fn selected_db(&self) -> Result<Option<String>, String> {
+        let selected_db = self.selected_db.lock().map_err(|e| e.to_string())?;
+        Ok(selected_db.clone())
     }
 }
 
 #[tauri::command]
-pub fn connect_to_postgres(
+pub async fn connect_to_postgres(
     connection_string: ConnectionString,
     state: State<'_, DbConnection>,
 ) -> Result<String, String> {
-    let client = Client::connect(&connection_string.value, NoTls).map_err(|e| e.to_string())?;
-
-    let mut current_client = state.client.lock().map_err(|e| e.to_string())?;
-    *current_client = Some(client);
+    let value = connection_string.value.clone();
+    let client = run_blocking(move || Client::connect(&value, NoTls).map_err(|e| e.to_string())).await?;
 
-    let mut current_conn_string = state.connection_string.lock().map_err(|e| e.to_string())?;
-    *current_conn_string = Some(connection_string.value);
+    state.pool.reset(connection_string.value, client)?;
 
     Ok("Connected successfully".to_string())
 }
 
 #[tauri::command]
-pub fn pg_show_databases(state: State<'_, DbConnection>) -> Result<Vec<String>, String> {
-    let mut client = state.get_client()?;
-    let client = client.as_mut().ok_or("Database not connected")?;
+pub async fn pg_show_databases(state: State<'_, DbConnection>) -> Result<Vec<String>, String> {
+    let pool = state.pool();
 
-    let rows = client
-        .query(
-            "SELECT datname FROM pg_database WHERE datistemplate = false",
-            &[],
-        )
-        .map_err(|e| e.to_string())?;
+    run_blocking(move || {
+        let mut client = pool.get()?;
+
+        let rows = client
+            .query(
+                "SELECT datname FROM pg_database WHERE datistemplate = false",
+                &[],
+            )
+            .map_err(|e| e.to_string())?;
 
-    let databases = rows.iter().map(|row| row.get(0)).collect();
+        let databases = rows.iter().map(|row| row.get(0)).collect();
 
-    Ok(databases)
+        Ok(databases)
+    })
+    .await
 }
 
 #[tauri::command]
-pub fn pg_select_database(
+pub async fn pg_select_database(
     databasename: DatabaseName,
     state: State<'_, DbConnection>,
 ) -> Result<DatabaseObjects, String> {
-    let mut conn_string_lock = state.connection_string.lock().map_err(|e| e.to_string())?;
-    let conn_string = conn_string_lock
-        .as_mut()
+    let conn_string = state
+        .pool
+        .connection_string()?
         .ok_or("No connection string available")?;
 
-    println!("{}", conn_string);
-
     // Update the connection string with the new database name
     let new_conn_string = if conn_string.matches('/').count() == 3 {
         // If the connection string already has a path (which might be a database name),
@@ -103,25 +300,27 @@ pub fn pg_select_database(
         format!("{}/{}", conn_string, databasename.name)
     };
 
-    println!("New connection string: {}", new_conn_string);
-
-    // Create a new client with the updated connection string
-    let new_client = Client::connect(&new_conn_string, NoTls).map_err(|e| e.to_string())?;
-
-    // Update the client
-    let mut client_lock = state.client.lock().map_err(|e| e.to_string())?;
-    *client_lock = Some(new_client);
+    // Create a new client with the updated connection string and load the
+    // database objects with it before it joins the pool
+    let connect_string = new_conn_string.clone();
+    let (new_client, objects) = run_blocking(move || {
+        let mut client = Client::connect(&connect_string, NoTls).map_err(|e| e.to_string())?;
+        let objects = load_database_objects(&mut client)?;
+        Ok((client, objects))
+    })
+    .await?;
 
-    // Update the connection string
-    *conn_string = new_conn_string;
+    // Update the client and connection string
+    state.pool.reset(new_conn_string, new_client)?;
 
     // Update the selected database
     let mut selected_db = state.selected_db.lock().map_err(|e| e.to_string())?;
     *selected_db = Some(databasename.name.clone());
 
-    // Get database objects using the new client
-    let client = client_lock.as_mut().unwrap();
+    Ok(objects)
+}
 
+fn load_database_objects(client: &mut Client) -> Result<DatabaseObjects, String> {
     // Get all tables
     let tables: Vec<String> = client
         .query(
@@ -175,72 +374,141 @@ pub fn pg_select_database(
 }
 
 #[tauri::command]
-pub fn pg_execute_query(query: String, state: State<'_, DbConnection>) -> Result<String, String> {
-    let mut client = state.get_client()?;
-    let client = client.as_mut().ok_or("Database not connected")?;
+pub async fn pg_execute_query(
+    query: String,
+    state: State<'_, DbConnection>,
+    history: State<'_, HistoryStore>,
+) -> Result<QueryResponse, String> {
+    let pool = state.session()?;
+    let execution = Execution {
+        engine: Engine::Postgres,
+        connection: state.label()?,
+        database: state.selected_db()?,
+        query: &query,
+    };
+    let started = SystemTime::now();
 
-    let selected_db = state.selected_db.lock().map_err(|e| e.to_string())?;
-    if selected_db.is_none() {
-        return Err("No database selected. Please select a database first.".to_string());
-    }
+    let statement = query.clone();
+    let result = run_blocking(move || {
+        let mut client = pool.pinned()?;
+        run_query(&mut client, &statement)
+    })
+    .await;
+
+    history.record(execution, started, &result);
+    result
+}
+
+fn run_query(client: &mut Client, query: &str) -> Result<QueryResponse, String> {
+    let mut stats = ExecutionStats::default();
+    let started = Instant::now();
 
     let query_type = query.trim().to_uppercase();
 
-    if query_type.starts_with(&"SELECT column_name, data_type, character_maximum_length, is_nullable FROM information_schema.columns".to_uppercase()) {
-        let rows = client.query(&query, &[]).map_err(|e| e.to_string())?;
+    let output = if query_type.starts_with(&"SELECT column_name, data_type, character_maximum_length, is_nullable FROM information_schema.columns".to_uppercase()) {
+        let rows = fetch_rows(client, query, started, &mut stats)?;
+        stats.wall_time_ms = millis(started.elapsed());
 
         if rows.is_empty() {
-            return Ok("No columns found for the specified table.".to_string());
-        }
+            "No columns found for the specified table.".to_string()
+        } else {
+            let mut output = String::from("Column Name | Data Type | Max Length | Nullable\n");
+            output.push_str("-----------|-----------|------------|----------\n");
+
+            for row in rows {
+                let column_name: &str = row.get(0);
+                let data_type: &str = row.get(1);
+                let max_length: Option<i32> = row.get(2);
+                let is_nullable: &str = row.get(3);
+
+                output.push_str(&format!(
+                    "{:<11}|{:<11}|{:<12}|{:<10}\n",
+                    column_name,
+                    data_type,
+                    max_length.map_or("NULL".to_string(), |v| v.to_string()),
+                    is_nullable
+                ));
+            }
 
-        let mut output = String::from("Column Name | Data Type | Max Length | Nullable\n");
-        output.push_str("-----------|-----------|------------|----------\n");
-
-        for row in rows {
-            let column_name: &str = row.get(0);
-            let data_type: &str = row.get(1);
-            let max_length: Option<i32> = row.get(2);
-            let is_nullable: &str = row.get(3);
-
-            output.push_str(&format!(
-                "{:<11}|{:<11}|{:<12}|{:<10}\n",
-                column_name,
-                data_type,
-                max_length.map_or("NULL".to_string(), |v| v.to_string()),
-                is_nullable
-            ));
+            output
         }
-
-        Ok(output)
     } else if query_type.starts_with("SELECT") || query_type.starts_with("SHOW") {
-        let rows = client.query(&query, &[]).map_err(|e| e.to_string())?;
+        let rows = fetch_rows(client, query, started, &mut stats)?;
+        stats.wall_time_ms = millis(started.elapsed());
 
         if rows.is_empty() {
-            return Ok("Query executed successfully. No rows returned.".to_string());
-        }
-
-        let mut output = String::new();
-
-        for row in &rows {
-            for (i, column) in row.columns().iter().enumerate() {
-                if i > 0 {
-                    output.push('\t');
+            "Query executed successfully. No rows returned.".to_string()
+        } else {
+            let mut output = String::new();
+
+            for row in &rows {
+                for (i, column) in row.columns().iter().enumerate() {
+                    if i > 0 {
+                        output.push('\t');
+                    }
+                    output.push_str(column.name());
+                    output.push_str(": ");
+                    output.push_str(&value_to_string(row, i));
                 }
-                output.push_str(column.name());
-                output.push_str(": ");
-                output.push_str(&value_to_string(row, i));
+                output.push('\n');
             }
-            output.push('\n');
-        }
 
-        Ok(output)
+            output
+        }
     } else {
-        let result = client.execute(&query, &[]);
+        let affected_rows = client
+            .execute(query, &[])
+            .map_err(|e| format!("Query execution error: {}", e))?;
+        stats.wall_time_ms = millis(started.elapsed());
+        stats.rows_affected = Some(affected_rows);
+        stats.command_tag = Some(command_tag(&query_type, affected_rows));
+
+        format!("Query executed successfully. Rows affected: {}", affected_rows)
+    };
+
+    Ok(QueryResponse { output, stats })
+}
+
+pub fn fetch_rows(
+    client: &mut Client,
+    query: &str,
+    started: Instant,
+    stats: &mut ExecutionStats,
+) -> Result<Vec<Row>, String> {
+    let mut rows = Vec::new();
+    let mut iter = client
+        .query_raw(query, std::iter::empty::<String>())
+        .map_err(|e| e.to_string())?;
+
+    while let Some(row) = iter.next().map_err(|e| e.to_string())? {
+        stats.mark_row(started);
+        rows.push(row);
+    }
+
+    let count = iter.rows_affected().unwrap_or(rows.len() as u64);
+    stats.command_tag = Some(command_tag(&query.trim().to_uppercase(), count));
+
+    Ok(rows)
+}
 
-        match result {
-            Ok(affected_rows) => Ok(format!("Query executed successfully. Rows affected: {}", affected_rows)),
-            Err(e) => Err(format!("Query execution error: {}", e)),
+// The driver only hands back the row count from CommandComplete, so the tag
+// is rebuilt from the statement keyword the same way the server formats it.
+fn command_tag(query_type: &str, count: u64) -> String {
+    let keyword = query_type
+        .split_whitespace()
+        .next()
+        .unwrap_or_default();
+    match keyword {
+        "INSERT" => format!("INSERT 0 {}", count),
+        "SELECT" | "UPDATE" | "DELETE" | "MERGE" | "FETCH" | "MOVE" | "COPY" => {
+            format!("{} {}", keyword, count)
         }
+        "CREATE" | "DROP" | "ALTER" => query_type
+            .split_whitespace()
+            .take(2)
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => keyword.to_string(),
     }
 }
 
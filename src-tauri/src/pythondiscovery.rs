@@ -0,0 +1,542 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::State;
+
+use crate::pythonmanager::{command, find_in_path};
+use crate::pythonrequirements::TorchVariant;
+
+const SETTINGS_FILE: &str = "python_settings.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PythonSettings {
+    // The interpreter picked by the user; when unset the first supported
+    // candidate found is used
+    interpreter: Option<String>,
+    // Inclusive bounds. A bound without a patch number covers every patch
+    // release, so a maximum of "3.12" allows 3.12.9.
+    min_version: String,
+    max_version: Option<String>,
+    // Which PyTorch build to install into the virtualenv
+    pub torch_variant: TorchVariant,
+    // Directory of pre-downloaded wheels. When set, packages are installed
+    // from it alone and nothing is fetched from the network.
+    pub wheelhouse: Option<String>,
+}
+
+impl Default for PythonSettings {
+    fn default() -> Self {
+        PythonSettings {
+            interpreter: None,
+            min_version: "3.10".to_string(),
+            max_version: Some("3.12".to_string()),
+            torch_variant: TorchVariant::Auto,
+            wheelhouse: None,
+        }
+    }
+}
+
+pub struct PythonSettingsStore {
+    dir: PathBuf,
+    settings: Mutex<PythonSettings>,
+}
+
+impl PythonSettingsStore {
+    pub fn load(dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let settings = fs::read_to_string(dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default();
+        Ok(PythonSettingsStore {
+            dir,
+            settings: Mutex::new(settings),
+        })
+    }
+
+    pub fn settings(&self) -> Result<PythonSettings, String> {
+        let settings = self.settings.lock().map_err(|e| e.to_string())?;
+        Ok(settings.clone())
+    }
+
+    fn save(&self, settings: PythonSettings) -> Result<(), String> {
+        let mut current = self.settings.lock().map_err(|e| e.to_string())?;
+        let json = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
+        fs::write(self.dir.join(SETTINGS_FILE), json).map_err(|e| e.to_string())?;
+        *current = settings;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PythonVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    // Pre-release tag such as `rc1` or `b2`
+    pub pre: Option<String>,
+}
+
+impl PythonVersion {
+    // Reads `Python 3.12.0rc1`, `3.10.11` or `3.13.0b1+` as printed by
+    // `python --version`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let token = text
+            .split_whitespace()
+            .find(|t| t.starts_with(|c: char| c.is_ascii_digit()))?;
+        let mut parts = token.splitn(3, '.');
+        let major = parts.next()?.parse().ok()?;
+        let (minor, minor_rest) = leading_number(parts.next()?)?;
+        let (patch, rest) = match parts.next() {
+            Some(patch) if minor_rest.is_empty() => leading_number(patch)?,
+            _ => (0, minor_rest),
+        };
+        // A trailing `+` marks a build from a development checkout
+        let rest = rest.trim_end_matches('+');
+        let pre = (!rest.is_empty()).then(|| rest.to_string());
+        Some(PythonVersion {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    }
+
+    fn numbers(&self) -> [u32; 3] {
+        [self.major, self.minor, self.patch]
+    }
+}
+
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+fn leading_number(text: &str) -> Option<(u32, &str)> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    Some((text[..end].parse().ok()?, &text[end..]))
+}
+
+// Numeric components of a bound such as `3.10` or `3.12.4`.
+fn bound_parts(bound: &str) -> Result<Vec<u32>, String> {
+    let parts: Result<Vec<u32>, _> = bound.trim().split('.').map(str::parse).collect();
+    match parts {
+        Ok(parts) if (1..=3).contains(&parts.len()) => Ok(parts),
+        _ => Err(format!("Invalid Python version bound: {}", bound)),
+    }
+}
+
+impl PythonSettings {
+    fn validate(&self) -> Result<(), String> {
+        let min = bound_parts(&self.min_version)?;
+        if let Some(max) = &self.max_version {
+            let max_parts = bound_parts(max)?;
+            // Only the components both bounds have are compared, as a
+            // maximum of 3.12 still allows a minimum of 3.12.4
+            let len = min.len().min(max_parts.len());
+            if min[..len] > max_parts[..len] {
+                return Err(format!(
+                    "The minimum Python version {} is above the maximum {}",
+                    self.min_version, max
+                ));
+            }
+        }
+        if let Some(wheelhouse) = &self.wheelhouse {
+            if !Path::new(wheelhouse).is_dir() {
+                return Err(format!("Wheelhouse directory not found: {}", wheelhouse));
+            }
+        }
+        Ok(())
+    }
+
+    // Components beyond a bound's own are ignored, so 3.12.9 is within a
+    // maximum of 3.12 and 3.10.0rc1 meets a minimum of 3.10.
+    pub fn allows(&self, version: &PythonVersion) -> bool {
+        let numbers = version.numbers();
+        let meets = |bound: &str, ok: fn(&[u32], &[u32]) -> bool| {
+            bound_parts(bound).is_ok_and(|parts| ok(&numbers[..parts.len()], &parts))
+        };
+        let below_max = match &self.max_version {
+            Some(max) => meets(max, |v, b| v <= b),
+            None => true,
+        };
+        meets(&self.min_version, |v, b| v >= b) && below_max
+    }
+
+    pub fn describe_range(&self) -> String {
+        match &self.max_version {
+            Some(max) if *max == self.min_version => format!("Python {}", max),
+            Some(max) => format!("Python {} to {}", self.min_version, max),
+            None => format!("Python {} or newer", self.min_version),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CandidateSource {
+    Settings,
+    Path,
+    Launcher,
+    Pyenv,
+    Asdf,
+}
+
+#[derive(Serialize, Clone)]
+pub struct PythonCandidate {
+    pub path: String,
+    pub source: CandidateSource,
+    pub version: Option<String>,
+    // Within the configured version range
+    pub supported: bool,
+    pub selected: bool,
+    // Why the interpreter couldn't be run or its version read
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PythonDiscovery {
+    pub candidates: Vec<PythonCandidate>,
+    pub selected: Option<PythonCandidate>,
+    pub settings: PythonSettings,
+}
+
+// Runs `<path> --version`. Python 2 printed the version on stderr.
+pub fn interpreter_version(path: &Path) -> Result<PythonVersion, String> {
+    let output = command(path)
+        .arg("--version")
+        .output()
+        .map_err(|e| e.to_string())?;
+    let text = format!(
+        "{} {}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    if !output.status.success() {
+        return Err(format!("Failed to check Python version: {}", text.trim()));
+    }
+    PythonVersion::parse(&text)
+        .ok_or_else(|| format!("Failed to parse Python version: {}", text.trim()))
+}
+
+// Interpreter paths from every source, most specific first: the configured
+// one, PATH (which includes pyenv and asdf shims when they're set up), the
+// Windows `py` launcher, then versions installed by pyenv and asdf.
+fn candidate_paths(settings: &PythonSettings) -> Vec<(PathBuf, CandidateSource)> {
+    let mut paths = Vec::new();
+    if let Some(interpreter) = &settings.interpreter {
+        paths.push((PathBuf::from(interpreter), CandidateSource::Settings));
+    }
+
+    let names: &[&str] = if cfg!(windows) {
+        &["python", "python3"]
+    } else {
+        &["python3", "python"]
+    };
+    for name in names {
+        paths.extend(
+            find_in_path(name)
+                .into_iter()
+                .map(|p| (p, CandidateSource::Path)),
+        );
+    }
+    // Versioned names such as python3.11, which package managers install
+    // side by side
+    for minor in (6..=20).rev() {
+        paths.extend(
+            find_in_path(&format!("python3.{}", minor))
+                .into_iter()
+                .map(|p| (p, CandidateSource::Path)),
+        );
+    }
+
+    if cfg!(windows) {
+        paths.extend(
+            launcher_interpreters()
+                .into_iter()
+                .map(|p| (p, CandidateSource::Launcher)),
+        );
+    }
+
+    let home = env::var_os(if cfg!(windows) { "USERPROFILE" } else { "HOME" }).map(PathBuf::from);
+    let pyenv_root = env::var_os("PYENV_ROOT")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|h| h.join(".pyenv")));
+    if let Some(root) = pyenv_root {
+        // pyenv-win keeps its versions one level further down
+        for versions in [
+            root.join("versions"),
+            root.join("pyenv-win").join("versions"),
+        ] {
+            paths.extend(
+                installed_versions(&versions)
+                    .into_iter()
+                    .map(|p| (p, CandidateSource::Pyenv)),
+            );
+        }
+    }
+    let asdf_root = env::var_os("ASDF_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| home.as_ref().map(|h| h.join(".asdf")));
+    if let Some(root) = asdf_root {
+        paths.extend(
+            installed_versions(&root.join("installs").join("python"))
+                .into_iter()
+                .map(|p| (p, CandidateSource::Asdf)),
+        );
+    }
+    paths
+}
+
+// The interpreter of each version directory under `dir`, as laid out by
+// pyenv and asdf.
+fn installed_versions(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            if cfg!(windows) {
+                entry.path().join("python.exe")
+            } else {
+                entry.path().join("bin").join("python")
+            }
+        })
+        .filter(|path| path.is_file())
+        .collect();
+    versions.sort();
+    versions.reverse();
+    versions
+}
+
+// Interpreters registered with the Windows `py` launcher. `py -0p` prints
+// one per line as a tag, an optional `*` for the default, and the path.
+fn launcher_interpreters() -> Vec<PathBuf> {
+    let Ok(output) = command("py").arg("-0p").output() else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let (tag, rest) = line.split_once(char::is_whitespace)?;
+            if !tag.starts_with('-') {
+                return None;
+            }
+            let path = rest.trim_start().trim_start_matches('*').trim();
+            (!path.is_empty()).then(|| PathBuf::from(path))
+        })
+        .collect()
+}
+
+// Every interpreter found, each run once to read its version.
+pub fn discover(settings: &PythonSettings) -> PythonDiscovery {
+    let mut seen: Vec<PathBuf> = Vec::new();
+    let mut candidates: Vec<PythonCandidate> = Vec::new();
+
+    for (path, source) in candidate_paths(settings) {
+        // The same interpreter is often reachable through several links
+        let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+
+        let (version, error) = match interpreter_version(&path) {
+            Ok(version) => (Some(version), None),
+            Err(e) => (None, Some(e)),
+        };
+        candidates.push(PythonCandidate {
+            path: path.to_string_lossy().into_owned(),
+            source,
+            supported: version.as_ref().is_some_and(|v| settings.allows(v)),
+            version: version.map(|v| v.to_string()),
+            selected: false,
+            error,
+        });
+    }
+
+    // The configured interpreter wins when it is usable; otherwise the
+    // first supported one is used
+    let selected = candidates
+        .iter()
+        .position(|c| c.source == CandidateSource::Settings && c.supported)
+        .or_else(|| candidates.iter().position(|c| c.supported));
+    if let Some(i) = selected {
+        candidates[i].selected = true;
+    }
+
+    PythonDiscovery {
+        selected: selected.map(|i| candidates[i].clone()),
+        candidates,
+        settings: settings.clone(),
+    }
+}
+
+// The interpreter to create the virtualenv with, and its version.
+pub fn resolve_python(settings: &PythonSettings) -> Result<(PathBuf, PythonVersion), String> {
+    let discovery = discover(settings);
+    let Some(selected) = discovery.selected else {
+        let found: Vec<String> = discovery
+            .candidates
+            .iter()
+            .filter_map(|c| c.version.as_ref().map(|v| format!("{} ({})", v, c.path)))
+            .collect();
+        return Err(if found.is_empty() {
+            "Python not found in PATH".to_string()
+        } else {
+            format!(
+                "{} is not installed. Found: {}",
+                settings.describe_range(),
+                found.join(", ")
+            )
+        });
+    };
+    let version = selected
+        .version
+        .as_deref()
+        .and_then(PythonVersion::parse)
+        .ok_or_else(|| format!("Failed to parse Python version of {}", selected.path))?;
+    Ok((PathBuf::from(selected.path), version))
+}
+
+#[tauri::command]
+pub async fn list_python_interpreters(
+    store: State<'_, PythonSettingsStore>,
+) -> Result<PythonDiscovery, String> {
+    let settings = store.settings()?;
+    tauri::async_runtime::spawn_blocking(move || discover(&settings))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_python_settings(
+    store: State<'_, PythonSettingsStore>,
+) -> Result<PythonSettings, String> {
+    store.settings()
+}
+
+#[tauri::command]
+pub fn set_python_settings(
+    settings: PythonSettings,
+    store: State<'_, PythonSettingsStore>,
+) -> Result<(), String> {
+    settings.validate()?;
+    store.save(settings)
+}
+
+// Checks the interpreter runs and is within the version range, then keeps
+// it as the one to use.
+#[tauri::command]
+pub fn select_python_interpreter(
+    path: String,
+    store: State<'_, PythonSettingsStore>,
+) -> Result<PythonCandidate, String> {
+    let mut settings = store.settings()?;
+    let version = interpreter_version(Path::new(&path))?;
+    if !settings.allows(&version) {
+        return Err(format!(
+            "Python {} is outside the supported range ({})",
+            version,
+            settings.describe_range()
+        ));
+    }
+
+    settings.interpreter = Some(path.clone());
+    store.save(settings)?;
+    Ok(PythonCandidate {
+        path,
+        source: CandidateSource::Settings,
+        version: Some(version.to_string()),
+        supported: true,
+        selected: true,
+        error: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u32, minor: u32, patch: u32, pre: Option<&str>) -> PythonVersion {
+        PythonVersion {
+            major,
+            minor,
+            patch,
+            pre: pre.map(str::to_string),
+        }
+    }
+
+    fn range(min: &str, max: Option<&str>) -> PythonSettings {
+        PythonSettings {
+            min_version: min.to_string(),
+            max_version: max.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn parses_versions() {
+        assert_eq!(
+            PythonVersion::parse("Python 3.10.11\n"),
+            Some(version(3, 10, 11, None))
+        );
+        assert_eq!(
+            PythonVersion::parse("Python 3.12.0rc1"),
+            Some(version(3, 12, 0, Some("rc1")))
+        );
+        assert_eq!(
+            PythonVersion::parse("3.13.0b1+"),
+            Some(version(3, 13, 0, Some("b1")))
+        );
+        assert_eq!(PythonVersion::parse("Python"), None);
+    }
+
+    // `interpreter_version` joins stdout and stderr; Python 2 only writes
+    // to stderr
+    #[test]
+    fn parses_python_2_from_stderr() {
+        assert_eq!(
+            PythonVersion::parse(" Python 2.7.18\n"),
+            Some(version(2, 7, 18, None))
+        );
+    }
+
+    #[test]
+    fn bounds_cover_every_patch_release() {
+        let settings = range("3.10", Some("3.12"));
+        assert!(settings.allows(&version(3, 10, 0, Some("rc1"))));
+        assert!(settings.allows(&version(3, 12, 9, None)));
+        assert!(!settings.allows(&version(3, 9, 18, None)));
+        assert!(!settings.allows(&version(3, 13, 0, None)));
+
+        let settings = range("3.10.4", Some("3.12.2"));
+        assert!(!settings.allows(&version(3, 10, 3, None)));
+        assert!(settings.allows(&version(3, 10, 4, None)));
+        assert!(!settings.allows(&version(3, 12, 3, None)));
+
+        assert!(range("3.10", None).allows(&version(4, 0, 0, None)));
+    }
+
+    #[test]
+    fn rejects_minimum_above_maximum() {
+        assert!(range("3.13", Some("3.12")).validate().is_err());
+        assert!(range("3.12.4", Some("3.12.2")).validate().is_err());
+        assert!(range("3.12.4", Some("3.12")).validate().is_ok());
+        assert!(range("3.12", Some("3.12")).validate().is_ok());
+        assert!(range("3.10", Some("x")).validate().is_err());
+    }
+}
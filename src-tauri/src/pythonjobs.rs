@@ -0,0 +1,523 @@
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::{async_runtime, AppHandle, Emitter, Manager, State};
+
+use crate::pythonmanager::terminate_process_tree;
+
+const CANCELLED: &str = "Cancelled";
+
+// stderr lines kept for the error message when a step fails
+const ERROR_TAIL: usize = 50;
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    CreateVenv,
+    UpdateRequirements,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Serialize, Clone)]
+pub struct JobStarted {
+    job_id: u64,
+    kind: JobKind,
+}
+
+#[derive(Serialize, Clone)]
+pub struct JobOutput {
+    job_id: u64,
+    stream: OutputStream,
+    line: String,
+}
+
+#[derive(Serialize, Clone)]
+pub struct JobProgress {
+    job_id: u64,
+    step: String,
+    // `collecting`, `downloading`, `installing` or `installed` while pip
+    // runs
+    phase: Option<&'static str>,
+    package: Option<String>,
+    // Bytes of the current download, when pip reports them
+    downloaded: Option<u64>,
+    total: Option<u64>,
+    percent: Option<u32>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct JobFinished {
+    job_id: u64,
+    kind: JobKind,
+    status: JobStatus,
+    message: String,
+}
+
+struct JobState {
+    id: u64,
+    cancelled: AtomicBool,
+    // The process of the step that is running, so cancelling can stop it
+    child: Mutex<Option<Child>>,
+}
+
+impl JobState {
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    fn cancel(&self) -> Result<(), String> {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.kill()
+    }
+
+    fn kill(&self) -> Result<(), String> {
+        let mut child = self.child.lock().map_err(|e| e.to_string())?;
+        if let Some(child) = child.as_mut() {
+            terminate_process_tree(child)?;
+        }
+        Ok(())
+    }
+}
+
+// Virtualenv jobs rewrite the same environment, so only one runs at a time.
+pub struct VenvJobs {
+    next_id: AtomicU64,
+    current: Mutex<Option<Arc<JobState>>>,
+}
+
+impl VenvJobs {
+    pub fn new() -> Self {
+        VenvJobs {
+            next_id: AtomicU64::new(1),
+            current: Mutex::new(None),
+        }
+    }
+
+    // Registers a job and announces it on `venv-job-started`.
+    pub fn start(&self, app: &AppHandle, kind: JobKind) -> Result<Job, String> {
+        let mut current = self.current.lock().map_err(|e| e.to_string())?;
+        if current.is_some() {
+            return Err("Another virtual environment job is already running".to_string());
+        }
+
+        let state = Arc::new(JobState {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            cancelled: AtomicBool::new(false),
+            child: Mutex::new(None),
+        });
+        *current = Some(state.clone());
+
+        let _ = app.emit(
+            "venv-job-started",
+            JobStarted {
+                job_id: state.id,
+                kind,
+            },
+        );
+        Ok(Job {
+            app: app.clone(),
+            kind,
+            state,
+            finished: false,
+        })
+    }
+
+    fn current(&self) -> Result<Option<Arc<JobState>>, String> {
+        Ok(self.current.lock().map_err(|e| e.to_string())?.clone())
+    }
+
+    // Cancels the running job, if any, and waits for its process to stop.
+    pub fn cancel(&self) -> Result<(), String> {
+        match self.current()? {
+            Some(state) => state.cancel(),
+            None => Ok(()),
+        }
+    }
+
+    fn clear(&self, id: u64) {
+        if let Ok(mut current) = self.current.lock() {
+            if current.as_ref().is_some_and(|job| job.id == id) {
+                *current = None;
+            }
+        }
+    }
+}
+
+// A running job. Each step is a process whose output is streamed on
+// `venv-job-output` and, for pip, parsed into `venv-job-progress`.
+pub struct Job {
+    app: AppHandle,
+    kind: JobKind,
+    state: Arc<JobState>,
+    finished: bool,
+}
+
+impl Job {
+    // Runs one step to completion. Fails with the step's stderr when it
+    // exits unsuccessfully, or with "Cancelled" when the job was cancelled.
+    pub fn run(&self, step: &str, mut command: Command) -> Result<(), String> {
+        if self.state.is_cancelled() {
+            return Err(CANCELLED.to_string());
+        }
+        self.progress(step, &PipEvent::default());
+
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Python block-buffers a piped stdout, which would hold lines
+            // back until the step ends
+            .env("PYTHONUNBUFFERED", "1");
+        // Its own group, so cancelling also stops anything pip spawns
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to start {}: {}", step, e))?;
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        *self.state.child.lock().map_err(|e| e.to_string())? = Some(child);
+        // Cancelled between the check above and the child being stored
+        if self.state.is_cancelled() {
+            self.state.kill()?;
+        }
+
+        let stderr_reader = stderr.map(|stderr| {
+            let app = self.app.clone();
+            let job_id = self.state.id;
+            thread::spawn(move || {
+                let mut tail: Vec<String> = Vec::new();
+                for_each_line(stderr, |line| {
+                    emit_output(&app, job_id, OutputStream::Stderr, &line);
+                    if tail.len() == ERROR_TAIL {
+                        tail.remove(0);
+                    }
+                    tail.push(line);
+                });
+                tail
+            })
+        });
+
+        if let Some(stdout) = stdout {
+            let mut parser = PipParser::default();
+            for_each_line(stdout, |line| match parser.line(&line) {
+                // Raw progress lines come many times a second; only the
+                // percentage changes are passed on
+                Some(event) if event.raw_progress => {
+                    if parser.take_percent_change() {
+                        self.progress(step, &event);
+                    }
+                }
+                Some(event) => {
+                    emit_output(&self.app, self.state.id, OutputStream::Stdout, &line);
+                    self.progress(step, &event);
+                }
+                None => emit_output(&self.app, self.state.id, OutputStream::Stdout, &line),
+            });
+        }
+        let errors = stderr_reader
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+
+        // Polled rather than waited on, so the lock stays free for a cancel
+        let status = loop {
+            {
+                let mut child = self.state.child.lock().map_err(|e| e.to_string())?;
+                let Some(running) = child.as_mut() else {
+                    return Err(format!("{} was not started", step));
+                };
+                if let Some(status) = running.try_wait().map_err(|e| e.to_string())? {
+                    *child = None;
+                    break status;
+                }
+            }
+            thread::sleep(Duration::from_millis(100));
+        };
+
+        if self.state.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else if status.success() {
+            Ok(())
+        } else if errors.is_empty() {
+            Err(format!("{} exited with {}", step, status))
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    // Announces the outcome on `venv-job-finished`; dropping the job frees
+    // the slot for the next one. Cancellation wins over whatever error it
+    // caused.
+    pub fn finish(mut self, result: Result<String, String>) -> Result<String, String> {
+        let result = if self.state.is_cancelled() {
+            Err(CANCELLED.to_string())
+        } else {
+            result
+        };
+        let (status, message) = match &result {
+            Ok(message) => (JobStatus::Succeeded, message.clone()),
+            Err(e) if self.state.is_cancelled() => (JobStatus::Cancelled, e.clone()),
+            Err(e) => (JobStatus::Failed, e.clone()),
+        };
+        self.emit_finished(status, message);
+        result
+    }
+
+    fn emit_finished(&mut self, status: JobStatus, message: String) {
+        self.finished = true;
+        let _ = self.app.emit(
+            "venv-job-finished",
+            JobFinished {
+                job_id: self.state.id,
+                kind: self.kind,
+                status,
+                message,
+            },
+        );
+    }
+
+    fn progress(&self, step: &str, event: &PipEvent) {
+        let percent = match (event.downloaded, event.total) {
+            (Some(downloaded), Some(total)) if total > 0 => Some((downloaded * 100 / total) as u32),
+            _ => None,
+        };
+        let _ = self.app.emit(
+            "venv-job-progress",
+            JobProgress {
+                job_id: self.state.id,
+                step: step.to_string(),
+                phase: event.phase,
+                package: event.package.clone(),
+                downloaded: event.downloaded,
+                total: event.total,
+                percent,
+            },
+        );
+    }
+}
+
+// Frees the slot however the job ends, so a panic in a step can't leave
+// every later job refused. A job dropped without `finish` has its process
+// stopped and is reported as failed.
+impl Drop for Job {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.state.kill();
+            self.emit_finished(
+                JobStatus::Failed,
+                "The job stopped unexpectedly".to_string(),
+            );
+        }
+        self.app.state::<VenvJobs>().clear(self.state.id);
+    }
+}
+
+fn emit_output(app: &AppHandle, job_id: u64, stream: OutputStream, line: &str) {
+    let _ = app.emit(
+        "venv-job-output",
+        JobOutput {
+            job_id,
+            stream,
+            line: line.to_string(),
+        },
+    );
+}
+
+// Calls `f` with each line of `reader`, decoded lossily and without its line
+// ending.
+pub fn for_each_line(reader: impl Read, mut f: impl FnMut(String)) {
+    for line in BufReader::new(reader).split(b'\n') {
+        let Ok(line) = line else {
+            break;
+        };
+        let line = String::from_utf8_lossy(&line);
+        let line = line.trim_end_matches('\r');
+        if !line.trim().is_empty() {
+            f(line.to_string());
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+struct PipEvent {
+    phase: Option<&'static str>,
+    package: Option<String>,
+    downloaded: Option<u64>,
+    total: Option<u64>,
+    raw_progress: bool,
+}
+
+// Follows pip's output: which package it's on and, with
+// `--progress-bar raw`, how far the current download is.
+#[derive(Default)]
+struct PipParser {
+    package: Option<String>,
+    percent: Option<u32>,
+    reported_percent: Option<u32>,
+}
+
+impl PipParser {
+    fn line(&mut self, line: &str) -> Option<PipEvent> {
+        let line = line.trim();
+        if let Some(rest) = line.strip_prefix("Progress ") {
+            // `Progress 1048576 of 201326592`
+            let (downloaded, total) = rest.split_once(" of ")?;
+            let downloaded: u64 = downloaded.trim().parse().ok()?;
+            let total: u64 = total.trim().parse().ok()?;
+            self.percent = (total > 0).then(|| (downloaded * 100 / total) as u32);
+            return Some(PipEvent {
+                phase: Some("downloading"),
+                package: self.package.clone(),
+                downloaded: Some(downloaded),
+                total: Some(total),
+                raw_progress: true,
+            });
+        }
+
+        let (phase, package) = if let Some(rest) = line.strip_prefix("Collecting ") {
+            (
+                "collecting",
+                rest.split_whitespace().next().map(str::to_string),
+            )
+        } else if let Some(rest) = line.strip_prefix("Downloading ") {
+            // A URL or file name, followed by the size in brackets
+            let file = rest.split_whitespace().next().unwrap_or(rest);
+            let file = file.rsplit('/').next().unwrap_or(file).replace("%2B", "+");
+            ("downloading", Some(file))
+        } else if line.starts_with("Installing collected packages:") {
+            ("installing", None)
+        } else if line.starts_with("Successfully installed") {
+            ("installed", None)
+        } else {
+            return None;
+        };
+
+        if package.is_some() {
+            self.package = package.clone();
+            self.percent = None;
+            self.reported_percent = None;
+        }
+        Some(PipEvent {
+            phase: Some(phase),
+            package,
+            ..PipEvent::default()
+        })
+    }
+
+    fn take_percent_change(&mut self) -> bool {
+        let changed = self.percent != self.reported_percent;
+        self.reported_percent = self.percent;
+        changed
+    }
+}
+
+// Stops the running virtualenv job. The command it was started from fails
+// with "Cancelled".
+#[tauri::command]
+pub async fn cancel_venv_job(jobs: State<'_, VenvJobs>) -> Result<String, String> {
+    let state = jobs
+        .current()?
+        .ok_or_else(|| "No virtual environment job is running".to_string())?;
+    async_runtime::spawn_blocking(move || state.cancel())
+        .await
+        .map_err(|e| e.to_string())??;
+    Ok("Virtual environment job cancelled".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_pip_packages() {
+        let mut parser = PipParser::default();
+
+        let event = parser.line("Collecting torch==2.5.1+cpu").unwrap();
+        assert_eq!(event.phase, Some("collecting"));
+        assert_eq!(event.package.as_deref(), Some("torch==2.5.1+cpu"));
+        assert!(!event.raw_progress);
+
+        let event = parser
+            .line("  Downloading https://download.pytorch.org/whl/cpu/torch-2.5.1%2Bcpu-cp312-cp312-linux_x86_64.whl (174.7 MB)")
+            .unwrap();
+        assert_eq!(event.phase, Some("downloading"));
+        assert_eq!(
+            event.package.as_deref(),
+            Some("torch-2.5.1+cpu-cp312-cp312-linux_x86_64.whl")
+        );
+
+        let event = parser
+            .line("Downloading numpy-1.26.4-cp312-cp312-manylinux_2_17_x86_64.whl (18.0 MB)")
+            .unwrap();
+        assert_eq!(
+            event.package.as_deref(),
+            Some("numpy-1.26.4-cp312-cp312-manylinux_2_17_x86_64.whl")
+        );
+
+        let event = parser
+            .line("Installing collected packages: numpy, torch")
+            .unwrap();
+        assert_eq!(event.phase, Some("installing"));
+        assert_eq!(event.package, None);
+        let event = parser
+            .line("Successfully installed numpy-1.26.4 torch-2.5.1+cpu")
+            .unwrap();
+        assert_eq!(event.phase, Some("installed"));
+
+        assert!(parser
+            .line("Requirement already satisfied: filelock in ./venv/lib")
+            .is_none());
+    }
+
+    #[test]
+    fn reads_raw_download_progress() {
+        let mut parser = PipParser::default();
+        parser.line("Downloading torch-2.5.1%2Bcpu-cp312-cp312-win_amd64.whl (200 MB)");
+
+        let event = parser.line("Progress 1048576 of 201326592").unwrap();
+        assert!(event.raw_progress);
+        assert_eq!(event.phase, Some("downloading"));
+        assert_eq!(
+            event.package.as_deref(),
+            Some("torch-2.5.1+cpu-cp312-cp312-win_amd64.whl")
+        );
+        assert_eq!(event.downloaded, Some(1048576));
+        assert_eq!(event.total, Some(201326592));
+        assert!(parser.take_percent_change());
+
+        // Still 0%, so nothing new to report
+        parser.line("Progress 1572864 of 201326592").unwrap();
+        assert!(!parser.take_percent_change());
+        parser.line("Progress 100663296 of 201326592").unwrap();
+        assert!(parser.take_percent_change());
+
+        // A new download starts counting again
+        parser.line("Downloading numpy-1.26.4-cp312-cp312-win_amd64.whl (15.8 MB)");
+        parser.line("Progress 0 of 15800000").unwrap();
+        assert!(parser.take_percent_change());
+
+        assert!(parser.line("Progress unknown").is_none());
+        assert!(parser.line("Progress 5 of many").is_none());
+    }
+}
@@ -1,87 +1,157 @@
+use std::env;
+use std::ffi::OsStr;
 use std::fs;
-use std::os::windows::process::CommandExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Child;
 use std::process::Command;
-use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
-use tauri::async_runtime;
-
-fn find_python() -> Option<String> {
-    let output = Command::new("where").arg("python").output().ok()?;
-    if output.status.success() {
-        String::from_utf8(output.stdout)
-            .ok()?
-            .lines()
-            .next()
-            .map(str::to_string)
-    } else {
-        None
+use std::time::{Duration, Instant};
+use tauri::{async_runtime, AppHandle, State};
+
+use crate::pythondiscovery::{resolve_python, PythonSettings, PythonSettingsStore};
+use crate::pythonjobs::{Job, JobKind, VenvJobs};
+use crate::pythonrequirements::{install_requirements, sync_requirements, InstallOptions};
+
+pub const VENV_NAME: &str = "sqlmantauri";
+
+// How long a stopped sidecar gets to exit before it is killed
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[cfg(windows)]
+const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+// A command that doesn't flash a console window on Windows.
+pub fn command<S: AsRef<OsStr>>(program: S) -> Command {
+    #[allow(unused_mut)]
+    let mut command = Command::new(program);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(CREATE_NO_WINDOW);
     }
+    command
 }
 
-fn parse_python_version(version_string: &str) -> Option<(u32, u32, u32)> {
-    let parts: Vec<&str> = version_string
-        .split_whitespace()
-        .nth(1)?
-        .split('.')
+// Every match for `name` on PATH, in PATH order. On Windows each PATHEXT
+// extension is tried as well.
+pub fn find_in_path(name: &str) -> Vec<PathBuf> {
+    let Some(path) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    #[cfg(windows)]
+    let extensions: Vec<String> = env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(str::to_lowercase)
         .collect();
-    if parts.len() >= 3 {
-        Some((
-            parts[0].parse().ok()?,
-            parts[1].parse().ok()?,
-            parts[2].parse().ok()?,
-        ))
-    } else {
-        None
+    #[cfg(not(windows))]
+    let extensions: Vec<String> = Vec::new();
+
+    let mut found = Vec::new();
+    for dir in env::split_paths(&path) {
+        let candidates = std::iter::once(dir.join(name)).chain(
+            extensions
+                .iter()
+                .map(|ext| dir.join(format!("{}{}", name, ext))),
+        );
+        for candidate in candidates {
+            if is_executable(&candidate) && !found.contains(&candidate) {
+                found.push(candidate);
+            }
+        }
     }
+    found
 }
 
-const CREATE_NO_WINDOW: u32 = 0x08000000;
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
 
-#[tauri::command]
-pub fn check_python_installation() -> Result<String, String> {
-    let python_path = find_python().ok_or_else(|| "Python not found in PATH".to_string())?;
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
 
-    let output = Command::new(python_path)
-        .args(&["--version"])
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()
-        .map_err(|e| e.to_string())?;
+// Where the virtualenv lives: the user profile on Windows, as it always
+// has, and the per-user data directory elsewhere.
+#[cfg(windows)]
+pub fn venv_dir() -> Result<PathBuf, String> {
+    let home_dir = env::var("USERPROFILE").map_err(|e| e.to_string())?;
+    Ok(Path::new(&home_dir).join(VENV_NAME))
+}
 
-    if output.status.success() {
-        let version_string = String::from_utf8_lossy(&output.stdout);
-        if let Some((major, minor, _)) = parse_python_version(&version_string) {
-            if major == 3 && minor == 10 {
-                Ok(format!(
-                    "Python 3.10.* is installed. ({})",
-                    version_string.trim()
-                ))
-            } else {
-                Err(format!(
-                    "Python 3.10.* is not installed. Found: {}",
-                    version_string.trim()
-                ))
-            }
-        } else {
-            Err(format!(
-                "Failed to parse Python version: {}",
-                version_string.trim()
-            ))
-        }
+#[cfg(not(windows))]
+pub fn venv_dir() -> Result<PathBuf, String> {
+    let home = env::var_os("HOME")
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+        .ok_or_else(|| "HOME is not set".to_string())?;
+    let data_dir = if cfg!(target_os = "macos") {
+        home.join("Library").join("Application Support")
     } else {
-        Err("Failed to check Python version.".to_string())
+        // XDG_DATA_HOME only counts when it is an absolute path
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .filter(|dir| dir.is_absolute())
+            .unwrap_or_else(|| home.join(".local").join("share"))
+    };
+    Ok(data_dir.join(VENV_NAME))
+}
+
+// The bundled `scripts` directory next to the executable.
+pub fn scripts_dir() -> Result<PathBuf, String> {
+    let current_exe = env::current_exe().map_err(|e| e.to_string())?;
+    let app_dir = current_exe.parent().ok_or("Failed to get app directory")?;
+    Ok(app_dir.join("scripts"))
+}
+
+// `Scripts` on Windows, `bin` elsewhere.
+fn venv_bin(venv_path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv_path.join("Scripts")
+    } else {
+        venv_path.join("bin")
     }
 }
 
+pub fn venv_python(venv_path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        venv_bin(venv_path).join("python.exe")
+    } else {
+        venv_bin(venv_path).join("python")
+    }
+}
+
+#[tauri::command]
+pub async fn check_python_installation(
+    store: State<'_, PythonSettingsStore>,
+) -> Result<String, String> {
+    let settings = store.settings()?;
+    let (python_path, version) = async_runtime::spawn_blocking(move || resolve_python(&settings))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    Ok(format!(
+        "Python {} is installed. ({})",
+        version,
+        python_path.display()
+    ))
+}
+
+// winget on Windows and Homebrew on macOS. Linux distributions package
+// Python themselves and installing needs root, so that is left to the user.
 #[tauri::command]
 pub async fn install_python() -> Result<String, String> {
-    println!("Starting Python 3.10.11 installation using winget...");
+    println!("Starting Python 3.10 installation...");
 
     let installation_result = async_runtime::spawn(async move {
-        let output = Command::new("winget")
-            .args(&[
+        let mut installer = if cfg!(windows) {
+            let mut installer = command("winget");
+            installer.args([
                 "install",
                 "--id",
                 "9PJPW5LDXLZ5",
@@ -90,19 +160,30 @@ pub async fn install_python() -> Result<String, String> {
                 "--silent",
                 "--accept-package-agreements",
                 "--accept-source-agreements",
-            ])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
-            .map_err(|e| e.to_string())?;
-
+            ]);
+            installer
+        } else if cfg!(target_os = "macos") {
+            let brew = find_in_path("brew").into_iter().next().ok_or_else(|| {
+                "Homebrew was not found. Install Python 3.10 from python.org or Homebrew."
+                    .to_string()
+            })?;
+            let mut installer = command(brew);
+            installer.args(["install", "python@3.10"]);
+            installer
+        } else {
+            return Err(
+                "Install Python 3.10 and its venv module with your distribution's package \
+                 manager (e.g. `sudo apt install python3.10 python3.10-venv`)."
+                    .to_string(),
+            );
+        };
+
+        let output = installer.output().map_err(|e| e.to_string())?;
         if output.status.success() {
-            Ok("Python 3.10.11 installation process completed.".to_string())
+            Ok("Python 3.10 installation process completed.".to_string())
         } else {
             let error_message = String::from_utf8_lossy(&output.stderr);
-            Err(format!(
-                "Failed to install Python 3.10.11: {}",
-                error_message
-            ))
+            Err(format!("Failed to install Python 3.10: {}", error_message))
         }
     })
     .await;
@@ -114,208 +195,202 @@ pub async fn install_python() -> Result<String, String> {
 }
 
 #[tauri::command]
-pub async fn get_installation_status() -> Result<String, String> {
-    let output = Command::new("where")
-        .arg("python")
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()
+pub async fn get_installation_status(
+    store: State<'_, PythonSettingsStore>,
+) -> Result<String, String> {
+    let settings = store.settings()?;
+    let range = settings.describe_range();
+    let resolved = async_runtime::spawn_blocking(move || resolve_python(&settings))
+        .await
         .map_err(|e| e.to_string())?;
 
-    if output.status.success() {
-        let python_path = String::from_utf8_lossy(&output.stdout);
-        if python_path.contains("Python310") {
-            Ok("Python 3.10 is installed and in PATH.".to_string())
-        } else {
-            Ok("Python is installed, but version 3.10 is not in PATH. A system restart may be required.".to_string())
-        }
-    } else {
-        Ok(
-            "Python 3.10 installation not detected in PATH. A system restart may be required."
-                .to_string(),
-        )
+    match resolved {
+        Ok((_, version)) => Ok(format!("Python {} is installed and in PATH.", version)),
+        Err(_) => Ok(format!(
+            "{} installation not detected in PATH. A system restart may be required.",
+            range
+        )),
     }
 }
 
+// pip's version as (major, minor), e.g. (24, 2).
+fn pip_version(venv_path: &Path) -> Option<(u32, u32)> {
+    let output = command(venv_python(venv_path))
+        .args(["-m", "pip", "--version"])
+        .output()
+        .ok()?;
+    // `pip 24.2 from /path/to/site-packages/pip (python 3.12)`
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut parts = text.split_whitespace().nth(1)?.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts
+        .next()
+        .and_then(|minor| minor.parse().ok())
+        .unwrap_or(0);
+    Some((major, minor))
+}
+
+// Runs pip with the virtualenv's own interpreter, which needs no activation
+// script or shell, as a step of `job`.
+pub fn pip_install(job: &Job, venv_path: &Path, args: &[&str]) -> Result<(), String> {
+    let mut pip = command(venv_python(venv_path));
+    pip.args(["-m", "pip", "install"]);
+    // Line-by-line download progress the job can parse, from pip 24.1
+    if pip_version(venv_path).is_some_and(|version| version >= (24, 1)) {
+        pip.args(["--progress-bar", "raw"]);
+    }
+    pip.args(args);
+    job.run("Installing packages", pip)
+}
+
+// Creates the virtualenv and installs the requirements as a background job
+// whose output and progress are streamed as events. It can be stopped with
+// `cancel_venv_job`, which leaves no half-built environment behind.
 #[tauri::command]
-pub async fn manage_venv() -> Result<String, String> {
-    let venv_name = "sqlmantauri";
-    let home_dir = std::env::var("USERPROFILE").map_err(|e| e.to_string())?;
-    let venv_path = Path::new(&home_dir).join(venv_name);
+pub async fn manage_venv(
+    app: AppHandle,
+    store: State<'_, PythonSettingsStore>,
+    jobs: State<'_, VenvJobs>,
+) -> Result<String, String> {
+    let venv_path = venv_dir()?;
 
     println!("Creating virtual environment '{}'...", venv_path.display());
 
     if venv_path.exists() {
-        Ok(format!(
+        return Ok(format!(
             "Virtual environment '{}' already exists.",
-            venv_name
-        ))
-    } else {
-        // Create the virtual environment
-        let output = Command::new("python")
-            .args(&["-m", "venv", venv_path.to_str().unwrap()])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
-            .map_err(|e| e.to_string())?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to create virtual environment: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
+            VENV_NAME
+        ));
+    }
 
-        // Activate the virtual environment and install any required packages
-        let activate_script = venv_path.join("Scripts").join("activate.bat");
-        let output = Command::new("cmd")
-            .args(&[
-                "/C",
-                activate_script.to_str().unwrap(),
-                "&&",
-                "pip",
-                "install",
-                "torch",
-                "torchvision",
-                "torchaudio",
-                "--index-url",
-                "https://download.pytorch.org/whl/cu124",
-                "&&",
-                "pip",
-                "install",
-                "aiohttp==3.10.0",
-                "aiofiles==24.1.0",
-                "huggingface_hub==0.24.3",
-                "ctransformers==0.2.27",
-                "psutil==5.9.6",
-                "fastapi==0.111.1",
-                "uvicorn==0.30.3",
-                "pydantic==2.8.2",
-                "numpy==1.26.4",
-            ])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
-            .map_err(|e| e.to_string())?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to install required packages: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
+    let settings = store.settings()?;
+    let job = jobs.start(&app, JobKind::CreateVenv)?;
+    async_runtime::spawn_blocking(move || {
+        let result = create_venv(&job, &venv_path, &settings);
+        if result.is_err() {
+            // Otherwise the next attempt would find it and report success
+            let _ = fs::remove_dir_all(&venv_path);
         }
+        job.finish(result)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-        Ok(format!(
-            "Virtual environment '{}' created successfully and packages installed.",
-            venv_name
-        ))
+fn create_venv(job: &Job, venv_path: &Path, settings: &PythonSettings) -> Result<String, String> {
+    let (python_path, _) = resolve_python(settings)?;
+    if let Some(parent) = venv_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
     }
+
+    let mut venv = command(python_path);
+    venv.arg("-m").arg("venv").arg(venv_path);
+    job.run("Creating virtual environment", venv)
+        .map_err(|e| format!("Failed to create virtual environment: {}", e))?;
+
+    // Install the required packages
+    install_requirements(job, venv_path, &InstallOptions::from_settings(settings))
+        .map_err(|e| format!("Failed to install required packages: {}", e))?;
+
+    Ok(format!(
+        "Virtual environment '{}' created successfully and packages installed.",
+        VENV_NAME
+    ))
 }
 
 #[tauri::command]
 pub fn activate_venv() -> Result<String, String> {
-    let venv_name = "sqlmantauri";
-    let home_dir = std::env::var("USERPROFILE").map_err(|e| e.to_string())?;
-    let venv_path = Path::new(&home_dir).join(venv_name);
-    let activate_script = venv_path.join("Scripts").join("activate");
+    let venv_path = venv_dir()?;
+    let bin_dir = venv_bin(&venv_path);
 
-    if !activate_script.exists() {
+    if !bin_dir.join("activate").exists() {
         return Err(format!(
             "Virtual environment '{}' not found or not properly set up.",
-            venv_name
+            VENV_NAME
         ));
     }
 
     // Set environment variables to activate the virtual environment
-    std::env::set_var("VIRTUAL_ENV", venv_path.to_str().unwrap());
+    env::set_var("VIRTUAL_ENV", &venv_path);
 
-    let new_path = format!(
-        "{}{}{}",
-        venv_path.join("Scripts").to_str().unwrap(),
-        std::path::MAIN_SEPARATOR,
-        std::env::var("PATH").unwrap()
-    );
-    std::env::set_var("PATH", new_path);
+    let current_path = env::var_os("PATH").unwrap_or_default();
+    let paths = std::iter::once(bin_dir).chain(env::split_paths(&current_path));
+    let new_path = env::join_paths(paths).map_err(|e| e.to_string())?;
+    env::set_var("PATH", new_path);
 
     // Remove PYTHONHOME if it's set
-    std::env::remove_var("PYTHONHOME");
+    env::remove_var("PYTHONHOME");
 
-    Ok(format!("Virtual environment '{}' activated.", venv_name))
+    Ok(format!("Virtual environment '{}' activated.", VENV_NAME))
 }
 
-struct PythonProcess {
-    child: Child,
-}
+// Asks the process tree to exit, then kills it if it is still running after
+// STOP_TIMEOUT. Returns whether it exited on its own.
+#[cfg(windows)]
+pub fn terminate_process_tree(child: &mut Child) -> Result<bool, String> {
+    let pid = child.id().to_string();
+    // Without /F taskkill only asks the processes to close; it fails for
+    // those that can't be asked, which are then killed below
+    let _ = command("taskkill").args(["/T", "/PID", &pid]).output();
+    if wait_for_exit(child, STOP_TIMEOUT)? {
+        return Ok(true);
+    }
 
-lazy_static::lazy_static! {
-    static ref PYTHON_PROCESS: Arc<Mutex<Option<PythonProcess>>> = Arc::new(Mutex::new(None));
+    let output = command("taskkill")
+        .args(["/F", "/T", "/PID", &pid])
+        .output()
+        .map_err(|e| format!("Failed to kill Python process: {}", e))?;
+    // It may have exited between the wait and taskkill
+    if !output.status.success() && !wait_for_exit(child, Duration::ZERO)? {
+        return Err(format!(
+            "Failed to kill Python process: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    child
+        .wait()
+        .map_err(|e| format!("Failed to force kill Python process: {}", e))?;
+    Ok(false)
 }
 
-#[tauri::command]
-pub fn start_python_script() -> Result<String, String> {
-    let mut process = PYTHON_PROCESS.lock().map_err(|e| e.to_string())?;
-    if process.is_some() {
-        return Err("Python process is already running".to_string());
+#[cfg(unix)]
+pub fn terminate_process_tree(child: &mut Child) -> Result<bool, String> {
+    // The child leads its own group, so its id is the group id
+    let group = child.id() as libc::pid_t;
+    if unsafe { libc::killpg(group, libc::SIGTERM) } != 0 {
+        let error = std::io::Error::last_os_error();
+        // ESRCH: the group is already gone
+        if error.raw_os_error() != Some(libc::ESRCH) {
+            return Err(format!("Failed to stop Python process: {}", error));
+        }
+    }
+    if wait_for_exit(child, STOP_TIMEOUT)? {
+        return Ok(true);
     }
 
-    let home_dir = std::env::var("USERPROFILE").map_err(|e| e.to_string())?;
-    let venv_path = Path::new(&home_dir).join("sqlmantauri");
-    let python_executable = venv_path.join("Scripts").join("python.exe");
-
-    let current_exe = std::env::current_exe().map_err(|e| e.to_string())?;
-    let app_dir = current_exe.parent().ok_or("Failed to get app directory")?;
-    let script_path = app_dir.join("scripts").join("main.py");
-
-    let child = Command::new(python_executable)
-        .arg(script_path)
-        .creation_flags(CREATE_NO_WINDOW)
-        .spawn()
-        .map_err(|e| format!("Failed to start Python process: {}", e))?;
-
-    *process = Some(PythonProcess { child });
-    Ok("FastAPI SQLCoder service started successfully".to_string())
+    unsafe { libc::killpg(group, libc::SIGKILL) };
+    child
+        .wait()
+        .map_err(|e| format!("Failed to force kill Python process: {}", e))?;
+    Ok(false)
 }
 
-#[tauri::command]
-pub fn stop_python_script() -> Result<String, String> {
-    let mut process = PYTHON_PROCESS.lock().map_err(|e| e.to_string())?;
-    if let Some(mut p) = process.take() {
-        // On Windows, we need to kill the process tree
-        let output = Command::new("taskkill")
-            .args(&["/F", "/T", "/PID", &p.child.id().to_string()])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
-            .map_err(|e| format!("Failed to kill Python process: {}", e))?;
-
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to kill Python process: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ));
-        }
-
-        // Wait a bit to ensure the process is fully terminated
-        thread::sleep(Duration::from_secs(2));
-
-        // Check if the process has actually terminated
-        match p.child.try_wait() {
-            Ok(Some(_)) => Ok("FastAPI SQLCoder service stopped successfully".to_string()),
-            Ok(None) => {
-                // Force kill if it's still running
-                p.child
-                    .kill()
-                    .map_err(|e| format!("Failed to force kill Python process: {}", e))?;
-                Ok("FastAPI SQLCoder service forcefully terminated".to_string())
-            }
-            Err(e) => Err(format!("Error checking Python process status: {}", e)),
+// Whether the child exited within `timeout`.
+pub fn wait_for_exit(child: &mut Child, timeout: Duration) -> Result<bool, String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => return Ok(true),
+            Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(100)),
+            Ok(None) => return Ok(false),
+            Err(e) => return Err(format!("Error checking Python process status: {}", e)),
         }
-    } else {
-        Err("No Python process is running".to_string())
     }
 }
 
 #[tauri::command]
 pub fn delete_venv() -> Result<String, String> {
-    let venv_name = "sqlmantauri";
-    let home_dir = std::env::var("USERPROFILE").map_err(|e| e.to_string())?;
-    let venv_path = Path::new(&home_dir).join(venv_name);
+    let venv_path = venv_dir()?;
 
     if venv_path.exists() {
         // Attempt to remove the directory
@@ -323,69 +398,50 @@ pub fn delete_venv() -> Result<String, String> {
             .map_err(|e| format!("Failed to delete virtual environment: {}", e))?;
         Ok(format!(
             "Virtual environment '{}' deleted successfully.",
-            venv_name
+            VENV_NAME
         ))
     } else {
         Ok(format!(
             "Virtual environment '{}' does not exist.",
-            venv_name
+            VENV_NAME
         ))
     }
 }
 
+// Reinstalls drifted packages as a background job, like `manage_venv`.
 #[tauri::command]
-pub fn update_venv_requirements() -> Result<String, String> {
-    let venv_name = "sqlmantauri";
-    let home_dir = std::env::var("USERPROFILE").map_err(|e| e.to_string())?;
-    let venv_path = Path::new(&home_dir).join(venv_name);
-    let activate_script = venv_path.join("Scripts").join("activate.bat");
+pub async fn update_venv_requirements(
+    app: AppHandle,
+    store: State<'_, PythonSettingsStore>,
+    jobs: State<'_, VenvJobs>,
+) -> Result<String, String> {
+    let venv_path = venv_dir()?;
 
     if !venv_path.exists() {
         return Err(format!(
             "Virtual environment '{}' does not exist. Please create it first.",
-            venv_name
+            VENV_NAME
         ));
     }
 
-    // Activate the virtual environment and update packages
-    let output = Command::new("cmd")
-        .args(&[
-            "/C",
-            activate_script.to_str().unwrap(),
-            "&&",
-            "pip",
-            "install",
-            "torch",
-            "torchvision",
-            "torchaudio",
-            "--index-url",
-            "https://download.pytorch.org/whl/cu124",
-            "&&",
-            "pip",
-            "install",
-            "aiohttp==3.10.0",
-            "aiofiles==24.1.0",
-            "huggingface_hub==0.24.3",
-            "ctransformers==0.2.27",
-            "psutil==5.9.6",
-            "fastapi==0.111.1",
-            "uvicorn==0.30.3",
-            "pydantic==2.8.2",
-            "numpy==1.26.4",
-        ])
-        .creation_flags(CREATE_NO_WINDOW)
-        .output()
-        .map_err(|e| format!("Failed to update packages: {}", e))?;
-
-    if output.status.success() {
-        Ok(format!(
-            "Successfully updated packages in virtual environment '{}'.",
-            venv_name
-        ))
-    } else {
-        Err(format!(
-            "Failed to update packages: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ))
-    }
+    let settings = store.settings()?;
+    let job = jobs.start(&app, JobKind::UpdateRequirements)?;
+    async_runtime::spawn_blocking(move || {
+        let options = InstallOptions::from_settings(&settings);
+        let result = match sync_requirements(&job, &venv_path, &options) {
+            Ok(changed) if changed.is_empty() => Ok(format!(
+                "Packages in virtual environment '{}' are already up to date.",
+                VENV_NAME
+            )),
+            Ok(changed) => Ok(format!(
+                "Successfully updated {} package(s) in virtual environment '{}'.",
+                changed.len(),
+                VENV_NAME
+            )),
+            Err(e) => Err(format!("Failed to update packages: {}", e)),
+        };
+        job.finish(result)
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
@@ -0,0 +1,551 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{async_runtime, State};
+
+use crate::pythondiscovery::{PythonSettings, PythonSettingsStore};
+use crate::pythonjobs::Job;
+use crate::pythonmanager::{
+    command, find_in_path, pip_install, scripts_dir, venv_dir, venv_python, VENV_NAME,
+};
+
+const REQUIREMENTS_FILE: &str = "requirements.txt";
+
+const CPU_INDEX: &str = "https://download.pytorch.org/whl/cpu";
+const CUDA_INDEX: &str = "https://download.pytorch.org/whl/cu124";
+const CUDA_LABEL: &str = "cu124";
+
+// Packages whose build depends on the variant. Their pins get the variant's
+// local version label so pip replaces a build of the other kind.
+const TORCH_PACKAGES: &[&str] = &["torch", "torchvision", "torchaudio"];
+
+// Prints every distribution installed in the environment as a JSON object of
+// name to version. Asking importlib.metadata directly avoids parsing
+// `pip freeze`, which reports editable and URL installs differently.
+const LIST_INSTALLED: &str = "import json, importlib.metadata as m; \
+     print(json.dumps({d.metadata['Name']: d.version for d in m.distributions() if d.metadata['Name']}))";
+
+pub struct Requirement {
+    pub name: String,
+    pub version: String,
+}
+
+// The bundled requirements file: pinned packages plus the pip options
+// (index URLs and the like) they are installed with.
+pub struct Requirements {
+    pub path: PathBuf,
+    pub options: Vec<String>,
+    pub pins: Vec<Requirement>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TorchVariant {
+    // CUDA when an NVIDIA GPU is found, CPU otherwise
+    #[default]
+    Auto,
+    Cpu,
+    Cuda,
+}
+
+// Where packages come from for one install, resolved from the settings.
+pub struct InstallOptions {
+    // Cpu or Cuda, never Auto
+    pub variant: TorchVariant,
+    pub wheelhouse: Option<PathBuf>,
+}
+
+#[derive(Serialize)]
+pub struct TorchBuild {
+    pub configured: TorchVariant,
+    pub resolved: TorchVariant,
+    // Name of the NVIDIA GPU, if one was found
+    pub gpu: Option<String>,
+    pub wheelhouse: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RequirementDrift {
+    pub name: String,
+    pub required: String,
+    // None when the package isn't installed at all
+    pub installed: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct RequirementsStatus {
+    pub requirements_file: String,
+    pub up_to_date: bool,
+    pub drift: Vec<RequirementDrift>,
+}
+
+impl Requirements {
+    pub fn bundled() -> Result<Self, String> {
+        Self::load(scripts_dir()?.join(REQUIREMENTS_FILE))
+    }
+
+    pub fn load(path: PathBuf) -> Result<Self, String> {
+        let text = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+        let mut options = Vec::new();
+        let mut pins = Vec::new();
+        for (number, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('-') {
+                options.extend(line.split_whitespace().map(str::to_string));
+                continue;
+            }
+
+            let pin = line
+                .split_once("==")
+                .map(|(name, version)| (name.trim(), version.trim()))
+                .filter(|(name, version)| !name.is_empty() && !version.is_empty());
+            let Some((name, version)) = pin else {
+                return Err(format!(
+                    "{} line {}: requirement '{}' is not pinned with ==",
+                    path.display(),
+                    number + 1,
+                    line
+                ));
+            };
+            pins.push(Requirement {
+                name: name.to_string(),
+                version: version.to_string(),
+            });
+        }
+
+        Ok(Requirements {
+            path,
+            options,
+            pins,
+        })
+    }
+
+    // Pins whose installed version doesn't match, in file order.
+    pub fn drift(
+        &self,
+        installed: &HashMap<String, String>,
+        options: &InstallOptions,
+    ) -> Vec<RequirementDrift> {
+        self.pins
+            .iter()
+            .filter_map(|pin| {
+                let required = options.version(pin);
+                let current = installed.get(&normalize_name(&pin.name));
+                if current.is_some_and(|v| same_version(v, &required)) {
+                    return None;
+                }
+                Some(RequirementDrift {
+                    name: pin.name.clone(),
+                    required,
+                    installed: current.cloned(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl TorchVariant {
+    // Auto becomes Cuda or Cpu; `has_gpu` is only asked then, since it runs
+    // nvidia-smi.
+    fn resolve(self, has_gpu: impl FnOnce() -> bool) -> TorchVariant {
+        match self {
+            TorchVariant::Auto if has_gpu() => TorchVariant::Cuda,
+            TorchVariant::Auto => TorchVariant::Cpu,
+            variant => variant,
+        }
+    }
+}
+
+impl InstallOptions {
+    pub fn from_settings(settings: &PythonSettings) -> Self {
+        InstallOptions {
+            variant: settings
+                .torch_variant
+                .resolve(|| detect_cuda_gpu().is_some()),
+            wheelhouse: settings.wheelhouse.as_ref().map(PathBuf::from),
+        }
+    }
+
+    // PyTorch publishes macOS wheels on PyPI without a label, and they
+    // have no CUDA build.
+    fn local_label(&self) -> Option<&'static str> {
+        if cfg!(target_os = "macos") {
+            return None;
+        }
+        match self.variant {
+            TorchVariant::Cuda => Some(CUDA_LABEL),
+            _ => Some("cpu"),
+        }
+    }
+
+    fn version(&self, pin: &Requirement) -> String {
+        let is_torch = TORCH_PACKAGES
+            .iter()
+            .any(|p| normalize_name(p) == normalize_name(&pin.name));
+        match self.local_label() {
+            Some(label) if is_torch && !pin.version.contains('+') => {
+                format!("{}+{}", pin.version, label)
+            }
+            _ => pin.version.clone(),
+        }
+    }
+
+    // Pip options for where to find packages. A wheelhouse replaces every
+    // index, so nothing touches the network.
+    fn pip_args(&self) -> Vec<String> {
+        if let Some(wheelhouse) = &self.wheelhouse {
+            return vec![
+                "--no-index".to_string(),
+                "--find-links".to_string(),
+                wheelhouse.to_string_lossy().into_owned(),
+            ];
+        }
+        if cfg!(target_os = "macos") {
+            return Vec::new();
+        }
+        let index = match self.variant {
+            TorchVariant::Cuda => CUDA_INDEX,
+            _ => CPU_INDEX,
+        };
+        vec!["--extra-index-url".to_string(), index.to_string()]
+    }
+}
+
+// The name of the first NVIDIA GPU, as reported by nvidia-smi, which ships
+// with the driver.
+fn detect_cuda_gpu() -> Option<String> {
+    if cfg!(target_os = "macos") {
+        return None;
+    }
+    let nvidia_smi = find_in_path("nvidia-smi").into_iter().next()?;
+    let output = command(nvidia_smi)
+        .args(["--query-gpu=name", "--format=csv,noheader"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+// PEP 503 normalization: case-insensitive, with runs of `-`, `_` and `.`
+// treated as a single `-`.
+fn normalize_name(name: &str) -> String {
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if matches!(c, '-' | '_' | '.') {
+            if !normalized.ends_with('-') {
+                normalized.push('-');
+            }
+        } else {
+            normalized.push(c.to_ascii_lowercase());
+        }
+    }
+    normalized
+}
+
+// `==` semantics: a local label such as `+cu124` on the installed version
+// only matters when the requirement has one too, and trailing zero
+// components don't matter (1.0 == 1.0.0).
+fn same_version(installed: &str, required: &str) -> bool {
+    fn split(version: &str) -> (&str, Option<&str>) {
+        let (mut release, label) = match version.split_once('+') {
+            Some((release, label)) => (release, Some(label)),
+            None => (version, None),
+        };
+        while let Some(shorter) = release.strip_suffix(".0") {
+            release = shorter;
+        }
+        (release, label)
+    }
+    let (installed, installed_label) = split(installed);
+    let (required, required_label) = split(required);
+    installed == required && (required_label.is_none() || installed_label == required_label)
+}
+
+// Every distribution in the virtualenv, keyed by normalized name.
+pub fn installed_packages(venv_path: &Path) -> Result<HashMap<String, String>, String> {
+    let output = command(venv_python(venv_path))
+        .args(["-c", LIST_INSTALLED])
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !output.status.success() {
+        return Err(format!(
+            "Failed to list installed packages: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let packages: HashMap<String, String> =
+        serde_json::from_slice(&output.stdout).map_err(|e| e.to_string())?;
+    Ok(packages
+        .into_iter()
+        .map(|(name, version)| (normalize_name(&name), version))
+        .collect())
+}
+
+// Installs `specs` with the file's own options and those for the variant.
+fn install_specs(
+    job: &Job,
+    venv_path: &Path,
+    specs: Vec<String>,
+    requirements: &Requirements,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    let pip_args = options.pip_args();
+    let args: Vec<&str> = specs
+        .iter()
+        .chain(requirements.options.iter())
+        .chain(pip_args.iter())
+        .map(String::as_str)
+        .collect();
+    pip_install(job, venv_path, &args)
+}
+
+// Installs every pin into a fresh virtualenv.
+pub fn install_requirements(
+    job: &Job,
+    venv_path: &Path,
+    options: &InstallOptions,
+) -> Result<(), String> {
+    let requirements = Requirements::bundled()?;
+    // The pip bundled with older Pythons can't report download progress;
+    // a wheelhouse without a newer pip leaves it as it is
+    let pip_args = options.pip_args();
+    let args: Vec<&str> = ["--upgrade", "pip"]
+        .into_iter()
+        .chain(pip_args.iter().map(String::as_str))
+        .collect();
+    pip_install(job, venv_path, &args)?;
+
+    let specs = requirements
+        .pins
+        .iter()
+        .map(|pin| format!("{}=={}", pin.name, options.version(pin)))
+        .collect();
+    install_specs(job, venv_path, specs, &requirements, options)
+}
+
+// Reinstalls only the pins that drifted, which includes torch packages of
+// the other variant. Returns what was reinstalled.
+pub fn sync_requirements(
+    job: &Job,
+    venv_path: &Path,
+    options: &InstallOptions,
+) -> Result<Vec<RequirementDrift>, String> {
+    let requirements = Requirements::bundled()?;
+    let drift = requirements.drift(&installed_packages(venv_path)?, options);
+    if drift.is_empty() {
+        return Ok(drift);
+    }
+
+    let specs = drift
+        .iter()
+        .map(|d| format!("{}=={}", d.name, d.required))
+        .collect();
+    install_specs(job, venv_path, specs, &requirements, options)?;
+    Ok(drift)
+}
+
+#[tauri::command]
+pub async fn check_venv_requirements(
+    store: State<'_, PythonSettingsStore>,
+) -> Result<RequirementsStatus, String> {
+    let settings = store.settings()?;
+    async_runtime::spawn_blocking(move || {
+        let venv_path = venv_dir()?;
+        if !venv_path.exists() {
+            return Err(format!(
+                "Virtual environment '{}' does not exist. Please create it first.",
+                VENV_NAME
+            ));
+        }
+
+        let requirements = Requirements::bundled()?;
+        let options = InstallOptions::from_settings(&settings);
+        let drift = requirements.drift(&installed_packages(&venv_path)?, &options);
+        Ok(RequirementsStatus {
+            requirements_file: requirements.path.to_string_lossy().into_owned(),
+            up_to_date: drift.is_empty(),
+            drift,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+// The PyTorch build that would be installed with the current settings.
+#[tauri::command]
+pub async fn detect_torch_variant(
+    store: State<'_, PythonSettingsStore>,
+) -> Result<TorchBuild, String> {
+    let settings = store.settings()?;
+    async_runtime::spawn_blocking(move || {
+        let gpu = detect_cuda_gpu();
+        TorchBuild {
+            configured: settings.torch_variant,
+            resolved: settings.torch_variant.resolve(|| gpu.is_some()),
+            gpu,
+            wheelhouse: settings.wheelhouse,
+        }
+    })
+    .await
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(packages: &[(&str, &str)]) -> HashMap<String, String> {
+        packages
+            .iter()
+            .map(|(name, version)| (normalize_name(name), version.to_string()))
+            .collect()
+    }
+
+    fn pins(pins: &[(&str, &str)]) -> Requirements {
+        Requirements {
+            path: PathBuf::from(REQUIREMENTS_FILE),
+            options: Vec::new(),
+            pins: pins
+                .iter()
+                .map(|(name, version)| Requirement {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                })
+                .collect(),
+        }
+    }
+
+    fn options(variant: TorchVariant) -> InstallOptions {
+        InstallOptions {
+            variant,
+            wheelhouse: None,
+        }
+    }
+
+    #[test]
+    fn normalizes_names() {
+        assert_eq!(normalize_name("Pillow"), "pillow");
+        assert_eq!(normalize_name("typing_extensions"), "typing-extensions");
+        assert_eq!(normalize_name("zope.interface"), "zope-interface");
+        assert_eq!(normalize_name("a-_.b"), "a-b");
+    }
+
+    #[test]
+    fn compares_versions() {
+        assert!(same_version("2.1.0", "2.1.0"));
+        assert!(same_version("1.0", "1.0.0"));
+        assert!(same_version("2.0.0", "2"));
+        assert!(!same_version("2.1.1", "2.1"));
+        assert!(!same_version("2.10", "2.1"));
+        // An installed label only counts when one is required
+        assert!(same_version("2.5.1+cpu", "2.5.1"));
+        assert!(same_version("2.5.1+cu124", "2.5.1.0+cu124"));
+        assert!(!same_version("2.5.1+cpu", "2.5.1+cu124"));
+        assert!(!same_version("2.5.1+cu124", "2.5.1+cpu"));
+        assert!(!same_version("2.5.1", "2.5.1+cpu"));
+    }
+
+    #[test]
+    fn loads_pins_and_options() {
+        let path = std::env::temp_dir().join(format!("requirements-{}.txt", std::process::id()));
+        fs::write(
+            &path,
+            "# bundled packages\n\
+             --prefer-binary\n\
+             -c constraints.txt\n\
+             \n\
+             numpy == 1.26.4  # pinned for torch\n\
+             torch==2.5.1\n",
+        )
+        .unwrap();
+        let requirements = Requirements::load(path.clone());
+        fs::write(&path, "numpy==1.26.4\nrequests>=2\n").unwrap();
+        let unpinned = Requirements::load(path.clone());
+        fs::remove_file(&path).unwrap();
+
+        let requirements = requirements.unwrap();
+        assert_eq!(
+            requirements.options,
+            ["--prefer-binary", "-c", "constraints.txt"]
+        );
+        let pins: Vec<(&str, &str)> = requirements
+            .pins
+            .iter()
+            .map(|pin| (pin.name.as_str(), pin.version.as_str()))
+            .collect();
+        assert_eq!(pins, [("numpy", "1.26.4"), ("torch", "2.5.1")]);
+
+        let error = unpinned.err().unwrap();
+        assert!(error.ends_with("line 2: requirement 'requests>=2' is not pinned with =="));
+    }
+
+    #[test]
+    fn lists_missing_and_mismatched_pins() {
+        let requirements = pins(&[
+            ("numpy", "1.26.4"),
+            ("Typing_Extensions", "4.12.0"),
+            ("requests", "2.32.3"),
+        ]);
+        let drift = requirements.drift(
+            &installed(&[("numpy", "1.26.4"), ("typing-extensions", "4.12")]),
+            &options(TorchVariant::Cpu),
+        );
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].name, "requests");
+        assert_eq!(drift[0].required, "2.32.3");
+        assert_eq!(drift[0].installed, None);
+
+        let drift = requirements.drift(
+            &installed(&[
+                ("numpy", "2.0.0"),
+                ("typing-extensions", "4.12.0"),
+                ("requests", "2.32.3"),
+            ]),
+            &options(TorchVariant::Cpu),
+        );
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].name, "numpy");
+        assert_eq!(drift[0].installed.as_deref(), Some("2.0.0"));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn pins_torch_to_the_variant() {
+        let requirements = pins(&[
+            ("torch", "2.5.1"),
+            ("torchvision", "0.20.1+cpu"),
+            ("numpy", "1.26.4"),
+        ]);
+        let packages = installed(&[
+            ("torch", "2.5.1+cpu"),
+            ("torchvision", "0.20.1+cpu"),
+            ("numpy", "1.26.4"),
+        ]);
+
+        assert!(requirements
+            .drift(&packages, &options(TorchVariant::Cpu))
+            .is_empty());
+
+        // A CPU build is replaced by the CUDA one; an explicit label is kept
+        let drift = requirements.drift(&packages, &options(TorchVariant::Cuda));
+        assert_eq!(drift.len(), 1);
+        assert_eq!(drift[0].name, "torch");
+        assert_eq!(drift[0].required, "2.5.1+cu124");
+        assert_eq!(drift[0].installed.as_deref(), Some("2.5.1+cpu"));
+    }
+}
@@ -0,0 +1,660 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use mysql::consts::{ColumnFlags, ColumnType};
+use mysql::prelude::*;
+use postgres::fallible_iterator::FallibleIterator;
+use postgres::types::{FromSql, Kind, Type};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+// Engine-neutral view of a result set, used by everything that streams rows
+// somewhere other than the results grid (exports, copies, diffs).
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CellKind {
+    Bool,
+    Int,
+    UInt,
+    Float,
+    Decimal,
+    Text,
+    Bytes,
+    Date,
+    Time,
+    DateTime,
+    // A timestamp with a time zone, read as UTC
+    TimestampTz,
+    Json,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ColumnInfo {
+    pub name: String,
+    pub type_name: String,
+    pub kind: CellKind,
+    pub nullable: bool,
+    // Only known for MySQL DECIMAL columns
+    pub precision: Option<u32>,
+    pub scale: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cell {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    // Exact numerics are kept as their decimal text
+    Decimal(String),
+    Text(String),
+    Bytes(Vec<u8>),
+    Date(NaiveDate),
+    // MySQL TIME ranges past 24 hours and can be negative, so keep the text
+    Time(String),
+    DateTime(NaiveDateTime),
+    // PostgreSQL timestamptz, which is stored as UTC
+    TimestampTz(DateTime<Utc>),
+    Json(String),
+}
+
+impl Cell {
+    // Plain text rendering; None for NULL.
+    pub fn to_text(&self) -> Option<String> {
+        match self {
+            Cell::Null => None,
+            Cell::Bool(b) => Some(b.to_string()),
+            Cell::Int(i) => Some(i.to_string()),
+            Cell::UInt(u) => Some(u.to_string()),
+            Cell::Float(f) => Some(f.to_string()),
+            Cell::Decimal(s) | Cell::Text(s) | Cell::Time(s) | Cell::Json(s) => Some(s.clone()),
+            Cell::Bytes(b) => Some(format!("0x{}", to_hex(b))),
+            Cell::Date(d) => Some(d.format("%Y-%m-%d").to_string()),
+            Cell::DateTime(dt) => Some(datetime_text(dt)),
+            Cell::TimestampTz(dt) => Some(format!("{}+00", datetime_text(&dt.naive_utc()))),
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Cell::Null => serde_json::Value::Null,
+            Cell::Bool(b) => serde_json::Value::from(*b),
+            Cell::Int(i) => serde_json::Value::from(*i),
+            Cell::UInt(u) => serde_json::Value::from(*u),
+            // NaN and infinities have no JSON representation
+            Cell::Float(f) => serde_json::Number::from_f64(*f)
+                .map(serde_json::Value::Number)
+                .unwrap_or(serde_json::Value::Null),
+            Cell::Json(s) => {
+                serde_json::from_str(s).unwrap_or_else(|_| serde_json::Value::from(s.clone()))
+            }
+            other => other
+                .to_text()
+                .map(serde_json::Value::from)
+                .unwrap_or(serde_json::Value::Null),
+        }
+    }
+}
+
+pub fn datetime_text(dt: &NaiveDateTime) -> String {
+    dt.format("%Y-%m-%d %H:%M:%S%.f").to_string()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub trait RowSink {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String>;
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String>;
+    fn finish(&mut self) -> Result<(), String>;
+
+    // Whether `start` may be called again for each further result set of a
+    // multi-statement query. Sinks that hold a single table only get the
+    // first one.
+    fn multiple_result_sets(&self) -> bool {
+        false
+    }
+
+    // Values the sink couldn't write as they were, reported with the
+    // summary once the stream is done
+    fn warnings(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+// Streams a MySQL query into `sink` row by row. Returns the row count.
+// Statements that produce no result set (SET, INSERT, ...) are skipped.
+pub fn stream_mysql<Q: Queryable>(
+    conn: &mut Q,
+    query: &str,
+    sink: &mut dyn RowSink,
+) -> Result<u64, String> {
+    let mut result = conn
+        .query_iter(query)
+        .map_err(|e| format!("Query execution error: {}", e))?;
+
+    let mut count = 0;
+    let mut started = false;
+    while let Some(mut set) = result.iter() {
+        let mysql_columns = set.columns().as_ref().to_vec();
+        if mysql_columns.is_empty() {
+            // Draining surfaces the error of a failed statement
+            for row in set.by_ref() {
+                row.map_err(|e| format!("Query execution error: {}", e))?;
+            }
+            continue;
+        }
+        if started && !sink.multiple_result_sets() {
+            break;
+        }
+
+        let columns: Vec<ColumnInfo> = mysql_columns.iter().map(mysql_column).collect();
+        sink.start(&columns)?;
+        started = true;
+
+        for row in set.by_ref() {
+            let row = row.map_err(|e| format!("Query execution error: {}", e))?;
+            let cells = row
+                .unwrap()
+                .into_iter()
+                .zip(&columns)
+                .map(|(value, column)| mysql_cell(value, column))
+                .collect();
+            sink.row(cells)?;
+            count += 1;
+        }
+    }
+
+    if !started {
+        sink.start(&[])?;
+    }
+    sink.finish()?;
+    Ok(count)
+}
+
+pub fn mysql_column(column: &mysql::Column) -> ColumnInfo {
+    let flags = column.flags();
+    let unsigned = flags.contains(ColumnFlags::UNSIGNED_FLAG);
+    // Character set 63 is `binary`: BLOB and VARBINARY rather than TEXT
+    let binary = column.character_set() == 63;
+
+    let kind = match column.column_type() {
+        ColumnType::MYSQL_TYPE_TINY
+        | ColumnType::MYSQL_TYPE_SHORT
+        | ColumnType::MYSQL_TYPE_INT24
+        | ColumnType::MYSQL_TYPE_LONG
+        | ColumnType::MYSQL_TYPE_LONGLONG
+        | ColumnType::MYSQL_TYPE_YEAR => {
+            if unsigned {
+                CellKind::UInt
+            } else {
+                CellKind::Int
+            }
+        }
+        ColumnType::MYSQL_TYPE_BIT => CellKind::UInt,
+        ColumnType::MYSQL_TYPE_FLOAT | ColumnType::MYSQL_TYPE_DOUBLE => CellKind::Float,
+        ColumnType::MYSQL_TYPE_DECIMAL | ColumnType::MYSQL_TYPE_NEWDECIMAL => CellKind::Decimal,
+        ColumnType::MYSQL_TYPE_DATE | ColumnType::MYSQL_TYPE_NEWDATE => CellKind::Date,
+        ColumnType::MYSQL_TYPE_TIME | ColumnType::MYSQL_TYPE_TIME2 => CellKind::Time,
+        ColumnType::MYSQL_TYPE_DATETIME
+        | ColumnType::MYSQL_TYPE_DATETIME2
+        | ColumnType::MYSQL_TYPE_TIMESTAMP
+        | ColumnType::MYSQL_TYPE_TIMESTAMP2 => CellKind::DateTime,
+        ColumnType::MYSQL_TYPE_JSON => CellKind::Json,
+        ColumnType::MYSQL_TYPE_GEOMETRY => CellKind::Bytes,
+        ColumnType::MYSQL_TYPE_TINY_BLOB
+        | ColumnType::MYSQL_TYPE_MEDIUM_BLOB
+        | ColumnType::MYSQL_TYPE_LONG_BLOB
+        | ColumnType::MYSQL_TYPE_BLOB
+        | ColumnType::MYSQL_TYPE_VARCHAR
+        | ColumnType::MYSQL_TYPE_VAR_STRING
+        | ColumnType::MYSQL_TYPE_STRING
+            if binary =>
+        {
+            CellKind::Bytes
+        }
+        _ => CellKind::Text,
+    };
+
+    let type_name = format!("{:?}", column.column_type())
+        .trim_start_matches("MYSQL_TYPE_")
+        .to_lowercase();
+
+    // column_length counts the sign and decimal point as well as the digits
+    let (precision, scale) = if kind == CellKind::Decimal {
+        let scale = column.decimals() as u32;
+        let overhead = (scale > 0) as u32 + (!unsigned) as u32;
+        (
+            Some(column.column_length().saturating_sub(overhead).max(1)),
+            Some(scale),
+        )
+    } else {
+        (None, None)
+    };
+
+    ColumnInfo {
+        name: column.name_str().into_owned(),
+        type_name,
+        kind,
+        nullable: !flags.contains(ColumnFlags::NOT_NULL_FLAG),
+        precision,
+        scale,
+    }
+}
+
+// The text protocol returns every value as bytes, so the column kind decides
+// how they are read back. Anything that does not parse (e.g. MySQL's zero
+// dates) is kept as text.
+pub fn mysql_cell(value: mysql::Value, column: &ColumnInfo) -> Cell {
+    match value {
+        mysql::Value::NULL => Cell::Null,
+        mysql::Value::Int(i) => Cell::Int(i),
+        mysql::Value::UInt(u) => Cell::UInt(u),
+        mysql::Value::Float(f) => Cell::Float(f as f64),
+        mysql::Value::Double(d) => Cell::Float(d),
+        mysql::Value::Date(y, m, d, h, i, s, us) => {
+            let date = NaiveDate::from_ymd_opt(y as i32, m as u32, d as u32);
+            match (column.kind, date) {
+                (CellKind::Date, Some(date)) => Cell::Date(date),
+                (_, Some(date)) => match date.and_hms_micro_opt(h as u32, i as u32, s as u32, us) {
+                    Some(dt) => Cell::DateTime(dt),
+                    None => Cell::Text(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, m, d, h, i, s)),
+                },
+                (_, None) => Cell::Text(format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", y, m, d, h, i, s)),
+            }
+        }
+        mysql::Value::Time(neg, d, h, i, s, us) => Cell::Time(format!(
+            "{}{:02}:{:02}:{:02}.{:06}",
+            if neg { "-" } else { "" },
+            d * 24 + h as u32,
+            i,
+            s,
+            us
+        )),
+        mysql::Value::Bytes(bytes) => {
+            if column.kind == CellKind::Bytes {
+                return Cell::Bytes(bytes);
+            }
+            // BIT(n) comes back as big-endian bytes even in text mode
+            if column.type_name == "bit" {
+                return Cell::UInt(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64));
+            }
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            match column.kind {
+                CellKind::Int => text.parse().map(Cell::Int).unwrap_or(Cell::Text(text)),
+                CellKind::UInt => text.parse().map(Cell::UInt).unwrap_or(Cell::Text(text)),
+                CellKind::Float => text.parse().map(Cell::Float).unwrap_or(Cell::Text(text)),
+                CellKind::Decimal => Cell::Decimal(text),
+                CellKind::Date => NaiveDate::parse_from_str(&text, "%Y-%m-%d")
+                    .map(Cell::Date)
+                    .unwrap_or(Cell::Text(text)),
+                CellKind::DateTime => NaiveDateTime::parse_from_str(&text, "%Y-%m-%d %H:%M:%S%.f")
+                    .map(Cell::DateTime)
+                    .unwrap_or(Cell::Text(text)),
+                CellKind::Time => Cell::Time(text),
+                CellKind::Json => Cell::Json(text),
+                _ => Cell::Text(text),
+            }
+        }
+    }
+}
+
+// Streams a PostgreSQL query into `sink` row by row. Returns the row count.
+pub fn stream_postgres<C: postgres::GenericClient>(
+    client: &mut C,
+    query: &str,
+    sink: &mut dyn RowSink,
+) -> Result<u64, String> {
+    // Preparing first gives the columns even when no rows come back
+    let mut statement = client.prepare(query).map_err(|e| e.to_string())?;
+    let columns: Vec<ColumnInfo> = statement
+        .columns()
+        .iter()
+        .map(|column| pg_column(column.name(), column.type_()))
+        .collect();
+    let types: Vec<Type> = statement
+        .columns()
+        .iter()
+        .map(|column| column.type_().clone())
+        .collect();
+    if let Some(index) = types.iter().position(|ty| !pg_decodes(ty)) {
+        // Statements that can't go in a WITH clause keep the old advice
+        statement = client.prepare(&pg_text_query(query, &types)).map_err(|_| {
+            format!(
+                "Column {} has unsupported type {}; cast it to text in the query",
+                columns[index].name, columns[index].type_name
+            )
+        })?;
+    }
+    sink.start(&columns)?;
+
+    let mut rows = client
+        .query_raw(&statement, std::iter::empty::<String>())
+        .map_err(|e| e.to_string())?;
+
+    let mut count = 0;
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let cells = (0..columns.len())
+            .map(|i| pg_cell(&row, i))
+            .collect::<Result<Vec<_>, _>>()?;
+        sink.row(cells)?;
+        count += 1;
+    }
+
+    sink.finish()?;
+    Ok(count)
+}
+
+// Whether `pg_cell` decodes values of `ty` itself.
+fn pg_decodes(ty: &Type) -> bool {
+    matches!(
+        *ty,
+        Type::BOOL
+            | Type::INT2
+            | Type::INT4
+            | Type::INT8
+            | Type::OID
+            | Type::FLOAT4
+            | Type::FLOAT8
+            | Type::NUMERIC
+            | Type::BYTEA
+            | Type::DATE
+            | Type::TIME
+            | Type::TIMESTAMP
+            | Type::TIMESTAMPTZ
+            | Type::JSON
+            | Type::JSONB
+            | Type::UUID
+    ) || <String as FromSql>::accepts(ty)
+        || matches!(ty.kind(), Kind::Enum(_))
+}
+
+// Wraps `query` so that columns of the types `pg_cell` can't decode
+// (intervals, inet, money, arrays, ranges, ...) come back in their text
+// form. The CTE's column list renames the columns by position, which copes
+// with duplicate names, and a CTE takes INSERT ... RETURNING as well as
+// SELECT.
+fn pg_text_query(query: &str, types: &[Type]) -> String {
+    let names: Vec<String> = (1..=types.len()).map(|i| format!("c{}", i)).collect();
+    let select: Vec<String> = names
+        .iter()
+        .zip(types)
+        .map(|(name, ty)| {
+            if pg_decodes(ty) {
+                name.clone()
+            } else {
+                format!("{}::text", name)
+            }
+        })
+        .collect();
+    // The line break ends a trailing `--` comment
+    format!(
+        "WITH q({}) AS (\n{}\n) SELECT {} FROM q",
+        names.join(", "),
+        query.trim().trim_end_matches(';'),
+        select.join(", ")
+    )
+}
+
+pub fn pg_column(name: &str, ty: &Type) -> ColumnInfo {
+    let kind = match *ty {
+        Type::BOOL => CellKind::Bool,
+        Type::INT2 | Type::INT4 | Type::INT8 => CellKind::Int,
+        Type::OID => CellKind::UInt,
+        Type::FLOAT4 | Type::FLOAT8 => CellKind::Float,
+        Type::NUMERIC => CellKind::Decimal,
+        Type::BYTEA => CellKind::Bytes,
+        Type::DATE => CellKind::Date,
+        Type::TIME => CellKind::Time,
+        Type::TIMESTAMP => CellKind::DateTime,
+        Type::TIMESTAMPTZ => CellKind::TimestampTz,
+        Type::JSON | Type::JSONB => CellKind::Json,
+        _ => CellKind::Text,
+    };
+
+    ColumnInfo {
+        name: name.to_string(),
+        type_name: ty.name().to_string(),
+        kind,
+        // Result metadata doesn't carry nullability or type modifiers
+        nullable: true,
+        precision: None,
+        scale: None,
+    }
+}
+
+pub fn pg_cell(row: &postgres::Row, index: usize) -> Result<Cell, String> {
+    let ty = row.columns()[index].type_().clone();
+    let get_error = |e: postgres::Error| format!("Column {}: {}", row.columns()[index].name(), e);
+
+    let cell = match ty {
+        Type::BOOL => row
+            .try_get::<_, Option<bool>>(index)
+            .map_err(get_error)?
+            .map(Cell::Bool),
+        Type::INT2 => row
+            .try_get::<_, Option<i16>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Int(v as i64)),
+        Type::INT4 => row
+            .try_get::<_, Option<i32>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Int(v as i64)),
+        Type::INT8 => row
+            .try_get::<_, Option<i64>>(index)
+            .map_err(get_error)?
+            .map(Cell::Int),
+        Type::OID => row
+            .try_get::<_, Option<u32>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::UInt(v as u64)),
+        Type::FLOAT4 => row
+            .try_get::<_, Option<f32>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Float(v as f64)),
+        Type::FLOAT8 => row
+            .try_get::<_, Option<f64>>(index)
+            .map_err(get_error)?
+            .map(Cell::Float),
+        Type::NUMERIC => row
+            .try_get::<_, Option<PgNumeric>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Decimal(v.0)),
+        Type::BYTEA => row
+            .try_get::<_, Option<Vec<u8>>>(index)
+            .map_err(get_error)?
+            .map(Cell::Bytes),
+        Type::DATE => row
+            .try_get::<_, Option<NaiveDate>>(index)
+            .map_err(get_error)?
+            .map(Cell::Date),
+        Type::TIME => row
+            .try_get::<_, Option<NaiveTime>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Time(v.format("%H:%M:%S%.f").to_string())),
+        Type::TIMESTAMP => row
+            .try_get::<_, Option<NaiveDateTime>>(index)
+            .map_err(get_error)?
+            .map(Cell::DateTime),
+        Type::TIMESTAMPTZ => row
+            .try_get::<_, Option<DateTime<Utc>>>(index)
+            .map_err(get_error)?
+            .map(Cell::TimestampTz),
+        Type::JSON | Type::JSONB => row
+            .try_get::<_, Option<PgJson>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Json(v.0)),
+        Type::UUID => row
+            .try_get::<_, Option<PgUuid>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Text(v.0)),
+        _ if <String as FromSql>::accepts(&ty) => row
+            .try_get::<_, Option<String>>(index)
+            .map_err(get_error)?
+            .map(Cell::Text),
+        // Enum labels are sent as plain text
+        _ if matches!(ty.kind(), Kind::Enum(_)) => row
+            .try_get::<_, Option<PgEnum>>(index)
+            .map_err(get_error)?
+            .map(|v| Cell::Text(v.0)),
+        _ => {
+            return Err(format!(
+                "Column {} has unsupported type {}; cast it to text in the query",
+                row.columns()[index].name(),
+                ty.name()
+            ))
+        }
+    };
+
+    Ok(cell.unwrap_or(Cell::Null))
+}
+
+type SqlError = Box<dyn Error + Sync + Send>;
+
+struct PgNumeric(String);
+
+impl<'a> FromSql<'a> for PgNumeric {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, SqlError> {
+        decode_numeric(raw).map(PgNumeric)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::NUMERIC
+    }
+}
+
+// Binary NUMERIC: ndigits, weight, sign, dscale, then base-10000 digits.
+fn decode_numeric(raw: &[u8]) -> Result<String, SqlError> {
+    if raw.len() < 8 {
+        return Err("invalid numeric".into());
+    }
+    let read = |i: usize| i16::from_be_bytes([raw[i], raw[i + 1]]);
+    let ndigits = read(0).max(0) as usize;
+    let weight = read(2) as i32;
+    let sign = u16::from_be_bytes([raw[4], raw[5]]);
+    let dscale = u16::from_be_bytes([raw[6], raw[7]]) as usize;
+    if raw.len() < 8 + ndigits * 2 {
+        return Err("invalid numeric".into());
+    }
+
+    match sign {
+        0xC000 => return Ok("NaN".to_string()),
+        0xD000 => return Ok("Infinity".to_string()),
+        0xF000 => return Ok("-Infinity".to_string()),
+        _ => {}
+    }
+
+    let digit = |i: i32| -> i16 {
+        if i >= 0 && (i as usize) < ndigits {
+            read(8 + i as usize * 2)
+        } else {
+            0
+        }
+    };
+
+    let mut out = String::new();
+    if sign == 0x4000 {
+        out.push('-');
+    }
+    if weight < 0 {
+        out.push('0');
+    } else {
+        for i in 0..=weight {
+            if i == 0 {
+                out.push_str(&digit(i).to_string());
+            } else {
+                out.push_str(&format!("{:04}", digit(i)));
+            }
+        }
+    }
+    if dscale > 0 {
+        let mut fraction = String::new();
+        let mut i = weight + 1;
+        while fraction.len() < dscale {
+            fraction.push_str(&format!("{:04}", digit(i)));
+            i += 1;
+        }
+        fraction.truncate(dscale);
+        out.push('.');
+        out.push_str(&fraction);
+    }
+
+    Ok(out)
+}
+
+struct PgJson(String);
+
+impl<'a> FromSql<'a> for PgJson {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Self, SqlError> {
+        // JSONB is prefixed with a format version byte
+        let raw = if *ty == Type::JSONB {
+            raw.get(1..).unwrap_or_default()
+        } else {
+            raw
+        };
+        Ok(PgJson(std::str::from_utf8(raw)?.to_string()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::JSON || *ty == Type::JSONB
+    }
+}
+
+struct PgUuid(String);
+
+impl<'a> FromSql<'a> for PgUuid {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, SqlError> {
+        if raw.len() != 16 {
+            return Err("invalid uuid".into());
+        }
+        let hex = to_hex(raw);
+        Ok(PgUuid(format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        *ty == Type::UUID
+    }
+}
+
+struct PgEnum(String);
+
+impl<'a> FromSql<'a> for PgEnum {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Self, SqlError> {
+        Ok(PgEnum(std::str::from_utf8(raw)?.to_string()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        matches!(ty.kind(), Kind::Enum(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_undecoded_postgres_types_as_text() {
+        assert!(pg_decodes(&Type::VARCHAR));
+        assert!(pg_decodes(&Type::TIMESTAMPTZ));
+        assert!(!pg_decodes(&Type::INTERVAL));
+        assert!(!pg_decodes(&Type::INT4_ARRAY));
+
+        let types = [Type::INT4, Type::INTERVAL, Type::INT4];
+        assert_eq!(
+            pg_text_query("SELECT id, span, id FROM t;", &types),
+            "WITH q(c1, c2, c3) AS (\nSELECT id, span, id FROM t\n) SELECT c1, c2::text, c3 FROM q"
+        );
+        assert_eq!(
+            pg_text_query("TABLE t -- all of it", &types[1..2]),
+            "WITH q(c1) AS (\nTABLE t -- all of it\n) SELECT c1::text FROM q"
+        );
+    }
+}
@@ -0,0 +1,13 @@
+use tauri::async_runtime;
+
+// Runs a blocking driver call on the blocking thread pool so long queries
+// don't hold up unrelated commands.
+pub async fn run_blocking<T, F>(task: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    async_runtime::spawn_blocking(task)
+        .await
+        .map_err(|e| e.to_string())?
+}
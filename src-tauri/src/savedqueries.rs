@@ -0,0 +1,556 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tauri::State;
+
+use crate::history::epoch_millis;
+
+const SAVED_QUERIES_FILE: &str = "saved_queries.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SavedQuery {
+    id: u64,
+    name: String,
+    query: String,
+    description: Option<String>,
+    tags: Vec<String>,
+    // Slash separated, e.g. "reports/monthly"
+    folder: Option<String>,
+    // Connection label the query is scoped to; None means any connection
+    connection: Option<String>,
+    created_at: u64,
+    updated_at: u64,
+}
+
+#[derive(Deserialize)]
+pub struct SavedQueryInput {
+    name: String,
+    query: String,
+    description: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    folder: Option<String>,
+    connection: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct SavedQueryFilter {
+    text: Option<String>,
+    tag: Option<String>,
+    folder: Option<String>,
+    connection: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportSummary {
+    created: usize,
+    updated: usize,
+    skipped: Vec<String>,
+}
+
+struct SavedQueryData {
+    queries: Vec<SavedQuery>,
+    next_id: u64,
+}
+
+pub struct SavedQueryStore {
+    path: PathBuf,
+    data: Mutex<SavedQueryData>,
+}
+
+impl SavedQueryStore {
+    pub fn load(dir: PathBuf) -> Result<Self, String> {
+        fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let path = dir.join(SAVED_QUERIES_FILE);
+
+        let queries: Vec<SavedQuery> = match fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json).map_err(|e| e.to_string())?,
+            Err(_) => Vec::new(),
+        };
+        let next_id = queries.iter().map(|q| q.id).max().unwrap_or(0) + 1;
+
+        Ok(SavedQueryStore {
+            path,
+            data: Mutex::new(SavedQueryData { queries, next_id }),
+        })
+    }
+
+    fn save(&self, queries: &[SavedQuery]) -> Result<(), String> {
+        let tmp_path = self.path.with_extension("json.tmp");
+        let json = serde_json::to_string_pretty(queries).map_err(|e| e.to_string())?;
+        fs::write(&tmp_path, json).map_err(|e| e.to_string())?;
+        fs::rename(&tmp_path, &self.path).map_err(|e| e.to_string())
+    }
+}
+
+impl SavedQueryData {
+    fn insert(&mut self, input: SavedQueryInput) -> SavedQuery {
+        let now = epoch_millis(SystemTime::now());
+        let saved = SavedQuery {
+            id: self.next_id,
+            name: input.name,
+            query: input.query,
+            description: input.description,
+            tags: normalize_tags(input.tags),
+            folder: normalize_folder(input.folder),
+            connection: input.connection,
+            created_at: now,
+            updated_at: now,
+        };
+        self.next_id += 1;
+        self.queries.push(saved.clone());
+        saved
+    }
+}
+
+impl SavedQuery {
+    fn apply(&mut self, input: SavedQueryInput) {
+        self.name = input.name;
+        self.query = input.query;
+        self.description = input.description;
+        self.tags = normalize_tags(input.tags);
+        self.folder = normalize_folder(input.folder);
+        self.connection = input.connection;
+        self.updated_at = epoch_millis(SystemTime::now());
+    }
+
+    fn matches(&self, filter: &SavedQueryFilter) -> bool {
+        let text_matches = filter.text.as_ref().map_or(true, |text| {
+            let text = text.to_lowercase();
+            self.name.to_lowercase().contains(&text)
+                || self.query.to_lowercase().contains(&text)
+                || self
+                    .description
+                    .as_ref()
+                    .is_some_and(|d| d.to_lowercase().contains(&text))
+        });
+        let tag_matches = filter
+            .tag
+            .as_ref()
+            .map_or(true, |tag| self.tags.contains(&tag.to_lowercase()));
+        // A folder filter also matches everything below it
+        let folder_matches = filter.folder.as_ref().map_or(true, |folder| {
+            let folder = folder.trim_matches('/');
+            self.folder.as_deref().is_some_and(|f| {
+                f == folder || f.starts_with(&format!("{}/", folder))
+            })
+        });
+        // Unscoped queries are available on every connection
+        let connection_matches = filter.connection.as_ref().map_or(true, |connection| {
+            self.connection.is_none() || self.connection.as_ref() == Some(connection)
+        });
+
+        text_matches && tag_matches && folder_matches && connection_matches
+    }
+}
+
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut tags: Vec<String> = tags
+        .into_iter()
+        .map(|t| t.trim().to_lowercase())
+        .filter(|t| !t.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+fn normalize_folder(folder: Option<String>) -> Option<String> {
+    folder
+        .map(|f| {
+            f.split(['/', '\\'])
+                .map(str::trim)
+                .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+                .collect::<Vec<_>>()
+                .join("/")
+        })
+        .filter(|f| !f.is_empty())
+}
+
+#[tauri::command]
+pub fn create_saved_query(
+    input: SavedQueryInput,
+    store: State<'_, SavedQueryStore>,
+) -> Result<SavedQuery, String> {
+    let mut data = store.data.lock().map_err(|e| e.to_string())?;
+    let saved = data.insert(input);
+    store.save(&data.queries)?;
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn update_saved_query(
+    id: u64,
+    input: SavedQueryInput,
+    store: State<'_, SavedQueryStore>,
+) -> Result<SavedQuery, String> {
+    let mut data = store.data.lock().map_err(|e| e.to_string())?;
+    let saved = data
+        .queries
+        .iter_mut()
+        .find(|q| q.id == id)
+        .ok_or_else(|| format!("Saved query {} not found", id))?;
+    saved.apply(input);
+    let saved = saved.clone();
+    store.save(&data.queries)?;
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn delete_saved_query(id: u64, store: State<'_, SavedQueryStore>) -> Result<(), String> {
+    let mut data = store.data.lock().map_err(|e| e.to_string())?;
+    let count = data.queries.len();
+    data.queries.retain(|q| q.id != id);
+    if data.queries.len() == count {
+        return Err(format!("Saved query {} not found", id));
+    }
+    store.save(&data.queries)
+}
+
+#[tauri::command]
+pub fn list_saved_queries(
+    connection: Option<String>,
+    store: State<'_, SavedQueryStore>,
+) -> Result<Vec<SavedQuery>, String> {
+    search_saved_queries(
+        SavedQueryFilter {
+            connection,
+            ..Default::default()
+        },
+        store,
+    )
+}
+
+#[tauri::command]
+pub fn search_saved_queries(
+    filter: SavedQueryFilter,
+    store: State<'_, SavedQueryStore>,
+) -> Result<Vec<SavedQuery>, String> {
+    let data = store.data.lock().map_err(|e| e.to_string())?;
+    let mut queries: Vec<SavedQuery> = data
+        .queries
+        .iter()
+        .filter(|q| q.matches(&filter))
+        .cloned()
+        .collect();
+    queries.sort_by(|a, b| (&a.folder, &a.name).cmp(&(&b.folder, &b.name)));
+    Ok(queries)
+}
+
+// Writes every saved query as `<dir>/<folder>/<name>.sql` with the metadata
+// in a comment header, so the library can be shared through git. Names that
+// end up on the same file get a `-2`, `-3`, ... suffix; the header keeps the
+// real name for import.
+#[tauri::command]
+pub fn export_saved_queries(dir: String, store: State<'_, SavedQueryStore>) -> Result<usize, String> {
+    let data = store.data.lock().map_err(|e| e.to_string())?;
+    let root = Path::new(&dir);
+    // Compared case-insensitively, as the file system may be
+    let mut used = HashSet::new();
+
+    for saved in &data.queries {
+        let folder = match &saved.folder {
+            Some(folder) => root.join(folder),
+            None => root.to_path_buf(),
+        };
+        fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+        let stem = file_stem(&saved.name);
+        let mut file = folder.join(format!("{}.sql", stem));
+        let mut suffix = 2;
+        while !used.insert(file.to_string_lossy().to_lowercase()) {
+            file = folder.join(format!("{}-{}.sql", stem, suffix));
+            suffix += 1;
+        }
+        fs::write(&file, to_sql_file(saved))
+            .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+    }
+
+    Ok(data.queries.len())
+}
+
+// Reads `.sql` files from a folder tree. A file whose folder and name match
+// an existing saved query updates it, anything else is created.
+#[tauri::command]
+pub fn import_saved_queries(
+    dir: String,
+    store: State<'_, SavedQueryStore>,
+) -> Result<ImportSummary, String> {
+    let root = PathBuf::from(dir);
+    let mut files = Vec::new();
+    collect_sql_files(&root, &mut files)?;
+
+    let mut data = store.data.lock().map_err(|e| e.to_string())?;
+    let mut summary = ImportSummary {
+        created: 0,
+        updated: 0,
+        skipped: Vec::new(),
+    };
+
+    for file in files {
+        let Ok(content) = fs::read_to_string(&file) else {
+            summary.skipped.push(file.display().to_string());
+            continue;
+        };
+        let folder = file
+            .parent()
+            .and_then(|parent| parent.strip_prefix(&root).ok())
+            .map(|relative| relative.to_string_lossy().replace('\\', "/"));
+        let default_name = file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let input = parse_sql_file(&content, default_name, normalize_folder(folder));
+
+        if input.query.trim().is_empty() {
+            summary.skipped.push(file.display().to_string());
+            continue;
+        }
+
+        match data
+            .queries
+            .iter_mut()
+            .find(|q| q.name == input.name && q.folder == input.folder)
+        {
+            Some(existing) => {
+                existing.apply(input);
+                summary.updated += 1;
+            }
+            None => {
+                data.insert(input);
+                summary.created += 1;
+            }
+        }
+    }
+
+    store.save(&data.queries)?;
+    Ok(summary)
+}
+
+fn collect_sql_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        if path.is_dir() {
+            // Don't descend into .git and other hidden folders
+            if !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+            {
+                collect_sql_files(&path, files)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "sql") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect();
+    let stem = stem.trim().trim_matches('.');
+    if stem.is_empty() {
+        "query".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+// Header values are escaped so a line break can't end the header early and
+// a comma can't split a tag: `\\`, `\n`, `\r` and, in tags, `\,`.
+fn to_sql_file(saved: &SavedQuery) -> String {
+    let mut content = format!("-- name: {}\n", escape_header(&saved.name));
+    if let Some(description) = &saved.description {
+        for line in description.lines() {
+            content.push_str(&format!("-- description: {}\n", escape_header(line)));
+        }
+    }
+    if !saved.tags.is_empty() {
+        let tags: Vec<String> = saved
+            .tags
+            .iter()
+            .map(|tag| escape_header(tag).replace(',', "\\,"))
+            .collect();
+        content.push_str(&format!("-- tags: {}\n", tags.join(", ")));
+    }
+    if let Some(connection) = &saved.connection {
+        content.push_str(&format!("-- connection: {}\n", escape_header(connection)));
+    }
+    content.push('\n');
+    content.push_str(saved.query.trim_end());
+    content.push('\n');
+    content
+}
+
+// Parses the header written by `to_sql_file`. Leading `-- key: value` lines
+// are metadata; everything after the first other line is the query.
+fn parse_sql_file(content: &str, default_name: String, folder: Option<String>) -> SavedQueryInput {
+    let mut input = SavedQueryInput {
+        name: default_name,
+        query: String::new(),
+        description: None,
+        tags: Vec::new(),
+        folder,
+        connection: None,
+    };
+
+    let mut lines = content.lines().peekable();
+    while let Some(&line) = lines.peek() {
+        let Some((key, value)) = line
+            .strip_prefix("--")
+            .and_then(|rest| rest.split_once(':'))
+        else {
+            break;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "name" => input.name = unescape_header(value),
+            "description" => {
+                let value = unescape_header(value);
+                input.description = Some(match input.description.take() {
+                    Some(previous) => format!("{}\n{}", previous, value),
+                    None => value,
+                })
+            }
+            "tags" => input.tags = split_tags(value),
+            "connection" => input.connection = Some(unescape_header(value)),
+            _ => break,
+        }
+        lines.next();
+    }
+
+    input.query = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+    input
+}
+
+fn escape_header(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+// Undoes `escape_header`. Other backslashes are kept as they are, so files
+// written before values were escaped read the same.
+fn unescape_header(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(next @ ('\\' | ',')) => unescaped.push(next),
+            Some(next) => {
+                unescaped.push('\\');
+                unescaped.push(next);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+// Splits a `tags` header on the commas that aren't escaped.
+fn split_tags(value: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            ',' => {
+                tags.push(unescape_header(value[start..i].trim()));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    tags.push(unescape_header(value[start..].trim()));
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved(name: &str, tags: &[&str], connection: Option<&str>) -> SavedQuery {
+        SavedQuery {
+            id: 1,
+            name: name.to_string(),
+            query: "SELECT *\nFROM orders\nWHERE note = '-- name: x';".to_string(),
+            description: Some("Open orders\nby region, C:\\temp".to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            folder: Some("reports/monthly".to_string()),
+            connection: connection.map(str::to_string),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn round_trip(saved: &SavedQuery) -> SavedQueryInput {
+        let content = to_sql_file(saved);
+        parse_sql_file(&content, "file".to_string(), saved.folder.clone())
+    }
+
+    #[test]
+    fn round_trips_a_query() {
+        let saved = saved("Open orders", &["finance", "orders"], Some("prod"));
+        let input = round_trip(&saved);
+        assert_eq!(input.name, saved.name);
+        assert_eq!(input.query, saved.query);
+        assert_eq!(input.description, saved.description);
+        assert_eq!(input.tags, saved.tags);
+        assert_eq!(input.folder, saved.folder);
+        assert_eq!(input.connection, saved.connection);
+    }
+
+    #[test]
+    fn escapes_header_values() {
+        let saved = saved(
+            "Open\norders \\n",
+            &["a,b", "c\\", "d"],
+            Some("prod\r\n-- name: other"),
+        );
+        let content = to_sql_file(&saved);
+        assert!(content.starts_with("-- name: Open\\norders \\\\n\n"));
+        assert!(content.contains("-- tags: a\\,b, c\\\\, d\n"));
+
+        let input = round_trip(&saved);
+        assert_eq!(input.name, saved.name);
+        assert_eq!(input.tags, saved.tags);
+        assert_eq!(input.connection, saved.connection);
+        assert_eq!(input.query, saved.query);
+    }
+
+    #[test]
+    fn reads_files_without_escapes() {
+        let input = parse_sql_file(
+            "-- name: Load\n-- connection: C:\\data\\prod\n-- tags: a, b\n\nSELECT 1",
+            "file".to_string(),
+            None,
+        );
+        assert_eq!(input.name, "Load");
+        assert_eq!(input.connection.as_deref(), Some("C:\\data\\prod"));
+        assert_eq!(input.tags, ["a", "b"]);
+        assert_eq!(input.query, "SELECT 1");
+    }
+
+    #[test]
+    fn names_files_after_queries() {
+        assert_eq!(file_stem("a/b: c?"), "a_b_ c_");
+        assert_eq!(file_stem(" .. "), "query");
+    }
+}
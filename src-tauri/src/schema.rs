@@ -0,0 +1,705 @@
+use mysql::prelude::*;
+use serde::Serialize;
+
+use crate::dialect;
+use crate::engine::{Connection, Engine};
+
+// Table metadata read from the catalogs, used to recreate tables on another
+// connection and to compare schemas.
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ColumnDef {
+    pub name: String,
+    // Base type as the catalog names it: `varchar`, `int` on MySQL, the udt
+    // name (`int4`, `timestamptz`) on PostgreSQL
+    pub data_type: String,
+    // Full declared type, e.g. `varchar(255)`, `int unsigned`, `numeric(10,2)`
+    pub column_type: String,
+    pub nullable: bool,
+    // Default as an SQL expression, with string literals quoted
+    pub default: Option<String>,
+    pub auto_increment: bool,
+    pub unsigned: bool,
+    pub max_length: Option<u64>,
+    pub precision: Option<u64>,
+    pub scale: Option<u64>,
+    pub datetime_precision: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct IndexDef {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub unique: bool,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConstraintKind {
+    PrimaryKey,
+    Unique,
+    ForeignKey,
+    Check,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ConstraintDef {
+    pub name: String,
+    pub kind: ConstraintKind,
+    // The constraint as it follows `ADD CONSTRAINT <name>`, e.g.
+    // `FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE`
+    pub definition: String,
+    // Local columns of a primary, unique or foreign key; empty for checks
+    pub columns: Vec<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<ColumnDef>,
+    pub primary_key: Vec<String>,
+    // Secondary indexes; expression and partial indexes are left out
+    pub indexes: Vec<IndexDef>,
+    // MySQL unique keys are only listed as indexes; PostgreSQL unique
+    // constraints only here, without the index backing them
+    pub constraints: Vec<ConstraintDef>,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ViewDef {
+    pub name: String,
+    // The SELECT, as the server rewrote it
+    pub definition: String,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RoutineDef {
+    pub name: String,
+    // `function` or `procedure`
+    pub kind: String,
+    // Argument types, to tell PostgreSQL overloads apart; empty on MySQL
+    pub arguments: String,
+    // The complete CREATE statement
+    pub definition: String,
+}
+
+// Splits `schema.table`; PostgreSQL tables default to `public`, as in the
+// object browser.
+pub fn split_table_name(table: &str) -> (Option<&str>, &str) {
+    match table.split_once('.') {
+        Some((schema, name)) => (Some(schema), name),
+        None => (None, table),
+    }
+}
+
+pub fn table_schema(conn: &mut Connection, table: &str) -> Result<TableSchema, String> {
+    let (schema, name) = split_table_name(table);
+    let table_schema = match conn {
+        Connection::Mysql(conn) => mysql_table(conn, schema, name)?,
+        Connection::Postgres(client) => pg_table(client, schema.unwrap_or("public"), name)?,
+    };
+
+    if table_schema.columns.is_empty() {
+        return Err(format!("Table {} not found", table));
+    }
+    Ok(table_schema)
+}
+
+pub fn table_exists(conn: &mut Connection, table: &str) -> Result<bool, String> {
+    let (schema, name) = split_table_name(table);
+    match conn {
+        Connection::Mysql(conn) => conn
+            .exec_first::<u64, _, _>(
+                "SELECT COUNT(*) FROM information_schema.TABLES \
+                 WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ?",
+                (schema, name),
+            )
+            .map(|count| count.unwrap_or(0) > 0)
+            .map_err(|e| e.to_string()),
+        Connection::Postgres(client) => client
+            .query_one(
+                "SELECT EXISTS (SELECT 1 FROM information_schema.tables \
+                 WHERE table_schema = $1 AND table_name = $2)",
+                &[&schema.unwrap_or("public"), &name],
+            )
+            .map(|row| row.get(0))
+            .map_err(|e| e.to_string()),
+    }
+}
+
+// Collation of each text column of `table`. PostgreSQL's `default` is
+// resolved to the database's collation so servers can be compared.
+pub fn column_collations(
+    conn: &mut Connection,
+    table: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let (schema, name) = split_table_name(table);
+    match conn {
+        Connection::Mysql(conn) => conn
+            .exec(
+                "SELECT COLUMN_NAME, COLLATION_NAME FROM information_schema.COLUMNS \
+                 WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? \
+                   AND COLLATION_NAME IS NOT NULL",
+                (schema, name),
+            )
+            .map_err(|e| e.to_string()),
+        Connection::Postgres(client) => client
+            .query(
+                "SELECT a.attname::text, \
+                        CASE WHEN co.collname = 'default' \
+                             THEN (SELECT datcollate::text FROM pg_catalog.pg_database \
+                                   WHERE datname = current_database()) \
+                             ELSE co.collname::text END \
+                 FROM pg_catalog.pg_attribute a \
+                 JOIN pg_catalog.pg_class t ON t.oid = a.attrelid \
+                 JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace \
+                 JOIN pg_catalog.pg_collation co ON co.oid = a.attcollation \
+                 WHERE n.nspname = $1 AND t.relname = $2 AND a.attnum > 0 AND NOT a.attisdropped",
+                &[&schema.unwrap_or("public"), &name],
+            )
+            .map(|rows| rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+pub fn row_count(conn: &mut Connection, table: &str) -> Result<u64, String> {
+    let sql = format!(
+        "SELECT COUNT(*) FROM {}",
+        dialect::quote_table(table, conn.engine())
+    );
+    match conn {
+        Connection::Mysql(conn) => conn
+            .query_first::<u64, _>(sql)
+            .map(|count| count.unwrap_or(0))
+            .map_err(|e| e.to_string()),
+        Connection::Postgres(client) => client
+            .query_one(sql.as_str(), &[])
+            .map(|row| row.get::<_, i64>(0) as u64)
+            .map_err(|e| e.to_string()),
+    }
+}
+
+// The selected database on MySQL, the first schema on the search path on
+// PostgreSQL.
+pub fn current_schema(conn: &mut Connection) -> Result<String, String> {
+    let schema = match conn {
+        Connection::Mysql(conn) => conn
+            .query_first::<Option<String>, _>("SELECT DATABASE()")
+            .map_err(|e| e.to_string())?
+            .flatten(),
+        Connection::Postgres(client) => client
+            .query_one("SELECT current_schema()::text", &[])
+            .map_err(|e| e.to_string())?
+            .get(0),
+    };
+    schema.ok_or_else(|| "No database selected. Please select a database first.".to_string())
+}
+
+// Base tables of a schema, by name.
+pub fn list_tables(conn: &mut Connection, schema: &str) -> Result<Vec<String>, String> {
+    match conn {
+        Connection::Mysql(conn) => conn
+            .exec(
+                "SELECT TABLE_NAME FROM information_schema.TABLES \
+                 WHERE TABLE_SCHEMA = ? AND TABLE_TYPE = 'BASE TABLE' ORDER BY TABLE_NAME",
+                (schema,),
+            )
+            .map_err(|e| e.to_string()),
+        Connection::Postgres(client) => client
+            .query(
+                "SELECT table_name::text FROM information_schema.tables \
+                 WHERE table_schema = $1 AND table_type = 'BASE TABLE' ORDER BY table_name",
+                &[&schema],
+            )
+            .map(|rows| rows.iter().map(|row| row.get(0)).collect())
+            .map_err(|e| e.to_string()),
+    }
+}
+
+pub fn list_views(conn: &mut Connection, schema: &str) -> Result<Vec<ViewDef>, String> {
+    let views: Vec<(String, Option<String>)> = match conn {
+        Connection::Mysql(conn) => conn
+            .exec(
+                "SELECT TABLE_NAME, VIEW_DEFINITION FROM information_schema.VIEWS \
+                 WHERE TABLE_SCHEMA = ? ORDER BY TABLE_NAME",
+                (schema,),
+            )
+            .map_err(|e| e.to_string())?,
+        Connection::Postgres(client) => client
+            .query(
+                "SELECT c.relname::text, pg_get_viewdef(c.oid, true) \
+                 FROM pg_catalog.pg_class c \
+                 JOIN pg_catalog.pg_namespace n ON n.oid = c.relnamespace \
+                 WHERE n.nspname = $1 AND c.relkind = 'v' ORDER BY c.relname",
+                &[&schema],
+            )
+            .map_err(|e| e.to_string())?
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect(),
+    };
+
+    Ok(views
+        .into_iter()
+        .map(|(name, definition)| ViewDef {
+            name,
+            definition: definition
+                .unwrap_or_default()
+                .trim()
+                .trim_end_matches(';')
+                .to_string(),
+        })
+        .collect())
+}
+
+// Functions and procedures. Routines installed by PostgreSQL extensions
+// belong to the extension and are left out.
+pub fn list_routines(conn: &mut Connection, schema: &str) -> Result<Vec<RoutineDef>, String> {
+    match conn {
+        Connection::Mysql(conn) => {
+            let names: Vec<(String, String)> = conn
+                .exec(
+                    "SELECT ROUTINE_NAME, ROUTINE_TYPE FROM information_schema.ROUTINES \
+                     WHERE ROUTINE_SCHEMA = ? ORDER BY ROUTINE_NAME",
+                    (schema,),
+                )
+                .map_err(|e| e.to_string())?;
+
+            names
+                .into_iter()
+                .map(|(name, kind)| {
+                    // The statement is NULL for users without access to
+                    // the routine's body
+                    let row: Option<mysql::Row> = conn
+                        .query_first(format!(
+                            "SHOW CREATE {} {}.{}",
+                            kind,
+                            dialect::quote_ident(schema, Engine::Mysql),
+                            dialect::quote_ident(&name, Engine::Mysql)
+                        ))
+                        .map_err(|e| e.to_string())?;
+                    let definition = row
+                        .and_then(|row| row.get_opt::<Option<String>, _>(2))
+                        .and_then(|value| value.ok())
+                        .flatten()
+                        .unwrap_or_default();
+                    Ok(RoutineDef {
+                        name,
+                        kind: kind.to_lowercase(),
+                        arguments: String::new(),
+                        definition,
+                    })
+                })
+                .collect()
+        }
+        Connection::Postgres(client) => client
+            .query(
+                "SELECT p.proname::text, \
+                        CASE p.prokind WHEN 'p' THEN 'procedure' ELSE 'function' END, \
+                        pg_get_function_identity_arguments(p.oid), pg_get_functiondef(p.oid) \
+                 FROM pg_catalog.pg_proc p \
+                 JOIN pg_catalog.pg_namespace n ON n.oid = p.pronamespace \
+                 WHERE n.nspname = $1 AND p.prokind IN ('f', 'p') \
+                   AND NOT EXISTS (SELECT 1 FROM pg_catalog.pg_depend d \
+                                   WHERE d.objid = p.oid AND d.deptype = 'e') \
+                 ORDER BY 1, 3",
+                &[&schema],
+            )
+            .map(|rows| {
+                rows.iter()
+                    .map(|row| RoutineDef {
+                        name: row.get(0),
+                        kind: row.get(1),
+                        arguments: row.get(2),
+                        definition: row.get(3),
+                    })
+                    .collect()
+            })
+            .map_err(|e| e.to_string()),
+    }
+}
+
+type MysqlColumnRow = (
+    String,
+    String,
+    String,
+    String,
+    Option<String>,
+    String,
+    Option<u64>,
+    Option<u64>,
+    Option<u64>,
+    Option<u64>,
+);
+
+fn mysql_table(
+    conn: &mut mysql::PooledConn,
+    schema: Option<&str>,
+    name: &str,
+) -> Result<TableSchema, String> {
+    let rows: Vec<MysqlColumnRow> = conn
+        .exec(
+            "SELECT COLUMN_NAME, DATA_TYPE, COLUMN_TYPE, IS_NULLABLE, COLUMN_DEFAULT, EXTRA, \
+                    CHARACTER_MAXIMUM_LENGTH, NUMERIC_PRECISION, NUMERIC_SCALE, DATETIME_PRECISION \
+             FROM information_schema.COLUMNS \
+             WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? \
+             ORDER BY ORDINAL_POSITION",
+            (schema, name),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let columns = rows
+        .into_iter()
+        .map(
+            |(
+                name,
+                data_type,
+                column_type,
+                nullable,
+                default,
+                extra,
+                max_length,
+                precision,
+                scale,
+                datetime_precision,
+            )| ColumnDef {
+                name,
+                data_type: data_type.to_lowercase(),
+                unsigned: column_type.to_lowercase().contains("unsigned"),
+                default: mysql_default(default, &data_type, &extra),
+                column_type,
+                nullable: nullable == "YES",
+                auto_increment: extra.to_lowercase().contains("auto_increment"),
+                max_length,
+                precision,
+                scale,
+                datetime_precision,
+            },
+        )
+        .collect();
+
+    // Functional key parts have no column name; those indexes are skipped
+    let index_rows: Vec<(String, u64, Option<String>)> = conn
+        .exec(
+            "SELECT INDEX_NAME, NON_UNIQUE, COLUMN_NAME FROM information_schema.STATISTICS \
+             WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ? \
+             ORDER BY INDEX_NAME, SEQ_IN_INDEX",
+            (schema, name),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut primary_key = Vec::new();
+    let mut indexes = group_indexes(
+        index_rows
+            .into_iter()
+            .map(|(index, non_unique, column)| (index, non_unique == 0, column)),
+    );
+    if let Some(pos) = indexes.iter().position(|index| index.name == "PRIMARY") {
+        primary_key = indexes.remove(pos).columns;
+    }
+
+    let mut constraints = Vec::new();
+    if !primary_key.is_empty() {
+        constraints.push(ConstraintDef {
+            name: "PRIMARY".to_string(),
+            kind: ConstraintKind::PrimaryKey,
+            definition: format!(
+                "PRIMARY KEY ({})",
+                dialect::column_list(&primary_key, Engine::Mysql)
+            ),
+            columns: primary_key.clone(),
+        });
+    }
+    constraints.extend(mysql_foreign_keys(conn, schema, name)?);
+    constraints.extend(mysql_checks(conn, schema, name));
+
+    Ok(TableSchema {
+        name: name.to_string(),
+        columns,
+        primary_key,
+        indexes,
+        constraints,
+    })
+}
+
+// information_schema reports string defaults without quotes and expression
+// defaults as bare text; numbers, bit literals and CURRENT_TIMESTAMP are
+// the same in both.
+fn mysql_default(default: Option<String>, data_type: &str, extra: &str) -> Option<String> {
+    let default = default?;
+    let upper = default.to_uppercase();
+    let numeric = matches!(
+        data_type.to_lowercase().as_str(),
+        "tinyint"
+            | "smallint"
+            | "mediumint"
+            | "int"
+            | "integer"
+            | "bigint"
+            | "decimal"
+            | "numeric"
+            | "float"
+            | "double"
+            | "real"
+            | "bit"
+            | "year"
+    );
+
+    Some(
+        if upper.starts_with("CURRENT_TIMESTAMP") || upper.starts_with("NOW(") || numeric {
+            default
+        } else if extra.to_uppercase().contains("DEFAULT_GENERATED") {
+            format!("({})", default)
+        } else {
+            dialect::quote_string(&default, Engine::Mysql)
+        },
+    )
+}
+
+type MysqlForeignKeyRow = (String, String, String, String, String, String, String, bool);
+
+fn mysql_foreign_keys(
+    conn: &mut mysql::PooledConn,
+    schema: Option<&str>,
+    name: &str,
+) -> Result<Vec<ConstraintDef>, String> {
+    let rows: Vec<MysqlForeignKeyRow> = conn
+        .exec(
+            "SELECT k.CONSTRAINT_NAME, k.COLUMN_NAME, k.REFERENCED_TABLE_SCHEMA, \
+                    k.REFERENCED_TABLE_NAME, k.REFERENCED_COLUMN_NAME, r.UPDATE_RULE, \
+                    r.DELETE_RULE, k.REFERENCED_TABLE_SCHEMA = k.TABLE_SCHEMA \
+             FROM information_schema.KEY_COLUMN_USAGE k \
+             JOIN information_schema.REFERENTIAL_CONSTRAINTS r \
+               ON r.CONSTRAINT_SCHEMA = k.CONSTRAINT_SCHEMA \
+              AND r.CONSTRAINT_NAME = k.CONSTRAINT_NAME AND r.TABLE_NAME = k.TABLE_NAME \
+             WHERE k.TABLE_SCHEMA = COALESCE(?, DATABASE()) AND k.TABLE_NAME = ? \
+             ORDER BY k.CONSTRAINT_NAME, k.ORDINAL_POSITION",
+            (schema, name),
+        )
+        .map_err(|e| e.to_string())?;
+
+    // One row per key column; consecutive rows of a constraint are folded
+    let mut keys: Vec<(String, Vec<String>, String, Vec<String>, String, String)> = Vec::new();
+    for (constraint, column, ref_schema, ref_table, ref_column, on_update, on_delete, local) in rows
+    {
+        match keys.last_mut() {
+            Some(key) if key.0 == constraint => {
+                key.1.push(column);
+                key.3.push(ref_column);
+            }
+            _ => {
+                // References within the schema are left unqualified so the
+                // same key compares equal across databases
+                let ref_table = if local {
+                    ref_table
+                } else {
+                    format!("{}.{}", ref_schema, ref_table)
+                };
+                keys.push((
+                    constraint,
+                    vec![column],
+                    ref_table,
+                    vec![ref_column],
+                    on_update,
+                    on_delete,
+                ))
+            }
+        }
+    }
+
+    Ok(keys
+        .into_iter()
+        .map(
+            |(constraint, columns, ref_table, ref_columns, on_update, on_delete)| ConstraintDef {
+                name: constraint,
+                kind: ConstraintKind::ForeignKey,
+                definition: format!(
+                    "FOREIGN KEY ({}) REFERENCES {} ({}) ON UPDATE {} ON DELETE {}",
+                    dialect::column_list(&columns, Engine::Mysql),
+                    dialect::quote_table(&ref_table, Engine::Mysql),
+                    dialect::column_list(&ref_columns, Engine::Mysql),
+                    on_update,
+                    on_delete
+                ),
+                columns,
+            },
+        )
+        .collect())
+}
+
+// CHECK_CONSTRAINTS only exists from MySQL 8.0.16; older servers don't
+// enforce checks, so there are none to report.
+fn mysql_checks(
+    conn: &mut mysql::PooledConn,
+    schema: Option<&str>,
+    name: &str,
+) -> Vec<ConstraintDef> {
+    conn.exec(
+        "SELECT tc.CONSTRAINT_NAME, cc.CHECK_CLAUSE \
+         FROM information_schema.TABLE_CONSTRAINTS tc \
+         JOIN information_schema.CHECK_CONSTRAINTS cc \
+           ON cc.CONSTRAINT_SCHEMA = tc.CONSTRAINT_SCHEMA \
+          AND cc.CONSTRAINT_NAME = tc.CONSTRAINT_NAME \
+         WHERE tc.TABLE_SCHEMA = COALESCE(?, DATABASE()) AND tc.TABLE_NAME = ? \
+           AND tc.CONSTRAINT_TYPE = 'CHECK' \
+         ORDER BY tc.CONSTRAINT_NAME",
+        (schema, name),
+    )
+    .map(|rows: Vec<(String, String)>| {
+        rows.into_iter()
+            .map(|(constraint, clause)| ConstraintDef {
+                name: constraint,
+                kind: ConstraintKind::Check,
+                definition: format!("CHECK ({})", clause),
+                columns: Vec::new(),
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+fn pg_table(
+    client: &mut postgres::Client,
+    schema: &str,
+    name: &str,
+) -> Result<TableSchema, String> {
+    let column_rows = client
+        .query(
+            "SELECT c.column_name::text, c.udt_name::text, format_type(a.atttypid, a.atttypmod), \
+                    c.is_nullable = 'YES', c.column_default::text, \
+                    c.is_identity = 'YES' OR COALESCE(c.column_default LIKE 'nextval(%', false), \
+                    c.character_maximum_length::int8, c.numeric_precision::int8, \
+                    c.numeric_scale::int8, c.datetime_precision::int8 \
+             FROM information_schema.columns c \
+             JOIN pg_catalog.pg_attribute a \
+               ON a.attrelid = (quote_ident(c.table_schema) || '.' || quote_ident(c.table_name))::regclass \
+              AND a.attname = c.column_name \
+             WHERE c.table_schema = $1 AND c.table_name = $2 \
+             ORDER BY c.ordinal_position",
+            &[&schema, &name],
+        )
+        .map_err(|e| e.to_string())?;
+
+    let size = |row: &postgres::Row, i: usize| row.get::<_, Option<i64>>(i).map(|v| v as u64);
+    let columns = column_rows
+        .iter()
+        .map(|row| ColumnDef {
+            name: row.get(0),
+            data_type: row.get(1),
+            column_type: row.get(2),
+            nullable: row.get(3),
+            default: row.get(4),
+            auto_increment: row.get(5),
+            unsigned: false,
+            max_length: size(row, 6),
+            precision: size(row, 7),
+            scale: size(row, 8),
+            datetime_precision: size(row, 9),
+        })
+        .collect();
+
+    // Key columns only (not INCLUDE columns), and no expression or partial
+    // indexes. Indexes backing a unique constraint are left to the
+    // constraint; the primary key's is how the key columns are found.
+    let index_rows = client
+        .query(
+            "SELECT i.relname::text, ix.indisunique, ix.indisprimary, a.attname::text \
+             FROM pg_catalog.pg_index ix \
+             JOIN pg_catalog.pg_class t ON t.oid = ix.indrelid \
+             JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace \
+             JOIN pg_catalog.pg_class i ON i.oid = ix.indexrelid \
+             CROSS JOIN LATERAL unnest(ix.indkey::int2[]) WITH ORDINALITY AS k(attnum, position) \
+             JOIN pg_catalog.pg_attribute a ON a.attrelid = t.oid AND a.attnum = k.attnum \
+             WHERE n.nspname = $1 AND t.relname = $2 \
+               AND ix.indexprs IS NULL AND ix.indpred IS NULL \
+               AND k.position <= ix.indnkeyatts \
+               AND (ix.indisprimary OR NOT EXISTS ( \
+                 SELECT 1 FROM pg_catalog.pg_constraint c WHERE c.conindid = ix.indexrelid)) \
+             ORDER BY i.relname, k.position",
+            &[&schema, &name],
+        )
+        .map_err(|e| e.to_string())?;
+
+    let primary_name: Option<String> = index_rows
+        .iter()
+        .find(|row| row.get::<_, bool>(2))
+        .map(|row| row.get(0));
+    let mut indexes = group_indexes(
+        index_rows
+            .iter()
+            .map(|row| (row.get(0), row.get(1), Some(row.get(3)))),
+    );
+    let primary_key = match primary_name {
+        Some(primary) => indexes
+            .iter()
+            .position(|index| index.name == primary)
+            .map(|pos| indexes.remove(pos).columns)
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+
+    let constraints = client
+        .query(
+            "SELECT c.conname::text, c.contype::text, pg_get_constraintdef(c.oid), \
+                    ARRAY(SELECT a.attname::text \
+                          FROM unnest(c.conkey) WITH ORDINALITY AS k(attnum, position) \
+                          JOIN pg_catalog.pg_attribute a \
+                            ON a.attrelid = c.conrelid AND a.attnum = k.attnum \
+                          WHERE c.contype <> 'c' \
+                          ORDER BY k.position) \
+             FROM pg_catalog.pg_constraint c \
+             JOIN pg_catalog.pg_class t ON t.oid = c.conrelid \
+             JOIN pg_catalog.pg_namespace n ON n.oid = t.relnamespace \
+             WHERE n.nspname = $1 AND t.relname = $2 AND c.contype IN ('p', 'u', 'f', 'c') \
+             ORDER BY c.conname",
+            &[&schema, &name],
+        )
+        .map_err(|e| e.to_string())?
+        .iter()
+        .map(|row| ConstraintDef {
+            name: row.get(0),
+            kind: match row.get::<_, String>(1).as_str() {
+                "p" => ConstraintKind::PrimaryKey,
+                "u" => ConstraintKind::Unique,
+                "f" => ConstraintKind::ForeignKey,
+                _ => ConstraintKind::Check,
+            },
+            definition: row.get(2),
+            columns: row.get(3),
+        })
+        .collect();
+
+    Ok(TableSchema {
+        name: name.to_string(),
+        columns,
+        primary_key,
+        indexes,
+        constraints,
+    })
+}
+
+// Folds (index, unique, column) rows, ordered by index and key position,
+// into one definition per index. An index with a part that isn't a plain
+// column is dropped.
+fn group_indexes(rows: impl Iterator<Item = (String, bool, Option<String>)>) -> Vec<IndexDef> {
+    let mut indexes: Vec<IndexDef> = Vec::new();
+    let mut skipped: Vec<String> = Vec::new();
+
+    for (name, unique, column) in rows {
+        let Some(column) = column else {
+            skipped.push(name);
+            continue;
+        };
+        match indexes.last_mut() {
+            Some(index) if index.name == name => index.columns.push(column),
+            _ => indexes.push(IndexDef {
+                name,
+                columns: vec![column],
+                unique,
+            }),
+        }
+    }
+
+    indexes.retain(|index| !skipped.contains(&index.name));
+    indexes
+}
@@ -0,0 +1,883 @@
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::dialect;
+use crate::engine::{Engine, Session};
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::runtime::run_blocking;
+use crate::schema::{
+    self, ColumnDef, ConstraintDef, ConstraintKind, IndexDef, RoutineDef, TableSchema, ViewDef,
+};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SchemaEndpoint {
+    // Another server; the engine's current connection when absent
+    connection_string: Option<String>,
+    // MySQL database or PostgreSQL schema. Defaults to the selected database,
+    // or the first schema on the search path.
+    schema: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffStatus {
+    // Only in the source
+    Added,
+    // Only in the target
+    Removed,
+    Changed,
+}
+
+#[derive(Serialize)]
+pub struct Change<T> {
+    pub name: String,
+    pub status: DiffStatus,
+    pub source: Option<T>,
+    pub target: Option<T>,
+}
+
+#[derive(Serialize)]
+pub struct TableDiff {
+    pub name: String,
+    pub status: DiffStatus,
+    // The parts below are only listed for changed tables
+    pub columns: Vec<Change<ColumnDef>>,
+    pub indexes: Vec<Change<IndexDef>>,
+    pub constraints: Vec<Change<ConstraintDef>>,
+}
+
+#[derive(Serialize)]
+pub struct SchemaDiff {
+    pub source_schema: String,
+    pub target_schema: String,
+    pub tables: Vec<TableDiff>,
+    pub views: Vec<Change<ViewDef>>,
+    pub routines: Vec<Change<RoutineDef>>,
+    // Statements that bring the target in line with the source, in order
+    pub migration: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+// Everything compared in one schema, with references to the schema itself
+// removed so two schemas of different names compare equal.
+struct Snapshot {
+    schema: String,
+    tables: Vec<TableSchema>,
+    views: Vec<ViewDef>,
+    routines: Vec<RoutineDef>,
+}
+
+// Compares two databases of one engine (two MySQL databases, or two
+// PostgreSQL schemas, on the current connection or on other servers) and
+// returns what differs along with the migration for the target.
+#[tauri::command]
+pub async fn diff_schemas(
+    engine: Engine,
+    source: SchemaEndpoint,
+    target: SchemaEndpoint,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+) -> Result<SchemaDiff, String> {
+    let current = |endpoint: &SchemaEndpoint| match endpoint.connection_string {
+        Some(_) => Ok(None),
+        None => Session::open(engine, &mysql_state, &pg_state).map(Some),
+    };
+    let source_session = current(&source)?;
+    let target_session = current(&target)?;
+
+    run_blocking(move || {
+        let source = read_snapshot(engine, &source, source_session)?;
+        let target = read_snapshot(engine, &target, target_session)?;
+        Ok(diff(engine, &source, &target))
+    })
+    .await
+}
+
+fn read_snapshot(
+    engine: Engine,
+    endpoint: &SchemaEndpoint,
+    session: Option<Session>,
+) -> Result<Snapshot, String> {
+    let session = match (session, endpoint.connection_string.as_deref()) {
+        (Some(session), _) => session,
+        (None, Some(connection_string)) => {
+            Session::from_connection_string(engine, connection_string, endpoint.schema.as_deref())?
+        }
+        (None, None) => return Err("Database not connected".to_string()),
+    };
+    let mut conn = session.connect()?;
+    let schema = match &endpoint.schema {
+        Some(schema) => schema.clone(),
+        None => schema::current_schema(&mut conn)?,
+    };
+
+    let mut tables = Vec::new();
+    for table in schema::list_tables(&mut conn, &schema)? {
+        let mut table = schema::table_schema(&mut conn, &format!("{}.{}", schema, table))?;
+        for column in &mut table.columns {
+            column.column_type = strip_schema(&column.column_type, &schema, engine);
+            column.default = column
+                .default
+                .as_deref()
+                .map(|d| strip_schema(d, &schema, engine));
+        }
+        for constraint in &mut table.constraints {
+            constraint.definition = strip_schema(&constraint.definition, &schema, engine);
+        }
+        tables.push(table);
+    }
+
+    let views = schema::list_views(&mut conn, &schema)?
+        .into_iter()
+        .map(|view| ViewDef {
+            definition: strip_schema(&view.definition, &schema, engine),
+            ..view
+        })
+        .collect();
+    let routines = schema::list_routines(&mut conn, &schema)?
+        .into_iter()
+        .map(|routine| {
+            let definition = match engine {
+                Engine::Mysql => strip_definer(&routine.definition),
+                Engine::Postgres => routine.definition.clone(),
+            };
+            RoutineDef {
+                definition: strip_schema(&definition, &schema, engine),
+                ..routine
+            }
+        })
+        .collect();
+
+    Ok(Snapshot {
+        schema,
+        tables,
+        views,
+        routines,
+    })
+}
+
+fn diff(engine: Engine, source: &Snapshot, target: &Snapshot) -> SchemaDiff {
+    let mut migration = Migration::default();
+    let mut warnings = Vec::new();
+
+    // Added and removed tables; those in both are compared below
+    let table_changes = diff_items(
+        &source.tables,
+        &target.tables,
+        |t| t.name.clone(),
+        |_, _| true,
+    );
+    let mut tables = Vec::new();
+    for change in table_changes {
+        match (&change.source, &change.target) {
+            (Some(table), None) => migration.create_table(table, engine),
+            (None, Some(table)) => migration.table_drops.push(format!(
+                "DROP TABLE {}",
+                dialect::quote_ident(&table.name, engine)
+            )),
+            _ => {}
+        }
+        tables.push(TableDiff {
+            name: change.name,
+            status: change.status,
+            columns: Vec::new(),
+            indexes: Vec::new(),
+            constraints: Vec::new(),
+        });
+    }
+
+    for table in &source.tables {
+        let Some(other) = target.tables.iter().find(|t| t.name == table.name) else {
+            continue;
+        };
+        let columns = diff_items(
+            &table.columns,
+            &other.columns,
+            |c| c.name.clone(),
+            PartialEq::eq,
+        );
+        let indexes = diff_items(
+            &table.indexes,
+            &other.indexes,
+            |i| i.name.clone(),
+            PartialEq::eq,
+        );
+        let constraints = diff_items(
+            &table.constraints,
+            &other.constraints,
+            |c| c.name.clone(),
+            PartialEq::eq,
+        );
+        if columns.is_empty() && indexes.is_empty() && constraints.is_empty() {
+            continue;
+        }
+
+        migration.alter_table(&table.name, &columns, &indexes, &constraints, engine);
+        tables.push(TableDiff {
+            name: table.name.clone(),
+            status: DiffStatus::Changed,
+            columns,
+            indexes,
+            constraints,
+        });
+    }
+
+    let views = diff_items(
+        &source.views,
+        &target.views,
+        |v| v.name.clone(),
+        PartialEq::eq,
+    );
+    for change in &views {
+        if let Some(view) = &change.target {
+            migration.object_drops.push(format!(
+                "DROP VIEW {}",
+                dialect::quote_ident(&view.name, engine)
+            ));
+        }
+        match &change.source {
+            Some(view) if view.definition.is_empty() => warnings.push(format!(
+                "The definition of view {} isn't visible to this user",
+                view.name
+            )),
+            Some(view) => migration.objects.push(format!(
+                "CREATE VIEW {} AS {}",
+                dialect::quote_ident(&view.name, engine),
+                view.definition
+            )),
+            None => {}
+        }
+    }
+
+    let routines = diff_items(
+        &source.routines,
+        &target.routines,
+        routine_key,
+        PartialEq::eq,
+    );
+    for change in &routines {
+        if let Some(routine) = &change.target {
+            let arguments = match engine {
+                Engine::Mysql => String::new(),
+                Engine::Postgres => format!("({})", routine.arguments),
+            };
+            migration.object_drops.push(format!(
+                "DROP {} {}{}",
+                routine.kind.to_uppercase(),
+                dialect::quote_ident(&routine.name, engine),
+                arguments
+            ));
+        }
+        match &change.source {
+            Some(routine) if routine.definition.is_empty() => warnings.push(format!(
+                "The definition of {} {} isn't visible to this user",
+                routine.kind, routine.name
+            )),
+            Some(routine) => migration.objects.push(routine.definition.clone()),
+            None => {}
+        }
+    }
+
+    let prelude = match engine {
+        Engine::Mysql => format!("USE {}", dialect::quote_ident(&target.schema, engine)),
+        Engine::Postgres => format!(
+            "SET search_path TO {}",
+            dialect::quote_ident(&target.schema, engine)
+        ),
+    };
+
+    SchemaDiff {
+        source_schema: source.schema.clone(),
+        target_schema: target.schema.clone(),
+        tables,
+        views,
+        routines,
+        migration: migration.statements(prelude),
+        warnings,
+    }
+}
+
+// Items of `source` missing from or different in `target`, then those only
+// in `target`. `same` decides whether two items of one name match.
+fn diff_items<T: Clone>(
+    source: &[T],
+    target: &[T],
+    key: impl Fn(&T) -> String,
+    same: impl Fn(&T, &T) -> bool,
+) -> Vec<Change<T>> {
+    let mut changes = Vec::new();
+    for item in source {
+        let name = key(item);
+        let status = match target.iter().find(|other| key(other) == name) {
+            None => DiffStatus::Added,
+            Some(other) if !same(item, other) => DiffStatus::Changed,
+            Some(_) => continue,
+        };
+        changes.push(Change {
+            target: target.iter().find(|other| key(other) == name).cloned(),
+            source: Some(item.clone()),
+            name,
+            status,
+        });
+    }
+    for item in target {
+        let name = key(item);
+        if !source.iter().any(|other| key(other) == name) {
+            changes.push(Change {
+                name,
+                status: DiffStatus::Removed,
+                source: None,
+                target: Some(item.clone()),
+            });
+        }
+    }
+    changes
+}
+
+// PostgreSQL functions are overloaded by argument types
+fn routine_key(routine: &RoutineDef) -> String {
+    format!("{} {}({})", routine.kind, routine.name, routine.arguments)
+}
+
+// Statements grouped by phase, so that dependents are dropped before what
+// they depend on and created after it.
+#[derive(Default)]
+struct Migration {
+    object_drops: Vec<String>,
+    foreign_key_drops: Vec<String>,
+    key_drops: Vec<String>,
+    table_drops: Vec<String>,
+    tables: Vec<String>,
+    keys: Vec<String>,
+    foreign_keys: Vec<String>,
+    objects: Vec<String>,
+}
+
+impl Migration {
+    fn create_table(&mut self, table: &TableSchema, engine: Engine) {
+        let quoted = dialect::quote_ident(&table.name, engine);
+        let mut definitions: Vec<String> = table
+            .columns
+            .iter()
+            .map(|column| format!("  {}", column_definition(column, engine)))
+            .collect();
+
+        for constraint in &table.constraints {
+            if constraint.kind == ConstraintKind::ForeignKey {
+                // Added once every table exists
+                self.foreign_keys
+                    .push(add_constraint(&quoted, constraint, engine));
+            } else if engine == Engine::Mysql && constraint.kind == ConstraintKind::PrimaryKey {
+                definitions.push(format!("  {}", constraint.definition));
+            } else {
+                definitions.push(format!(
+                    "  CONSTRAINT {} {}",
+                    dialect::quote_ident(&constraint.name, engine),
+                    constraint.definition
+                ));
+            }
+        }
+        self.tables.push(format!(
+            "CREATE TABLE {} (\n{}\n)",
+            quoted,
+            definitions.join(",\n")
+        ));
+
+        for index in &table.indexes {
+            self.keys.push(create_index(&quoted, index, engine));
+        }
+    }
+
+    fn alter_table(
+        &mut self,
+        table: &str,
+        columns: &[Change<ColumnDef>],
+        indexes: &[Change<IndexDef>],
+        constraints: &[Change<ConstraintDef>],
+        engine: Engine,
+    ) {
+        let quoted = dialect::quote_ident(table, engine);
+
+        for change in constraints {
+            if let Some(constraint) = &change.target {
+                let statement = drop_constraint(&quoted, constraint, engine);
+                if constraint.kind == ConstraintKind::ForeignKey {
+                    self.foreign_key_drops.push(statement);
+                } else {
+                    self.key_drops.push(statement);
+                }
+            }
+            if let Some(constraint) = &change.source {
+                let statement = add_constraint(&quoted, constraint, engine);
+                if constraint.kind == ConstraintKind::ForeignKey {
+                    self.foreign_keys.push(statement);
+                } else {
+                    self.keys.push(statement);
+                }
+            }
+        }
+
+        for change in indexes {
+            if let Some(index) = &change.target {
+                self.key_drops.push(match engine {
+                    Engine::Mysql => format!(
+                        "DROP INDEX {} ON {}",
+                        dialect::quote_ident(&index.name, engine),
+                        quoted
+                    ),
+                    Engine::Postgres => {
+                        format!("DROP INDEX {}", dialect::quote_ident(&index.name, engine))
+                    }
+                });
+            }
+            if let Some(index) = &change.source {
+                self.keys.push(create_index(&quoted, index, engine));
+            }
+        }
+
+        for change in columns {
+            match (&change.source, &change.target) {
+                (Some(column), None) => self.tables.push(format!(
+                    "ALTER TABLE {} ADD COLUMN {}",
+                    quoted,
+                    column_definition(column, engine)
+                )),
+                (None, Some(column)) => self.tables.push(format!(
+                    "ALTER TABLE {} DROP COLUMN {}",
+                    quoted,
+                    dialect::quote_ident(&column.name, engine)
+                )),
+                (Some(column), Some(old)) => match engine {
+                    Engine::Mysql => self.tables.push(format!(
+                        "ALTER TABLE {} MODIFY COLUMN {}",
+                        quoted,
+                        column_definition(column, engine)
+                    )),
+                    Engine::Postgres => self.alter_pg_column(&quoted, column, old),
+                },
+                (None, None) => {}
+            }
+        }
+    }
+
+    // PostgreSQL changes a column one property at a time.
+    fn alter_pg_column(&mut self, table: &str, column: &ColumnDef, old: &ColumnDef) {
+        let name = dialect::quote_ident(&column.name, Engine::Postgres);
+        let alter = format!("ALTER TABLE {} ALTER COLUMN {}", table, name);
+
+        if column.column_type != old.column_type {
+            self.tables.push(format!(
+                "{} TYPE {} USING {}::{}",
+                alter, column.column_type, name, column.column_type
+            ));
+        }
+        if column.nullable != old.nullable {
+            let action = if column.nullable { "DROP" } else { "SET" };
+            self.tables.push(format!("{} {} NOT NULL", alter, action));
+        }
+        if column.auto_increment != old.auto_increment {
+            self.tables.push(if column.auto_increment {
+                format!("{} ADD GENERATED BY DEFAULT AS IDENTITY", alter)
+            } else {
+                format!("{} DROP IDENTITY IF EXISTS", alter)
+            });
+        } else if !column.auto_increment && column.default != old.default {
+            self.tables.push(match &column.default {
+                Some(default) => format!("{} SET DEFAULT {}", alter, default),
+                None => format!("{} DROP DEFAULT", alter),
+            });
+        }
+    }
+
+    fn statements(self, prelude: String) -> Vec<String> {
+        let phases = [
+            self.object_drops,
+            self.foreign_key_drops,
+            self.key_drops,
+            self.table_drops,
+            self.tables,
+            self.keys,
+            self.foreign_keys,
+            self.objects,
+        ];
+        if phases.iter().all(Vec::is_empty) {
+            return Vec::new();
+        }
+        std::iter::once(prelude)
+            .chain(phases.into_iter().flatten())
+            .collect()
+    }
+}
+
+fn column_definition(column: &ColumnDef, engine: Engine) -> String {
+    let mut definition = format!(
+        "{} {}",
+        dialect::quote_ident(&column.name, engine),
+        column.column_type
+    );
+    if !column.nullable {
+        definition.push_str(" NOT NULL");
+    }
+    match engine {
+        // Serial columns become identity columns, which need no sequence of
+        // their own to exist first
+        Engine::Postgres if column.auto_increment => {
+            definition.push_str(" GENERATED BY DEFAULT AS IDENTITY");
+            return definition;
+        }
+        Engine::Mysql if column.auto_increment => {
+            definition.push_str(" AUTO_INCREMENT");
+            return definition;
+        }
+        _ => {}
+    }
+    if let Some(default) = &column.default {
+        definition.push_str(&format!(" DEFAULT {}", default));
+    }
+    definition
+}
+
+fn create_index(table: &str, index: &IndexDef, engine: Engine) -> String {
+    format!(
+        "CREATE {}INDEX {} ON {} ({})",
+        if index.unique { "UNIQUE " } else { "" },
+        dialect::quote_ident(&index.name, engine),
+        table,
+        dialect::column_list(&index.columns, engine)
+    )
+}
+
+fn add_constraint(table: &str, constraint: &ConstraintDef, engine: Engine) -> String {
+    if engine == Engine::Mysql && constraint.kind == ConstraintKind::PrimaryKey {
+        return format!("ALTER TABLE {} ADD {}", table, constraint.definition);
+    }
+    format!(
+        "ALTER TABLE {} ADD CONSTRAINT {} {}",
+        table,
+        dialect::quote_ident(&constraint.name, engine),
+        constraint.definition
+    )
+}
+
+fn drop_constraint(table: &str, constraint: &ConstraintDef, engine: Engine) -> String {
+    let name = dialect::quote_ident(&constraint.name, engine);
+    let action = match (engine, constraint.kind) {
+        (Engine::Postgres, _) => format!("DROP CONSTRAINT {}", name),
+        (Engine::Mysql, ConstraintKind::PrimaryKey) => "DROP PRIMARY KEY".to_string(),
+        (Engine::Mysql, ConstraintKind::ForeignKey) => format!("DROP FOREIGN KEY {}", name),
+        (Engine::Mysql, ConstraintKind::Check) => format!("DROP CHECK {}", name),
+        (Engine::Mysql, ConstraintKind::Unique) => format!("DROP INDEX {}", name),
+    };
+    format!("ALTER TABLE {} {}", table, action)
+}
+
+// Removes `schema.` qualifiers, quoted or not, so definitions read from two
+// schemas compare equal and the migration applies to the target schema.
+fn strip_schema(text: &str, schema: &str, engine: Engine) -> String {
+    let quoted = format!("{}.", dialect::quote_ident(schema, engine));
+    let plain = format!("{}.", schema);
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(c) = rest.chars().next() {
+        let at_boundary = !out
+            .chars()
+            .next_back()
+            .is_some_and(|prev| prev.is_alphanumeric() || prev == '_' || prev == '$');
+        if at_boundary {
+            if let Some(after) = rest
+                .strip_prefix(quoted.as_str())
+                .or_else(|| rest.strip_prefix(plain.as_str()))
+            {
+                rest = after;
+                continue;
+            }
+        }
+        out.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    out
+}
+
+// `CREATE DEFINER=`user`@`host` PROCEDURE ...` names the account that
+// created the routine, which differs between servers.
+fn strip_definer(definition: &str) -> String {
+    let Some(start) = definition.find(" DEFINER=") else {
+        return definition.to_string();
+    };
+    let rest = &definition[start + 1..];
+    match rest.find(' ') {
+        Some(end) => format!("{}{}", &definition[..start], &rest[end..]),
+        None => definition.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(name: &str, column_type: &str, nullable: bool) -> ColumnDef {
+        ColumnDef {
+            name: name.to_string(),
+            data_type: column_type.to_string(),
+            column_type: column_type.to_string(),
+            nullable,
+            default: None,
+            auto_increment: false,
+            unsigned: false,
+            max_length: None,
+            precision: None,
+            scale: None,
+            datetime_precision: None,
+        }
+    }
+
+    fn serial(name: &str) -> ColumnDef {
+        ColumnDef {
+            auto_increment: true,
+            ..column(name, "int4", false)
+        }
+    }
+
+    fn index(name: &str, columns: &[&str]) -> IndexDef {
+        IndexDef {
+            name: name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            unique: false,
+        }
+    }
+
+    fn constraint(name: &str, kind: ConstraintKind, definition: &str) -> ConstraintDef {
+        ConstraintDef {
+            name: name.to_string(),
+            kind,
+            definition: definition.to_string(),
+            columns: Vec::new(),
+        }
+    }
+
+    fn table(name: &str, columns: Vec<ColumnDef>) -> TableSchema {
+        TableSchema {
+            name: name.to_string(),
+            columns,
+            primary_key: Vec::new(),
+            indexes: Vec::new(),
+            constraints: Vec::new(),
+        }
+    }
+
+    fn snapshot(schema: &str, tables: Vec<TableSchema>) -> Snapshot {
+        Snapshot {
+            schema: schema.to_string(),
+            tables,
+            views: Vec::new(),
+            routines: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn strips_schema_qualifiers() {
+        assert_eq!(
+            strip_schema(
+                "nextval('app.users_id_seq'::regclass)",
+                "app",
+                Engine::Postgres
+            ),
+            "nextval('users_id_seq'::regclass)"
+        );
+        assert_eq!(
+            strip_schema(
+                "FOREIGN KEY (user_id) REFERENCES \"app\".users(id)",
+                "app",
+                Engine::Postgres
+            ),
+            "FOREIGN KEY (user_id) REFERENCES users(id)"
+        );
+        assert_eq!(
+            strip_schema("select `app`.`t`.`a` from `app`.`t`", "app", Engine::Mysql),
+            "select `t`.`a` from `t`"
+        );
+        // Only whole qualifiers, not names that end in the schema's name
+        assert_eq!(
+            strip_schema("myapp.t, app_2.t, $app.t", "app", Engine::Postgres),
+            "myapp.t, app_2.t, $app.t"
+        );
+    }
+
+    #[test]
+    fn strips_definers() {
+        assert_eq!(
+            strip_definer("CREATE DEFINER=`root`@`%` PROCEDURE `p`() BEGIN END"),
+            "CREATE PROCEDURE `p`() BEGIN END"
+        );
+        assert_eq!(
+            strip_definer("CREATE PROCEDURE `p`() BEGIN END"),
+            "CREATE PROCEDURE `p`() BEGIN END"
+        );
+    }
+
+    #[test]
+    fn migrates_postgres_tables_in_phase_order() {
+        let mut users = table(
+            "users",
+            vec![serial("id"), column("name", "varchar(100)", false)],
+        );
+        users.indexes = vec![index("users_name_idx", &["name"])];
+        users.constraints = vec![constraint(
+            "users_pkey",
+            ConstraintKind::PrimaryKey,
+            "PRIMARY KEY (id)",
+        )];
+        let mut orders = table(
+            "orders",
+            vec![serial("id"), column("user_id", "int4", true)],
+        );
+        orders.constraints = vec![constraint(
+            "orders_user_id_fkey",
+            ConstraintKind::ForeignKey,
+            "FOREIGN KEY (user_id) REFERENCES users(id)",
+        )];
+
+        let mut old_users = table(
+            "users",
+            vec![
+                serial("id"),
+                column("name", "text", true),
+                column("old", "text", true),
+            ],
+        );
+        old_users.indexes = vec![index("users_name_idx", &["name", "id"])];
+        old_users.constraints = users.constraints.clone();
+        let legacy = table("legacy", vec![serial("id")]);
+
+        let source = snapshot("app", vec![users, orders]);
+        let target = snapshot("staging", vec![old_users, legacy]);
+        let diff = diff(Engine::Postgres, &source, &target);
+
+        let tables: Vec<(&str, DiffStatus)> = diff
+            .tables
+            .iter()
+            .map(|t| (t.name.as_str(), t.status))
+            .collect();
+        assert!(
+            tables
+                == [
+                    ("orders", DiffStatus::Added),
+                    ("legacy", DiffStatus::Removed),
+                    ("users", DiffStatus::Changed),
+                ]
+        );
+        let users = &diff.tables[2];
+        let columns: Vec<(&str, DiffStatus)> = users
+            .columns
+            .iter()
+            .map(|c| (c.name.as_str(), c.status))
+            .collect();
+        assert!(columns == [("name", DiffStatus::Changed), ("old", DiffStatus::Removed)]);
+        assert_eq!(users.indexes.len(), 1);
+        assert!(users.constraints.is_empty());
+
+        assert_eq!(
+            diff.migration,
+            [
+                "SET search_path TO \"staging\"",
+                "DROP INDEX \"users_name_idx\"",
+                "DROP TABLE \"legacy\"",
+                "CREATE TABLE \"orders\" (\n  \"id\" int4 NOT NULL GENERATED BY DEFAULT AS IDENTITY,\n  \"user_id\" int4\n)",
+                "ALTER TABLE \"users\" ALTER COLUMN \"name\" TYPE varchar(100) USING \"name\"::varchar(100)",
+                "ALTER TABLE \"users\" ALTER COLUMN \"name\" SET NOT NULL",
+                "ALTER TABLE \"users\" DROP COLUMN \"old\"",
+                "CREATE INDEX \"users_name_idx\" ON \"users\" (\"name\")",
+                "ALTER TABLE \"orders\" ADD CONSTRAINT \"orders_user_id_fkey\" FOREIGN KEY (user_id) REFERENCES users(id)",
+            ]
+        );
+    }
+
+    #[test]
+    fn migrates_mysql_keys_around_column_changes() {
+        let fk =
+            |definition: &str| constraint("orders_user_fk", ConstraintKind::ForeignKey, definition);
+        let mut orders = table(
+            "orders",
+            vec![
+                column("id", "int", false),
+                column("user_id", "bigint", true),
+            ],
+        );
+        orders.constraints = vec![fk(
+            "FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE",
+        )];
+        let mut old_orders = table(
+            "orders",
+            vec![column("id", "int", false), column("user_id", "int", true)],
+        );
+        old_orders.indexes = vec![index("orders_note_idx", &["note"])];
+        old_orders.constraints = vec![fk("FOREIGN KEY (`user_id`) REFERENCES `users` (`id`)")];
+
+        let diff = diff(
+            Engine::Mysql,
+            &snapshot("app", vec![orders]),
+            &snapshot("staging", vec![old_orders]),
+        );
+        assert_eq!(
+            diff.migration,
+            [
+                "USE `staging`",
+                "ALTER TABLE `orders` DROP FOREIGN KEY `orders_user_fk`",
+                "DROP INDEX `orders_note_idx` ON `orders`",
+                "ALTER TABLE `orders` MODIFY COLUMN `user_id` bigint",
+                "ALTER TABLE `orders` ADD CONSTRAINT `orders_user_fk` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`) ON DELETE CASCADE",
+            ]
+        );
+    }
+
+    #[test]
+    fn recreates_changed_views_and_routines() {
+        let view = |name: &str, definition: &str| ViewDef {
+            name: name.to_string(),
+            definition: definition.to_string(),
+        };
+        let routine = |arguments: &str, definition: &str| RoutineDef {
+            name: "total".to_string(),
+            kind: "function".to_string(),
+            arguments: arguments.to_string(),
+            definition: definition.to_string(),
+        };
+        let mut source = snapshot("app", Vec::new());
+        source.views = vec![view("active", "SELECT 1"), view("hidden", "")];
+        source.routines = vec![routine("integer", "CREATE FUNCTION total(integer) ...")];
+        let mut target = snapshot("app", Vec::new());
+        target.views = vec![view("active", "SELECT 2")];
+        target.routines = vec![routine("bigint", "CREATE FUNCTION total(bigint) ...")];
+
+        let diff = diff(Engine::Postgres, &source, &target);
+        assert_eq!(
+            diff.migration,
+            [
+                "SET search_path TO \"app\"",
+                "DROP VIEW \"active\"",
+                "DROP FUNCTION \"total\"(bigint)",
+                "CREATE VIEW \"active\" AS SELECT 1",
+                "CREATE FUNCTION total(integer) ...",
+            ]
+        );
+        assert_eq!(
+            diff.warnings,
+            ["The definition of view hidden isn't visible to this user"]
+        );
+    }
+
+    #[test]
+    fn leaves_identical_schemas_alone() {
+        let users = table("users", vec![serial("id")]);
+        let diff = diff(
+            Engine::Mysql,
+            &snapshot("app", vec![users.clone()]),
+            &snapshot("staging", vec![users]),
+        );
+        assert!(diff.tables.is_empty());
+        assert!(diff.migration.is_empty());
+    }
+}
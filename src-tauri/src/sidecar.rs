@@ -0,0 +1,548 @@
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Stdio};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use tauri::{async_runtime, AppHandle, Emitter, Manager, State};
+
+use crate::pythonjobs::VenvJobs;
+use crate::pythonmanager::{
+    command, scripts_dir, terminate_process_tree, venv_dir, venv_python, wait_for_exit,
+};
+use crate::sidecarlog::{LogStream, SidecarLogStore};
+
+// Loading torch alone can take half a minute on a cold start
+const READY_TIMEOUT: Duration = Duration::from_secs(120);
+const PROBE_INTERVAL: Duration = Duration::from_millis(500);
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const MONITOR_INTERVAL: Duration = Duration::from_millis(500);
+
+// How long the service gets to exit after the shutdown request before its
+// process group is terminated
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+
+// Restarts wait 1s, 2s, 4s... up to MAX_BACKOFF. After MAX_FAILURES crashes
+// in a row the supervisor gives up; a run that stayed ready for STABLE_AFTER
+// starts the count again.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_FAILURES: u32 = 5;
+const STABLE_AFTER: Duration = Duration::from_secs(60);
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SidecarState {
+    Starting,
+    Ready,
+    Crashed,
+    Stopped,
+}
+
+#[derive(Serialize, Clone)]
+pub struct SidecarStatus {
+    pub state: SidecarState,
+    pub pid: Option<u32>,
+    // Restarts since the service was started
+    pub restarts: u32,
+    // Whether the supervisor will restart it after a crash
+    pub supervised: bool,
+    pub last_error: Option<String>,
+    // The port the current process listens on. It is picked afresh for
+    // every (re)start.
+    pub port: Option<u16>,
+}
+
+// Where the frontend reaches the service. Every request needs the token as
+// `Authorization: Bearer <token>`.
+#[derive(Serialize, Clone)]
+pub struct Endpoint {
+    pub url: String,
+    pub token: String,
+}
+
+struct Inner {
+    status: SidecarStatus,
+    child: Option<Child>,
+    // Generated for each start; main.py rejects requests without it
+    token: String,
+    // Bumped by every start and stop, so a supervisor thread can tell it
+    // has been replaced
+    run: u64,
+}
+
+// The SQLCoder service (scripts/main.py) and the thread supervising it.
+// State changes are emitted on `sidecar-state`.
+pub struct Sidecar {
+    inner: Mutex<Inner>,
+    changed: Condvar,
+}
+
+enum Exit {
+    Running,
+    Exited(String),
+    // Stopped or restarted by someone else
+    Replaced,
+}
+
+impl Sidecar {
+    pub fn new() -> Self {
+        Sidecar {
+            inner: Mutex::new(Inner {
+                status: SidecarStatus {
+                    state: SidecarState::Stopped,
+                    pid: None,
+                    restarts: 0,
+                    supervised: false,
+                    last_error: None,
+                    port: None,
+                },
+                child: None,
+                token: String::new(),
+                run: 0,
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Inner>, String> {
+        self.inner.lock().map_err(|e| e.to_string())
+    }
+
+    pub fn status(&self) -> Result<SidecarStatus, String> {
+        Ok(self.lock()?.status.clone())
+    }
+
+    // Updates the status of `run` and announces it. Returns false when
+    // the run has been replaced, leaving the status alone.
+    fn update(&self, app: &AppHandle, run: u64, f: impl FnOnce(&mut Inner)) -> bool {
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        if inner.run != run {
+            return false;
+        }
+        f(&mut inner);
+        let _ = app.emit("sidecar-state", inner.status.clone());
+        self.changed.notify_all();
+        true
+    }
+
+    // The service's address and token, once it is ready.
+    pub fn endpoint(&self) -> Result<Endpoint, String> {
+        let inner = self.lock()?;
+        match inner.status.port {
+            Some(port) if inner.status.state == SidecarState::Ready => Ok(Endpoint {
+                url: format!("http://127.0.0.1:{}", port),
+                token: inner.token.clone(),
+            }),
+            _ => Err("FastAPI SQLCoder service is not running".to_string()),
+        }
+    }
+
+    pub fn start(&self, app: &AppHandle) -> Result<(), String> {
+        let mut inner = self.lock()?;
+        if inner.status.supervised {
+            return Err("Python process is already running".to_string());
+        }
+        inner.token = new_token()?;
+        inner.run += 1;
+        inner.status = SidecarStatus {
+            state: SidecarState::Starting,
+            pid: None,
+            restarts: 0,
+            supervised: true,
+            last_error: None,
+            port: None,
+        };
+        let run = inner.run;
+        let _ = app.emit("sidecar-state", inner.status.clone());
+        drop(inner);
+
+        let app = app.clone();
+        thread::spawn(move || app.state::<Sidecar>().supervise(&app, run));
+        Ok(())
+    }
+
+    // Stops the supervisor and the process tree. Returns whether the
+    // service exited on its own, or None when nothing was running.
+    pub fn stop(&self, app: &AppHandle) -> Result<Option<bool>, String> {
+        let mut inner = self.lock()?;
+        if !inner.status.supervised && inner.child.is_none() {
+            return Ok(None);
+        }
+        inner.run += 1;
+        let child = inner.child.take();
+        let port = inner.status.port;
+        let token = inner.token.clone();
+        drop(inner);
+        // Wakes a supervisor waiting out its backoff
+        self.changed.notify_all();
+
+        let exited = match child {
+            Some(mut child) => shut_down(&mut child, port, &token)?,
+            None => true,
+        };
+
+        log(
+            app,
+            if exited {
+                "Service stopped"
+            } else {
+                "Service killed after not stopping in time"
+            },
+        );
+
+        let mut inner = self.lock()?;
+        inner.status.state = SidecarState::Stopped;
+        inner.status.pid = None;
+        inner.status.port = None;
+        inner.status.supervised = false;
+        let _ = app.emit("sidecar-state", inner.status.clone());
+        self.changed.notify_all();
+        Ok(Some(exited))
+    }
+
+    // Waits until the service leaves the starting state.
+    pub fn wait_started(&self, timeout: Duration) -> Result<SidecarStatus, String> {
+        let inner = self.lock()?;
+        let (inner, _) = self
+            .changed
+            .wait_timeout_while(inner, timeout, |inner| {
+                inner.status.state == SidecarState::Starting
+            })
+            .map_err(|e| e.to_string())?;
+        Ok(inner.status.clone())
+    }
+
+    fn supervise(&self, app: &AppHandle, run: u64) {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut failures = 0;
+        loop {
+            let Some((error, ready_for)) = self.run_once(app, run) else {
+                return;
+            };
+
+            if ready_for.is_some_and(|ready_for| ready_for >= STABLE_AFTER) {
+                failures = 0;
+                backoff = INITIAL_BACKOFF;
+            }
+            failures += 1;
+            let give_up = failures > MAX_FAILURES;
+            if give_up {
+                log(
+                    app,
+                    &format!("{}; giving up after {} attempts", error, failures),
+                );
+            } else {
+                log(
+                    app,
+                    &format!("{}; restarting in {}s", error, backoff.as_secs()),
+                );
+            }
+            let updated = self.update(app, run, |inner| {
+                inner.child = None;
+                inner.status.state = SidecarState::Crashed;
+                inner.status.pid = None;
+                inner.status.port = None;
+                inner.status.supervised = !give_up;
+                inner.status.last_error = Some(if give_up {
+                    format!("{} (gave up after {} attempts)", error, failures)
+                } else {
+                    error
+                });
+            });
+            if !updated || give_up {
+                return;
+            }
+
+            // Waits out the backoff unless stopped in the meantime
+            let Ok(inner) = self.inner.lock() else {
+                return;
+            };
+            let Ok((inner, _)) = self
+                .changed
+                .wait_timeout_while(inner, backoff, |inner| inner.run == run)
+            else {
+                return;
+            };
+            if inner.run != run {
+                return;
+            }
+            drop(inner);
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+
+            if !self.update(app, run, |inner| {
+                inner.status.restarts += 1;
+                inner.status.state = SidecarState::Starting;
+            }) {
+                return;
+            }
+        }
+    }
+
+    // Starts the service and follows it until it exits. Returns why it
+    // stopped and how long it had been ready for, or None once the run has
+    // been replaced.
+    fn run_once(&self, app: &AppHandle, run: u64) -> Option<(String, Option<Duration>)> {
+        let token = self.inner.lock().ok()?.token.clone();
+        let spawned = free_port().and_then(|port| Ok((port, spawn_sidecar(app, port, &token)?)));
+        let (port, mut child) = match spawned {
+            Ok(spawned) => spawned,
+            Err(e) => return Some((e, None)),
+        };
+        log(
+            app,
+            &format!("Service started (pid {}, port {})", child.id(), port),
+        );
+        {
+            let mut inner = self.inner.lock().ok()?;
+            if inner.run != run {
+                // Stopped while spawning; nothing else knows about this child
+                drop(inner);
+                let _ = terminate_process_tree(&mut child);
+                return None;
+            }
+            inner.status.pid = Some(child.id());
+            inner.status.port = Some(port);
+            inner.status.state = SidecarState::Starting;
+            inner.child = Some(child);
+            let _ = app.emit("sidecar-state", inner.status.clone());
+            self.changed.notify_all();
+        }
+
+        let started = Instant::now();
+        loop {
+            match self.check_exit(run) {
+                Exit::Running => {}
+                Exit::Exited(error) => return Some((error, None)),
+                Exit::Replaced => return None,
+            }
+            if probe(port, &token) {
+                break;
+            }
+            if started.elapsed() >= READY_TIMEOUT {
+                self.kill(run);
+                return Some((
+                    format!(
+                        "Service did not become ready within {}s",
+                        READY_TIMEOUT.as_secs()
+                    ),
+                    None,
+                ));
+            }
+            thread::sleep(PROBE_INTERVAL);
+        }
+
+        if !self.update(app, run, |inner| {
+            inner.status.state = SidecarState::Ready;
+            inner.status.last_error = None;
+        }) {
+            return None;
+        }
+        log(app, "Service is ready");
+        let ready_at = Instant::now();
+        loop {
+            thread::sleep(MONITOR_INTERVAL);
+            match self.check_exit(run) {
+                Exit::Running => {}
+                Exit::Exited(error) => return Some((error, Some(ready_at.elapsed()))),
+                Exit::Replaced => return None,
+            }
+        }
+    }
+
+    fn check_exit(&self, run: u64) -> Exit {
+        let Ok(mut inner) = self.inner.lock() else {
+            return Exit::Replaced;
+        };
+        if inner.run != run {
+            return Exit::Replaced;
+        }
+        let Some(child) = inner.child.as_mut() else {
+            return Exit::Exited("Service was killed".to_string());
+        };
+        match child.try_wait() {
+            Ok(Some(status)) => Exit::Exited(format!("Service exited with {}", status)),
+            Ok(None) => Exit::Running,
+            Err(e) => Exit::Exited(format!("Error checking Python process status: {}", e)),
+        }
+    }
+
+    fn kill(&self, run: u64) {
+        let child = match self.inner.lock() {
+            Ok(mut inner) if inner.run == run => inner.child.take(),
+            _ => None,
+        };
+        if let Some(mut child) = child {
+            let _ = terminate_process_tree(&mut child);
+        }
+    }
+}
+
+// Asks the service to exit: a shutdown request, and its stdin closed for
+// good measure. Whatever is still running after SHUTDOWN_GRACE is
+// terminated with the rest of its process group. Returns whether it exited
+// without being killed.
+fn shut_down(child: &mut Child, port: Option<u16>, token: &str) -> Result<bool, String> {
+    if let Some(port) = port {
+        post(port, token, "/shutdown");
+    }
+    drop(child.stdin.take());
+    if wait_for_exit(child, SHUTDOWN_GRACE)? {
+        return Ok(true);
+    }
+    terminate_process_tree(child)
+}
+
+// Stops the service and any virtualenv job on the way out, so no Python
+// process outlives the app.
+pub fn shutdown(app: &AppHandle) {
+    if let Err(e) = app.state::<Sidecar>().stop(app) {
+        log(app, &format!("Failed to stop the SQLCoder service: {}", e));
+    }
+    if let Err(e) = app.state::<VenvJobs>().cancel() {
+        log(
+            app,
+            &format!("Failed to stop the virtual environment job: {}", e),
+        );
+    }
+}
+
+// A port nobody is listening on right now. The service could still lose it
+// before binding, in which case it crashes and the restart picks another.
+fn free_port() -> Result<u16, String> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .map_err(|e| format!("Failed to find a free port: {}", e))?;
+    let port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    Ok(port)
+}
+
+// 32 random bytes, hex encoded.
+fn new_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| e.to_string())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// Starts main.py on `port` with its output captured into the sidecar log.
+// The port and token are passed in the environment, which unlike the
+// command line isn't visible to other users.
+fn spawn_sidecar(app: &AppHandle, port: u16, token: &str) -> Result<Child, String> {
+    let python_executable = venv_python(&venv_dir()?);
+    let script_path = scripts_dir()?.join("main.py");
+
+    let mut sidecar = command(python_executable);
+    sidecar
+        .arg(script_path)
+        // Never written to: main.py exits when it closes, which happens
+        // however the app goes away
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Otherwise stdout reaches the log in blocks rather than lines
+        .env("PYTHONUNBUFFERED", "1")
+        .env("SQLMAN_AI_WATCH_STDIN", "1")
+        .env("SQLMAN_AI_PORT", port.to_string())
+        .env("SQLMAN_AI_TOKEN", token);
+    // A group of its own, so the server and any workers it forks can be
+    // signalled together
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        sidecar.process_group(0);
+    }
+    let mut child = sidecar
+        .spawn()
+        .map_err(|e| format!("Failed to start Python process: {}", e))?;
+
+    let logs = app.state::<SidecarLogStore>();
+    if let Some(stdout) = child.stdout.take() {
+        logs.capture(app, LogStream::Stdout, stdout);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        logs.capture(app, LogStream::Stderr, stderr);
+    }
+    Ok(child)
+}
+
+fn log(app: &AppHandle, line: &str) {
+    app.state::<SidecarLogStore>()
+        .push(app, LogStream::Supervisor, line);
+}
+
+// Readiness probe: `POST /ping` answered with 200.
+fn probe(port: u16, token: &str) -> bool {
+    post(port, token, "/ping") == Some(200)
+}
+
+// Sends an empty POST to the service and returns the response status.
+fn post(port: u16, token: &str, path: &str) -> Option<u16> {
+    let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).ok()?;
+    let _ = stream.set_read_timeout(Some(PROBE_TIMEOUT));
+    let _ = stream.set_write_timeout(Some(PROBE_TIMEOUT));
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nAuthorization: Bearer {}\r\n\
+         Content-Length: 0\r\nConnection: close\r\n\r\n",
+        path, addr, token
+    );
+    stream.write_all(request.as_bytes()).ok()?;
+
+    // `HTTP/1.1 200 OK`
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line).ok()?;
+    status_line.split_whitespace().nth(1)?.parse().ok()
+}
+
+// Starts the service under supervision and waits for it to answer the
+// readiness probe. If it crashes it is restarted in the background, with
+// `sidecar-state` reporting each attempt.
+#[tauri::command]
+pub async fn start_python_script(
+    app: AppHandle,
+    sidecar: State<'_, Sidecar>,
+) -> Result<String, String> {
+    sidecar.start(&app)?;
+
+    let status = async_runtime::spawn_blocking(move || {
+        app.state::<Sidecar>()
+            .wait_started(READY_TIMEOUT + PROBE_TIMEOUT)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    match status.state {
+        SidecarState::Ready => Ok("FastAPI SQLCoder service started successfully".to_string()),
+        _ => Err(format!(
+            "FastAPI SQLCoder service failed to start: {}",
+            status
+                .last_error
+                .unwrap_or_else(|| "unknown error".to_string())
+        )),
+    }
+}
+
+#[tauri::command]
+pub async fn stop_python_script(app: AppHandle) -> Result<String, String> {
+    let stopped = async_runtime::spawn_blocking(move || app.state::<Sidecar>().stop(&app))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    match stopped {
+        Some(true) => Ok("FastAPI SQLCoder service stopped successfully".to_string()),
+        Some(false) => Ok("FastAPI SQLCoder service forcefully terminated".to_string()),
+        None => Err("No Python process is running".to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn sidecar_status(sidecar: State<'_, Sidecar>) -> Result<SidecarStatus, String> {
+    sidecar.status()
+}
+
+#[tauri::command]
+pub fn ai_endpoint(sidecar: State<'_, Sidecar>) -> Result<Endpoint, String> {
+    sidecar.endpoint()
+}
@@ -0,0 +1,174 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::SystemTime;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::history::epoch_millis;
+use crate::pythonjobs::for_each_line;
+
+const LOG_FILE: &str = "sidecar.log";
+
+// The log rotates to sidecar.1.log ... sidecar.N.log once it reaches
+// MAX_FILE_SIZE, so at most (ROTATED_FILES + 1) * MAX_FILE_SIZE is kept.
+const MAX_FILE_SIZE: u64 = 5 * 1024 * 1024;
+const ROTATED_FILES: usize = 3;
+
+// Lines kept in memory for `get_sidecar_logs`
+const BUFFER_LINES: usize = 2000;
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    // Lines from the supervisor itself: starts, exits and restarts
+    Supervisor,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LogLine {
+    timestamp: u64,
+    stream: LogStream,
+    line: String,
+}
+
+#[derive(Serialize)]
+pub struct SidecarLogs {
+    // The current log file; older output is in its rotated siblings
+    file: String,
+    lines: Vec<LogLine>,
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+// Output of the sidecar, kept in a ring buffer and appended to a rotating
+// file in the app log directory. Each line is also emitted on `sidecar-log`.
+pub struct SidecarLogStore {
+    dir: PathBuf,
+    buffer: Mutex<VecDeque<LogLine>>,
+    // Opened on the first line
+    file: Mutex<Option<LogFile>>,
+}
+
+impl SidecarLogStore {
+    pub fn new(dir: PathBuf) -> Self {
+        SidecarLogStore {
+            dir,
+            buffer: Mutex::new(VecDeque::with_capacity(BUFFER_LINES)),
+            file: Mutex::new(None),
+        }
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(LOG_FILE),
+            n => self.dir.join(format!("sidecar.{}.log", n)),
+        }
+    }
+
+    pub fn push(&self, app: &AppHandle, stream: LogStream, line: &str) {
+        let entry = LogLine {
+            timestamp: epoch_millis(SystemTime::now()),
+            stream,
+            line: line.to_string(),
+        };
+
+        // Logging must never take the sidecar down, so write failures only
+        // cost the file copy
+        let _ = self.write(&entry);
+        if let Ok(mut buffer) = self.buffer.lock() {
+            if buffer.len() == BUFFER_LINES {
+                buffer.pop_front();
+            }
+            buffer.push_back(entry.clone());
+        }
+        let _ = app.emit("sidecar-log", entry);
+    }
+
+    fn write(&self, entry: &LogLine) -> Result<(), String> {
+        let mut file = self.file.lock().map_err(|e| e.to_string())?;
+        if file.as_ref().is_some_and(|f| f.size >= MAX_FILE_SIZE) {
+            *file = None;
+            self.rotate()?;
+        }
+        if file.is_none() {
+            *file = Some(self.open()?);
+        }
+        let Some(log) = file.as_mut() else {
+            return Ok(());
+        };
+
+        let stream = match entry.stream {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+            LogStream::Supervisor => "supervisor",
+        };
+        let text = format!(
+            "{} [{}] {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            stream,
+            entry.line
+        );
+        log.file
+            .write_all(text.as_bytes())
+            .map_err(|e| e.to_string())?;
+        log.size += text.len() as u64;
+        Ok(())
+    }
+
+    fn open(&self) -> Result<LogFile, String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(0))
+            .map_err(|e| e.to_string())?;
+        let size = file.metadata().map_err(|e| e.to_string())?.len();
+        Ok(LogFile { file, size })
+    }
+
+    // sidecar.log becomes sidecar.1.log, which becomes sidecar.2.log, and
+    // so on; the oldest falls off the end.
+    fn rotate(&self) -> Result<(), String> {
+        let _ = fs::remove_file(self.path(ROTATED_FILES));
+        for index in (0..ROTATED_FILES).rev() {
+            let from = self.path(index);
+            if from.exists() {
+                fs::rename(&from, self.path(index + 1)).map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Reads `reader` line by line on its own thread until the pipe closes.
+    pub fn capture(&self, app: &AppHandle, stream: LogStream, reader: impl Read + Send + 'static) {
+        let app = app.clone();
+        thread::spawn(move || {
+            let logs = app.state::<SidecarLogStore>();
+            for_each_line(reader, |line| logs.push(&app, stream, &line));
+        });
+    }
+}
+
+// The most recent lines, oldest first. `limit` defaults to everything in the
+// buffer.
+#[tauri::command]
+pub fn get_sidecar_logs(
+    limit: Option<usize>,
+    logs: State<'_, SidecarLogStore>,
+) -> Result<SidecarLogs, String> {
+    let buffer = logs.buffer.lock().map_err(|e| e.to_string())?;
+    let skip = limit.map_or(0, |limit| buffer.len().saturating_sub(limit));
+    Ok(SidecarLogs {
+        file: logs.path(0).to_string_lossy().into_owned(),
+        lines: buffer.iter().skip(skip).cloned().collect(),
+    })
+}
@@ -0,0 +1,41 @@
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize, Clone)]
+pub struct QueryWarning {
+    pub level: String,
+    pub code: u32,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct ExecutionStats {
+    pub wall_time_ms: f64,
+    pub first_row_ms: Option<f64>,
+    pub server_time_ms: Option<f64>,
+    pub rows_returned: u64,
+    pub rows_affected: Option<u64>,
+    pub rows_examined: Option<u64>,
+    pub command_tag: Option<String>,
+    pub warnings: Vec<QueryWarning>,
+}
+
+#[derive(Serialize)]
+pub struct QueryResponse {
+    pub output: String,
+    pub stats: ExecutionStats,
+}
+
+impl ExecutionStats {
+    // Records time-to-first-row the first time it is called.
+    pub fn mark_row(&mut self, started: Instant) {
+        if self.first_row_ms.is_none() {
+            self.first_row_ms = Some(millis(started.elapsed()));
+        }
+        self.rows_returned += 1;
+    }
+}
+
+pub fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,561 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::dialect;
+use crate::engine::{Connection, Engine, Session};
+use crate::import::{
+    ImportColumn, ImportOptions, MysqlLoadMethod, RejectWriter, TableLoader, PROGRESS_EVERY,
+};
+use crate::mysqlcmd;
+use crate::pgcmd;
+use crate::resultset::{Cell, ColumnInfo, RowSink};
+use crate::runtime::run_blocking;
+use crate::schema::{self, ColumnDef, ConstraintKind, IndexDef, TableSchema};
+
+// Longest identifier PostgreSQL keeps; longer names are silently truncated
+const PG_MAX_IDENTIFIER: usize = 63;
+
+#[derive(Deserialize)]
+pub struct CopyEndpoint {
+    engine: Engine,
+    // Another server; the engine's current connection when absent
+    connection_string: Option<String>,
+}
+
+impl CopyEndpoint {
+    fn same_database(&self, other: &CopyEndpoint) -> bool {
+        self.engine == other.engine && self.connection_string == other.connection_string
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TableMapping {
+    pub source: String,
+    // Defaults to the source name
+    pub target: Option<String>,
+}
+
+impl TableMapping {
+    fn target_table(&self) -> String {
+        self.target
+            .clone()
+            .filter(|t| !t.trim().is_empty())
+            .unwrap_or_else(|| self.source.clone())
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExistingTable {
+    // Leave the target alone and report an error for that table
+    #[default]
+    Fail,
+    Replace,
+    // Insert into the existing table as it is
+    Append,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct CopyOptions {
+    existing: ExistingTable,
+    batch_size: usize,
+    copy_indexes: bool,
+    mysql_method: MysqlLoadMethod,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        CopyOptions {
+            existing: ExistingTable::Fail,
+            batch_size: 1000,
+            copy_indexes: true,
+            mysql_method: MysqlLoadMethod::Insert,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct CopyProgress {
+    table: String,
+    rows: u64,
+    done: bool,
+}
+
+#[derive(Serialize, Default)]
+pub struct TableCopySummary {
+    pub source_table: String,
+    pub target_table: String,
+    pub created: bool,
+    pub source_rows: u64,
+    pub copied: u64,
+    pub rejected: u64,
+    pub target_rows: u64,
+    // Whether the target gained exactly the rows read from the source
+    pub verified: bool,
+    pub reject_path: Option<String>,
+    // Names of the indexes created on the target
+    pub indexes: Vec<String>,
+    pub warnings: Vec<String>,
+    // Set when the table couldn't be copied; the job moves on to the next one
+    pub error: Option<String>,
+}
+
+// Copies tables from the `source` connection to the `target` one: the target table is created with translated column types, the
+// primary key and auto-increment columns, rows are streamed across in
+// batches, secondary indexes are added afterwards and row counts are
+// compared at the end. Defaults, foreign keys and triggers aren't copied.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn copy_tables(
+    app: AppHandle,
+    source: CopyEndpoint,
+    target: CopyEndpoint,
+    tables: Vec<TableMapping>,
+    options: Option<CopyOptions>,
+    mysql_state: State<'_, mysqlcmd::DbConnection>,
+    pg_state: State<'_, pgcmd::DbConnection>,
+) -> Result<Vec<TableCopySummary>, String> {
+    let options = options.unwrap_or_default();
+    if tables.is_empty() {
+        return Err("No tables to copy".to_string());
+    }
+    // Replacing would drop the source and appending would double it
+    if source.same_database(&target) {
+        if let Some(mapping) = tables
+            .iter()
+            .find(|m| same_table(&m.source, &m.target_table(), source.engine))
+        {
+            return Err(format!(
+                "Table {} can't be copied onto itself",
+                mapping.source
+            ));
+        }
+    }
+
+    let reject_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("copy_rejects");
+    let current = |endpoint: &CopyEndpoint| match endpoint.connection_string {
+        Some(_) => Ok(None),
+        None => Session::open(endpoint.engine, &mysql_state, &pg_state).map(Some),
+    };
+    let source_session = current(&source)?;
+    let target_session = current(&target)?;
+
+    run_blocking(move || {
+        fs::create_dir_all(&reject_dir).map_err(|e| e.to_string())?;
+        let source = open_session(&source, source_session)?;
+        let target = open_session(&target, target_session)?;
+        let mut source_conn = source.connect()?;
+        let mut target_conn = target.connect()?;
+
+        let summaries = tables
+            .iter()
+            .map(|mapping| {
+                let target_table = mapping.target_table();
+                let reject_path = reject_dir
+                    .join(format!("{}.rejects.csv", target_table))
+                    .to_string_lossy()
+                    .into_owned();
+                let mut summary = TableCopySummary {
+                    source_table: mapping.source.clone(),
+                    target_table,
+                    ..Default::default()
+                };
+
+                let mut job = TableCopy {
+                    app: &app,
+                    source: &mut source_conn,
+                    target: &mut target_conn,
+                    target_session: &target,
+                    options: &options,
+                    summary: &mut summary,
+                };
+                if let Err(e) = job.run(reject_path) {
+                    summary.error = Some(e);
+                }
+                emit_progress(&app, &summary.source_table, summary.source_rows, true);
+                summary
+            })
+            .collect();
+        Ok(summaries)
+    })
+    .await
+}
+
+fn open_session(endpoint: &CopyEndpoint, session: Option<Session>) -> Result<Session, String> {
+    match (session, endpoint.connection_string.as_deref()) {
+        (Some(session), _) => Ok(session),
+        (None, Some(connection_string)) => {
+            Session::from_connection_string(endpoint.engine, connection_string, None)
+        }
+        (None, None) => Err("Database not connected".to_string()),
+    }
+}
+
+// MySQL table names are case-insensitive on some platforms, so names that
+// differ only in case are taken as the same table there
+fn same_table(source: &str, target: &str, engine: Engine) -> bool {
+    let (source, target) = (source.trim(), target.trim());
+    match engine {
+        Engine::Mysql => source.eq_ignore_ascii_case(target),
+        Engine::Postgres => source == target,
+    }
+}
+
+struct TableCopy<'a> {
+    app: &'a AppHandle,
+    source: &'a mut Connection,
+    target: &'a mut Connection,
+    target_session: &'a Session,
+    options: &'a CopyOptions,
+    summary: &'a mut TableCopySummary,
+}
+
+impl TableCopy<'_> {
+    fn run(&mut self, reject_path: String) -> Result<(), String> {
+        let from = self.source.engine();
+        let to = self.target.engine();
+        let table = schema::table_schema(self.source, &self.summary.source_table)?;
+        let target_table = self.summary.target_table.clone();
+
+        let exists = schema::table_exists(self.target, &target_table)?;
+        let mut rows_before = 0;
+        if exists {
+            match self.options.existing {
+                ExistingTable::Fail => {
+                    return Err(format!(
+                        "Table {} already exists on the target",
+                        target_table
+                    ))
+                }
+                ExistingTable::Replace => self.target.execute(&format!(
+                    "DROP TABLE {}",
+                    dialect::quote_table(&target_table, to)
+                ))?,
+                ExistingTable::Append => {
+                    rows_before = schema::row_count(self.target, &target_table)?
+                }
+            }
+        }
+
+        let types: Vec<String> = table
+            .columns
+            .iter()
+            .map(|c| dialect::translate_type(c, from, to, is_indexed(&table, &c.name)))
+            .collect();
+        if !exists || self.options.existing == ExistingTable::Replace {
+            self.target
+                .execute(&create_table_sql(&table, &types, &target_table, to))?;
+            self.summary.created = true;
+        }
+
+        // The loader takes a connection of its own; this one is kept for the
+        // indexes and counts afterwards
+        let load_options = ImportOptions {
+            table: target_table.clone(),
+            create_table: false,
+            batch_size: self.options.batch_size,
+            mysql_method: self.options.mysql_method,
+            reject_path: Some(reject_path.clone()),
+            ..Default::default()
+        };
+        let headers = table.columns.iter().map(|c| c.name.clone()).collect();
+        let mut sink = CopySink {
+            app: self.app,
+            table: &self.summary.source_table,
+            connection: Some(self.target_session.connect()?),
+            options: &load_options,
+            rejects: Some(RejectWriter::new(reject_path, headers)),
+            bool_columns: types.iter().map(|t| t == "BOOLEAN").collect(),
+            types: &types,
+            loader: None,
+            rows: 0,
+        };
+        self.source.stream(
+            &select_sql(&table, &self.summary.source_table, from),
+            &mut sink,
+        )?;
+
+        let rows = sink.rows;
+        let loader = sink
+            .loader
+            .ok_or_else(|| "The source query returned no result set".to_string())?;
+        let loaded = loader.finish(rows)?;
+        self.summary.source_rows = rows;
+        self.summary.copied = loaded.imported;
+        self.summary.rejected = loaded.rejected;
+        self.summary.reject_path = loaded.reject_path;
+
+        if self.summary.created {
+            if self.options.copy_indexes {
+                self.create_indexes(&table, &target_table, from, to);
+            }
+            if to == Engine::Postgres {
+                self.reset_identities(&table, &target_table, &types);
+            }
+        }
+
+        self.summary.target_rows = schema::row_count(self.target, &target_table)?;
+        self.summary.verified = self.summary.target_rows == rows_before + rows;
+        Ok(())
+    }
+
+    fn create_indexes(
+        &mut self,
+        table: &TableSchema,
+        target_table: &str,
+        from: Engine,
+        to: Engine,
+    ) {
+        let indexes = secondary_indexes(table);
+        for index in &indexes {
+            let name = index_name(&index.name, &table.name, target_table, to);
+            let sql = format!(
+                "CREATE {}INDEX {} ON {} ({})",
+                if index.unique { "UNIQUE " } else { "" },
+                dialect::quote_ident(&name, to),
+                dialect::quote_table(target_table, to),
+                dialect::column_list(&index.columns, to)
+            );
+            match self.target.execute(&sql) {
+                Ok(()) => self.summary.indexes.push(name),
+                Err(e) => self
+                    .summary
+                    .warnings
+                    .push(format!("Index {} was not created: {}", index.name, e)),
+            }
+        }
+        if from != to && indexes.is_empty() && table.primary_key.is_empty() {
+            self.summary
+                .warnings
+                .push("The source table has no primary key or indexes".to_string());
+        }
+    }
+
+    // Explicit values were inserted into the identity columns, so their
+    // sequences would otherwise start handing out values already taken.
+    fn reset_identities(&mut self, table: &TableSchema, target_table: &str, types: &[String]) {
+        let quoted_table = dialect::quote_table(target_table, Engine::Postgres);
+        for (column, sql_type) in table.columns.iter().zip(types) {
+            if !is_identity(column, sql_type) {
+                continue;
+            }
+            let quoted = dialect::quote_ident(&column.name, Engine::Postgres);
+            let sql = format!(
+                "SELECT setval(pg_get_serial_sequence({}, {}), COALESCE(MAX({}), 0) + 1, false) FROM {}",
+                dialect::quote_string(&quoted_table, Engine::Postgres),
+                dialect::quote_string(&column.name, Engine::Postgres),
+                quoted,
+                quoted_table
+            );
+            if let Err(e) = self.target.execute(&sql) {
+                self.summary
+                    .warnings
+                    .push(format!("Sequence for {} was not reset: {}", column.name, e));
+            }
+        }
+    }
+}
+
+// The source's secondary indexes, with PostgreSQL unique constraints
+// recreated as unique indexes
+fn secondary_indexes(table: &TableSchema) -> Vec<IndexDef> {
+    let unique = table
+        .constraints
+        .iter()
+        .filter(|c| c.kind == ConstraintKind::Unique)
+        .map(|c| IndexDef {
+            name: c.name.clone(),
+            columns: c.columns.clone(),
+            unique: true,
+        });
+    table.indexes.iter().cloned().chain(unique).collect()
+}
+
+fn is_indexed(table: &TableSchema, column: &str) -> bool {
+    table.primary_key.iter().any(|c| c == column)
+        || secondary_indexes(table)
+            .iter()
+            .any(|index| index.columns.iter().any(|c| c == column))
+}
+
+fn is_identity(column: &ColumnDef, sql_type: &str) -> bool {
+    column.auto_increment
+        && matches!(
+            sql_type.to_lowercase().as_str(),
+            "smallint" | "integer" | "bigint"
+        )
+}
+
+fn create_table_sql(
+    table: &TableSchema,
+    types: &[String],
+    target_table: &str,
+    to: Engine,
+) -> String {
+    let mut definitions: Vec<String> = table
+        .columns
+        .iter()
+        .zip(types)
+        .map(|(column, sql_type)| {
+            let mut definition =
+                format!("  {} {}", dialect::quote_ident(&column.name, to), sql_type);
+            if !column.nullable {
+                definition.push_str(" NOT NULL");
+            }
+            if column.auto_increment {
+                match to {
+                    // Same-engine copies keep the declared type, which for a
+                    // serial column is a plain integer
+                    Engine::Postgres if is_identity(column, sql_type) => {
+                        definition.push_str(" GENERATED BY DEFAULT AS IDENTITY")
+                    }
+                    // MySQL only allows AUTO_INCREMENT on the first column of
+                    // a key
+                    Engine::Mysql if leads_key(table, &column.name) => {
+                        definition.push_str(" AUTO_INCREMENT")
+                    }
+                    _ => {}
+                }
+            }
+            definition
+        })
+        .collect();
+
+    if !table.primary_key.is_empty() {
+        definitions.push(format!(
+            "  PRIMARY KEY ({})",
+            dialect::column_list(&table.primary_key, to)
+        ));
+    }
+    format!(
+        "CREATE TABLE {} (\n{}\n)",
+        dialect::quote_table(target_table, to),
+        definitions.join(",\n")
+    )
+}
+
+fn leads_key(table: &TableSchema, column: &str) -> bool {
+    table.primary_key.first().is_some_and(|c| c == column)
+        || secondary_indexes(table)
+            .iter()
+            .any(|index| index.columns.first().is_some_and(|c| c == column))
+}
+
+// MySQL index names are per table, PostgreSQL's per schema, so names on a
+// PostgreSQL target carry the table name.
+fn index_name(name: &str, source_table: &str, target_table: &str, to: Engine) -> String {
+    if to == Engine::Mysql {
+        return name.to_string();
+    }
+    let (_, target_name) = schema::split_table_name(target_table);
+    let name = match name.strip_prefix(source_table) {
+        Some(rest) => format!("{}{}", target_name, rest),
+        None => format!("{}_{}", target_name, name),
+    };
+    match name.char_indices().nth(PG_MAX_IDENTIFIER) {
+        Some((end, _)) => name[..end].to_string(),
+        None => name,
+    }
+}
+
+fn select_sql(table: &TableSchema, source_table: &str, from: Engine) -> String {
+    let columns: Vec<String> = table
+        .columns
+        .iter()
+        .map(|column| dialect::select_column(column, from))
+        .collect();
+    format!(
+        "SELECT {} FROM {}",
+        columns.join(", "),
+        dialect::quote_table(source_table, from)
+    )
+}
+
+// Feeds the streamed source rows to a loader on the target. The loader is
+// made once the column kinds are known from the first result set.
+struct CopySink<'a> {
+    app: &'a AppHandle,
+    table: &'a str,
+    connection: Option<Connection>,
+    options: &'a ImportOptions,
+    rejects: Option<RejectWriter>,
+    types: &'a [String],
+    // Target columns that are BOOLEAN where the source had integers
+    bool_columns: Vec<bool>,
+    loader: Option<TableLoader>,
+    rows: u64,
+}
+
+impl RowSink for CopySink<'_> {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String> {
+        let columns: Vec<ImportColumn> = columns
+            .iter()
+            .zip(self.types)
+            .map(|(column, sql_type)| ImportColumn {
+                source: column.name.clone(),
+                target: column.name.clone(),
+                kind: column.kind,
+                sql_type: sql_type.clone(),
+            })
+            .collect();
+        let (Some(connection), Some(rejects)) = (self.connection.take(), self.rejects.take())
+        else {
+            return Err("Unexpected second result set".to_string());
+        };
+        self.loader = Some(TableLoader::new(
+            connection,
+            self.options,
+            &columns,
+            rejects,
+        )?);
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        let Some(loader) = self.loader.as_mut() else {
+            return Err("Row received before the columns".to_string());
+        };
+        self.rows += 1;
+
+        let cells: Vec<Cell> = row
+            .into_iter()
+            .zip(&self.bool_columns)
+            .map(|(cell, &is_bool)| match cell {
+                Cell::Int(i) if is_bool => Cell::Bool(i != 0),
+                Cell::UInt(u) if is_bool => Cell::Bool(u != 0),
+                other => other,
+            })
+            .collect();
+        let raw = cells
+            .iter()
+            .map(|cell| cell.to_text().unwrap_or_default())
+            .collect();
+        loader.push(self.rows, cells, raw)?;
+
+        if self.rows % PROGRESS_EVERY == 0 {
+            emit_progress(self.app, self.table, self.rows, false);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+fn emit_progress(app: &AppHandle, table: &str, rows: u64, done: bool) {
+    let _ = app.emit(
+        "copy-progress",
+        CopyProgress {
+            table: table.to_string(),
+            rows,
+            done,
+        },
+    );
+}
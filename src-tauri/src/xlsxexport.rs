@@ -0,0 +1,146 @@
+use chrono::Datelike;
+use rust_xlsxwriter::{ColNum, Format, RowNum, Workbook, XlsxError};
+
+use crate::resultset::{Cell, ColumnInfo, RowSink};
+
+// Excel's hard limits
+const MAX_ROWS: RowNum = 1_048_576;
+const MAX_COLUMNS: usize = 16_384;
+const MAX_STRING_CHARS: usize = 32_767;
+// Integers wider than this lose digits as an Excel number
+const MAX_EXACT_DIGITS: usize = 15;
+
+// One worksheet per result set with a bold, frozen header row and typed
+// cells. Sheets use constant-memory mode, which flushes each row to a temp
+// file, so large results aren't held in memory.
+pub struct XlsxSink {
+    path: String,
+    workbook: Workbook,
+    header: Format,
+    date: Format,
+    datetime: Format,
+    sheets: usize,
+    row: RowNum,
+}
+
+impl XlsxSink {
+    pub fn new(path: &str) -> Self {
+        XlsxSink {
+            path: path.to_string(),
+            workbook: Workbook::new(),
+            header: Format::new().set_bold(),
+            date: Format::new().set_num_format("yyyy-mm-dd"),
+            datetime: Format::new().set_num_format("yyyy-mm-dd hh:mm:ss"),
+            sheets: 0,
+            row: 0,
+        }
+    }
+
+    fn write_cell(&mut self, col: ColNum, cell: Cell) -> Result<(), XlsxError> {
+        let row = self.row;
+        let sheet = self.workbook.worksheet_from_index(self.sheets - 1)?;
+
+        match cell {
+            Cell::Null => {}
+            Cell::Bool(b) => {
+                sheet.write_boolean(row, col, b)?;
+            }
+            Cell::Int(i) if i.unsigned_abs().to_string().len() <= MAX_EXACT_DIGITS => {
+                sheet.write_number(row, col, i as f64)?;
+            }
+            Cell::UInt(u) if u.to_string().len() <= MAX_EXACT_DIGITS => {
+                sheet.write_number(row, col, u as f64)?;
+            }
+            Cell::Float(f) if f.is_finite() => {
+                sheet.write_number(row, col, f)?;
+            }
+            Cell::Decimal(ref d) if is_exact_number(d) => {
+                sheet.write_number(row, col, d.parse::<f64>().unwrap_or_default())?;
+            }
+            // Excel's date system starts in 1900
+            Cell::Date(d) if (1900..=9999).contains(&d.year()) => {
+                sheet.write_datetime_with_format(row, col, d, &self.date)?;
+            }
+            Cell::DateTime(dt) if (1900..=9999).contains(&dt.year()) => {
+                sheet.write_datetime_with_format(row, col, dt, &self.datetime)?;
+            }
+            // Excel has no time zones; written as UTC
+            Cell::TimestampTz(dt) if (1900..=9999).contains(&dt.year()) => {
+                sheet.write_datetime_with_format(row, col, dt.naive_utc(), &self.datetime)?;
+            }
+            other => {
+                let text = other.to_text().unwrap_or_default();
+                sheet.write_string(row, col, truncate(&text))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Whether a decimal's digits all survive conversion to an Excel number.
+fn is_exact_number(value: &str) -> bool {
+    let digits = value.chars().filter(char::is_ascii_digit).count();
+    digits <= MAX_EXACT_DIGITS && value.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+// Cells hold at most 32767 characters; longer text is cut rather than
+// failing the whole export.
+fn truncate(text: &str) -> &str {
+    match text.char_indices().nth(MAX_STRING_CHARS) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+fn xlsx_error(e: XlsxError) -> String {
+    format!("Excel export error: {}", e)
+}
+
+impl RowSink for XlsxSink {
+    fn start(&mut self, columns: &[ColumnInfo]) -> Result<(), String> {
+        if columns.len() > MAX_COLUMNS {
+            return Err(format!(
+                "Excel sheets hold at most {} columns; the result has {}",
+                MAX_COLUMNS,
+                columns.len()
+            ));
+        }
+
+        self.sheets += 1;
+        self.row = 1;
+
+        let sheet = self.workbook.add_worksheet_with_constant_memory();
+        sheet
+            .set_name(format!("Result {}", self.sheets))
+            .map_err(xlsx_error)?;
+        for (col, column) in columns.iter().enumerate() {
+            sheet
+                .write_string_with_format(0, col as ColNum, &column.name, &self.header)
+                .map_err(xlsx_error)?;
+        }
+        sheet.set_freeze_panes(1, 0).map_err(xlsx_error)?;
+        Ok(())
+    }
+
+    fn row(&mut self, row: Vec<Cell>) -> Result<(), String> {
+        if self.row >= MAX_ROWS {
+            return Err(format!(
+                "Excel sheets hold at most {} rows; export to CSV or Parquet instead",
+                MAX_ROWS - 1
+            ));
+        }
+        for (col, cell) in row.into_iter().enumerate() {
+            self.write_cell(col as ColNum, cell).map_err(xlsx_error)?;
+        }
+        self.row += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.workbook.save(&self.path).map_err(xlsx_error)
+    }
+
+    fn multiple_result_sets(&self) -> bool {
+        true
+    }
+}
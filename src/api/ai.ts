@@ -4,42 +4,31 @@ function delay(ms: number): Promise<void> {
   return new Promise((resolve) => setTimeout(resolve, ms));
 }
 
+type SidecarStatus = { state: "starting" | "ready" | "crashed" | "stopped" };
+
+type MessageResponse = { message: string };
+
+type GeneratedSql = { sql: string; tables: string[] };
+
 const setHfTokenAndRepo = async (token: string, repo: string) => {
   try {
-    const response = await fetch("http://localhost:8000/set_token_and_repo", {
-      method: "POST",
-      headers: {
-        "Content-Type": "application/json",
-      },
-      body: JSON.stringify({ token, repo }),
+    const result = await invoke<MessageResponse>("ai_set_repository", {
+      token,
+      repo,
     });
-    return response;
+    return { status: true, result };
   } catch (error) {
     console.log("Error setting token");
-  }
-};
-
-const ping = async () => {
-  try {
-    const response = await fetch("http://localhost:8000/ping", {
-      method: "POST",
-      headers: {
-        "Content-Type": "application/json",
-      },
-      body: "ping",
-    });
-    return response;
-  } catch (error) {
-    throw error;
+    return { status: false, error };
   }
 };
 
 const pingApi = async () => {
   for (let i = 0; i < 5; i++) {
     try {
-      const response = await ping();
-      if (response.ok) {
-        return response;
+      const status = await invoke<SidecarStatus>("sidecar_status");
+      if (status.state === "ready") {
+        return { ok: true };
       }
     } catch (error) {}
     await delay(1000);
@@ -93,49 +82,36 @@ async function start_python_script() {
 
 async function generateSQL(prompt: string) {
   try {
-    const response = await fetch("http://localhost:8000/generate_sql", {
-      method: "POST",
-      headers: {
-        "Content-Type": "application/json",
-      },
-      body: JSON.stringify({ prompt }),
-    });
-
-    if (!response.ok) {
-      throw new Error(`HTTP error! status: ${response.status}`);
-    }
-
-    const dataRaw = await response.json();
-    const data = dataRaw.split("```")[0];
-    return data;
+    const response = await invoke<GeneratedSql>("ai_generate_sql", { prompt });
+    return response.sql;
   } catch (error) {
     console.error("Error generating SQL:", error);
     throw error;
   }
 }
 
-async function setSchema(schema: Object) {
+async function setSchema(schema: { schema: Record<string, unknown> }) {
   console.log("Setting schema", schema);
   try {
-    const response = await fetch("http://localhost:8000/set_schema", {
-      method: "POST",
-      headers: {
-        "Content-Type": "application/json",
-      },
-      body: JSON.stringify(schema),
-    });
-
-    if (!response.ok) {
-      throw new Error(`HTTP error! status: ${response.status}`);
-    }
-
-    return response.json();
+    return await invoke<MessageResponse>("ai_set_schema", schema);
   } catch (error) {
     console.error("Error generating SQL:", error);
     throw error;
   }
 }
 
+async function listModels() {
+  return invoke<{ models: string[] }>("ai_list_models");
+}
+
+async function loadModel(modelName: string) {
+  return invoke<MessageResponse>("ai_load_model", { modelName });
+}
+
+async function unloadModel() {
+  return invoke<MessageResponse>("ai_unload_model");
+}
+
 export {
   pingApi,
   setHfTokenAndRepo,
@@ -146,4 +122,7 @@ export {
   start_python_script,
   generateSQL,
   setSchema,
+  listModels,
+  loadModel,
+  unloadModel,
 };
@@ -5,6 +5,8 @@ import {
   generateSQL,
   installPython,
   isVirtualEnvExisted,
+  listModels,
+  loadModel as requestModelLoad,
   pingApi,
   setHfTokenAndRepo,
   setSchema,
@@ -202,19 +204,14 @@ const AIProvider = ({ children }: { children: ReactNode }) => {
 
   const fetchModels = async () => {
     try {
-      const response = await fetch("http://localhost:8000/list_models");
-      const data = await response.json();
-      if (response.ok) {
-        const modelsItems = modelMap
-          .map((model) => (data.models.includes(model.model) ? model : null))
-          .filter((model) => model !== null);
-        setListOfModels(modelsItems);
-        setLoadingMessage("Models fetched successfully");
-      } else {
-        setError(data.detail || "Error fetching models");
-      }
+      const data = await listModels();
+      const modelsItems = modelMap
+        .map((model) => (data.models.includes(model.model) ? model : null))
+        .filter((model) => model !== null);
+      setListOfModels(modelsItems);
+      setLoadingMessage("Models fetched successfully");
     } catch (error) {
-      setError("Error fetching models");
+      setError((error as string) || "Error fetching models");
     }
   };
 
@@ -229,25 +226,13 @@ const AIProvider = ({ children }: { children: ReactNode }) => {
     const selectedModelStr = Array.from(selectedModel)[0];
 
     try {
-      const response = await fetch("http://localhost:8000/load_model", {
-        method: "POST",
-        headers: {
-          "Content-Type": "application/json",
-        },
-        body: JSON.stringify({ model_name: selectedModelStr }),
-      });
-      const data = await response.json();
-      if (response.ok) {
-        setLoadingMessage("Model loaded successfully");
-        setModelLoading(false);
-        setModel(selectedModelStr as string);
-        setIsModelReady(true);
-      } else {
-        setError(data.detail || "Error loading model");
-        setModelLoading(false);
-      }
+      await requestModelLoad(selectedModelStr as string);
+      setLoadingMessage("Model loaded successfully");
+      setModelLoading(false);
+      setModel(selectedModelStr as string);
+      setIsModelReady(true);
     } catch (error: any) {
-      setError("Error loading model: " + error.message);
+      setError("Error loading model: " + error);
       setModelLoading(false);
     }
   };
@@ -294,7 +279,7 @@ ORDER BY
         localStorage.getItem("selectedDatabase") || ""
       ),
     });
-    return res as string;
+    return (res as { output: string }).output;
   };
 
   const setSchematoModel = async () => {
@@ -12,6 +12,20 @@ interface Connection {
   isConnected: boolean;
 }
 
+interface QueryResponse {
+  output: string;
+  stats: {
+    wall_time_ms: number;
+    first_row_ms: number | null;
+    server_time_ms: number | null;
+    rows_returned: number;
+    rows_affected: number | null;
+    rows_examined: number | null;
+    command_tag: string | null;
+    warnings: { level: string; code: number; message: string }[];
+  };
+}
+
 type IhandleConnection = (
   dbType: string,
   host: string,
@@ -151,7 +165,7 @@ const DataProvider = ({ children }: { children: ReactNode }) => {
       query: query,
     })
       .then((r) => {
-        setRecentResults(r as string);
+        setRecentResults((r as QueryResponse).output);
         return r;
       })
       .catch((e) => {
@@ -182,7 +196,7 @@ ORDER BY
         localStorage.getItem("selectedDatabase") || ""
       ),
     });
-    return res as string;
+    return (res as QueryResponse).output;
   };
 
   return (